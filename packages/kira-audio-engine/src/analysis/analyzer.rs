@@ -0,0 +1,347 @@
+/*!
+ * AUDIO ANALYZER
+ *
+ * Hop-based analysis of a mono stream: every `hop_size` samples the last
+ * `fft_size` samples are windowed and transformed, band levels and the
+ * overall level are smoothed, and onsets are detected. Results are
+ * exposed as shader uniforms (`u_audio_*`) that any visual effect can
+ * bind to.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::{AnalysisError, AnalysisResult, Fft, OnsetDetector, WindowFunction};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+/// Seconds for the onset pulse uniform to decay by a factor of e
+const ONSET_DECAY_SECONDS: f32 = 0.15;
+
+/// Minimum time between reported onsets
+const ONSET_REFRACTORY_SECONDS: f32 = 0.05;
+
+/// Uniform name to value, ordered for stable iteration
+pub type ShaderUniforms = BTreeMap<String, f32>;
+
+/// A named frequency range, published as `u_audio_<name>`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrequencyBand {
+    pub name: String,
+    pub low: f32,
+    pub high: f32,
+}
+
+impl FrequencyBand {
+    pub fn new(name: &str, low: f32, high: f32) -> Self {
+        Self {
+            name: name.to_string(),
+            low,
+            high,
+        }
+    }
+}
+
+/// The conventional seven mixing bands from sub-bass to brilliance
+pub fn default_frequency_bands() -> Vec<FrequencyBand> {
+    vec![
+        FrequencyBand::new("sub_bass", 20.0, 60.0),
+        FrequencyBand::new("bass", 60.0, 250.0),
+        FrequencyBand::new("low_mid", 250.0, 500.0),
+        FrequencyBand::new("mid", 500.0, 2000.0),
+        FrequencyBand::new("high_mid", 2000.0, 4000.0),
+        FrequencyBand::new("presence", 4000.0, 6000.0),
+        FrequencyBand::new("brilliance", 6000.0, 20000.0),
+    ]
+}
+
+/// Analyzer configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnalysisSettings {
+    pub sample_rate: u32,
+    pub fft_size: usize,
+    /// Samples between successive analysis frames
+    pub hop_size: usize,
+    pub window: WindowFunction,
+    pub bands: Vec<FrequencyBand>,
+    /// Smoothing time constant for rising levels, in seconds
+    pub attack: f32,
+    /// Smoothing time constant for falling levels, in seconds
+    pub release: f32,
+    /// Level (dBFS) mapped to 0.0 in normalised outputs
+    pub floor_db: f32,
+}
+
+impl Default for AnalysisSettings {
+    fn default() -> Self {
+        Self {
+            sample_rate: crate::constants::SAMPLE_RATE,
+            fft_size: 2048,
+            hop_size: 512,
+            window: WindowFunction::default(),
+            bands: default_frequency_bands(),
+            attack: 0.01,
+            release: 0.25,
+            floor_db: -60.0,
+        }
+    }
+}
+
+/// Results of one analysis frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalysisFrame {
+    /// Smoothed RMS level, normalised 0.0-1.0
+    pub level: f32,
+    /// Peak absolute sample in the frame
+    pub peak: f32,
+    /// Smoothed band levels, normalised 0.0-1.0, in settings order
+    pub bands: Vec<f32>,
+    /// Spectral centroid in Hz
+    pub centroid: f32,
+    pub onset: bool,
+    /// Spectral flux of the frame
+    pub flux: f32,
+}
+
+/// Streaming FFT analyzer producing shader uniforms
+#[derive(Debug, Clone)]
+pub struct AudioAnalyzer {
+    settings: AnalysisSettings,
+    fft: Fft,
+    onsets: OnsetDetector,
+    /// Bin ranges for each band
+    band_bins: Vec<(usize, usize)>,
+    samples: VecDeque<f32>,
+    since_frame: usize,
+    level: f32,
+    bands: Vec<f32>,
+    onset_pulse: f32,
+    onset_count: u32,
+    latest: Option<AnalysisFrame>,
+}
+
+impl AudioAnalyzer {
+    pub fn new(settings: AnalysisSettings) -> AnalysisResult<Self> {
+        let fft = Fft::new(settings.fft_size, settings.window)?;
+        if settings.sample_rate == 0 || settings.hop_size == 0 || settings.hop_size > settings.fft_size {
+            return Err(AnalysisError::InvalidSettings(format!(
+                "sample rate {} with hop size {} for FFT size {}",
+                settings.sample_rate, settings.hop_size, settings.fft_size
+            )));
+        }
+        let valid_levels = settings.floor_db < 0.0 && settings.attack >= 0.0 && settings.release >= 0.0;
+        if !valid_levels {
+            return Err(AnalysisError::InvalidSettings(
+                "floor must be below 0 dB and smoothing times non-negative".to_string(),
+            ));
+        }
+        if let Some(band) = settings.bands.iter().find(|band| !(band.low >= 0.0 && band.low < band.high)) {
+            return Err(AnalysisError::InvalidSettings(format!("band {} has an empty range", band.name)));
+        }
+
+        let bin_width = settings.sample_rate as f32 / settings.fft_size as f32;
+        let last_bin = settings.fft_size / 2;
+        let band_bins = settings
+            .bands
+            .iter()
+            .map(|band| {
+                let low = ((band.low / bin_width).ceil() as usize).min(last_bin);
+                let high = ((band.high / bin_width).ceil() as usize).clamp(low, last_bin + 1);
+                (low, high)
+            })
+            .collect();
+
+        let refractory = (ONSET_REFRACTORY_SECONDS * settings.sample_rate as f32 / settings.hop_size as f32).ceil();
+
+        Ok(Self {
+            fft,
+            onsets: OnsetDetector::new(refractory as u32),
+            band_bins,
+            samples: VecDeque::from(vec![0.0; settings.fft_size]),
+            since_frame: 0,
+            level: 0.0,
+            bands: vec![0.0; settings.bands.len()],
+            onset_pulse: 0.0,
+            onset_count: 0,
+            latest: None,
+            settings,
+        })
+    }
+
+    pub fn settings(&self) -> &AnalysisSettings {
+        &self.settings
+    }
+
+    /// Most recent analysis frame
+    pub fn latest(&self) -> Option<&AnalysisFrame> {
+        self.latest.as_ref()
+    }
+
+    /// Onsets detected since the analyzer was created
+    pub fn onset_count(&self) -> u32 {
+        self.onset_count
+    }
+
+    /// Feed mono samples, returning a frame for every completed hop
+    pub fn process(&mut self, samples: &[f32]) -> Vec<AnalysisFrame> {
+        let mut frames = Vec::new();
+        for sample in samples {
+            self.samples.pop_front();
+            self.samples.push_back(*sample);
+            self.since_frame += 1;
+
+            if self.since_frame == self.settings.hop_size {
+                self.since_frame = 0;
+                let frame = self.analyse_frame();
+                self.latest = Some(frame.clone());
+                frames.push(frame);
+            }
+        }
+        frames
+    }
+
+    /// Feed mono samples and return the resulting shader uniforms
+    pub fn process_shader_uniforms(&mut self, samples: &[f32]) -> ShaderUniforms {
+        self.process(samples);
+        self.shader_uniforms()
+    }
+
+    /// Current values of every `u_audio_*` uniform, all in 0.0-1.0
+    /// except `u_audio_onset_count`
+    pub fn shader_uniforms(&self) -> ShaderUniforms {
+        let nyquist = self.settings.sample_rate as f32 / 2.0;
+        let (peak, centroid) = self
+            .latest
+            .as_ref()
+            .map_or((0.0, 0.0), |frame| (frame.peak.min(1.0), frame.centroid / nyquist));
+
+        let mut uniforms = ShaderUniforms::new();
+        uniforms.insert("u_audio_level".to_string(), self.level);
+        uniforms.insert("u_audio_peak".to_string(), peak);
+        uniforms.insert("u_audio_centroid".to_string(), centroid.clamp(0.0, 1.0));
+        uniforms.insert("u_audio_onset".to_string(), self.onset_pulse);
+        uniforms.insert("u_audio_onset_count".to_string(), self.onset_count as f32);
+        for (band, level) in self.settings.bands.iter().zip(&self.bands) {
+            uniforms.insert(format!("u_audio_{}", band.name), *level);
+        }
+        uniforms
+    }
+
+    fn analyse_frame(&mut self) -> AnalysisFrame {
+        let frame_seconds = self.settings.hop_size as f32 / self.settings.sample_rate as f32;
+        let floor_db = self.settings.floor_db;
+        let (attack, release) = (
+            smoothing_coefficient(frame_seconds, self.settings.attack),
+            smoothing_coefficient(frame_seconds, self.settings.release),
+        );
+        let smooth = |current: f32, target: f32| {
+            let coefficient = if target > current { attack } else { release };
+            current + (target - current) * coefficient
+        };
+
+        let window = self.samples.make_contiguous();
+        let peak = window.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        let rms = (window.iter().map(|s| s * s).sum::<f32>() / window.len() as f32).sqrt();
+        self.level = smooth(self.level, normalise_db(rms, floor_db));
+
+        let magnitudes = self.fft.magnitudes(window);
+
+        for ((low, high), level) in self.band_bins.iter().zip(&mut self.bands) {
+            let band_peak = magnitudes[*low..*high].iter().fold(0.0f32, |peak, m| peak.max(*m));
+            *level = smooth(*level, normalise_db(band_peak, floor_db));
+        }
+
+        let bin_width = self.settings.sample_rate as f32 / self.settings.fft_size as f32;
+        let total: f32 = magnitudes.iter().sum();
+        let centroid = if total > f32::EPSILON {
+            magnitudes.iter().enumerate().map(|(i, m)| i as f32 * bin_width * m).sum::<f32>() / total
+        } else {
+            0.0
+        };
+
+        let onset = self.onsets.process(magnitudes);
+        if onset {
+            self.onset_pulse = 1.0;
+            self.onset_count += 1;
+        } else {
+            self.onset_pulse *= (-frame_seconds / ONSET_DECAY_SECONDS).exp();
+        }
+
+        AnalysisFrame {
+            level: self.level,
+            peak,
+            bands: self.bands.clone(),
+            centroid,
+            onset,
+            flux: self.onsets.flux(),
+        }
+    }
+}
+
+/// One-pole smoothing coefficient for a time constant
+fn smoothing_coefficient(frame_seconds: f32, time_constant: f32) -> f32 {
+    if time_constant <= 0.0 {
+        1.0
+    } else {
+        1.0 - (-frame_seconds / time_constant).exp()
+    }
+}
+
+/// Map a linear amplitude onto 0.0-1.0 between `floor_db` and 0 dBFS
+fn normalise_db(amplitude: f32, floor_db: f32) -> f32 {
+    if amplitude <= 0.0 {
+        return 0.0;
+    }
+    ((20.0 * amplitude.log10() - floor_db) / -floor_db).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::TAU;
+
+    fn sine(frequency: f32, amplitude: f32, seconds: f32, sample_rate: u32) -> Vec<f32> {
+        (0..(seconds * sample_rate as f32) as usize)
+            .map(|i| (TAU * frequency * i as f32 / sample_rate as f32).sin() * amplitude)
+            .collect()
+    }
+
+    #[test]
+    fn test_band_levels_follow_the_spectrum() {
+        let mut analyzer = AudioAnalyzer::new(AnalysisSettings::default()).unwrap();
+        let uniforms = analyzer.process_shader_uniforms(&sine(100.0, 0.5, 0.5, 48000));
+
+        assert!(uniforms["u_audio_bass"] > 0.85);
+        assert!(uniforms["u_audio_brilliance"] < 0.1);
+        assert!(uniforms["u_audio_level"] > 0.8);
+        assert!(uniforms
+            .iter()
+            .filter(|(name, _)| *name != "u_audio_onset_count")
+            .all(|(_, value)| (0.0..=1.0).contains(value)));
+        assert_eq!(uniforms.len(), 5 + 7);
+
+        let centroid = analyzer.latest().unwrap().centroid;
+        assert!((centroid - 100.0).abs() < 50.0);
+    }
+
+    #[test]
+    fn test_onset_after_silence() {
+        let mut analyzer = AudioAnalyzer::new(AnalysisSettings::default()).unwrap();
+        analyzer.process(&vec![0.0; 9600]);
+        assert_eq!(analyzer.onset_count(), 0);
+
+        let frames = analyzer.process(&sine(440.0, 0.5, 0.5, 48000));
+        assert_eq!(analyzer.onset_count(), 1);
+        assert!(frames.iter().any(|frame| frame.onset));
+        assert!(analyzer.shader_uniforms()["u_audio_onset"] < 0.2);
+    }
+
+    #[test]
+    fn test_invalid_settings_rejected() {
+        let settings = AnalysisSettings {
+            hop_size: 4096,
+            ..AnalysisSettings::default()
+        };
+        assert!(AudioAnalyzer::new(settings).is_err());
+    }
+}
@@ -0,0 +1,163 @@
+/*!
+ * FFT
+ *
+ * In-place iterative radix-2 FFT with precomputed twiddles and window,
+ * so per-frame analysis does not allocate.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::{AnalysisError, AnalysisResult};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+/// Window applied to each frame before the transform
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WindowFunction {
+    Rectangular,
+    #[default]
+    Hann,
+    Hamming,
+    Blackman,
+}
+
+impl WindowFunction {
+    /// Window coefficient for sample `index` of `size`
+    pub fn coefficient(&self, index: usize, size: usize) -> f32 {
+        let x = TAU * index as f32 / size as f32;
+        match self {
+            WindowFunction::Rectangular => 1.0,
+            WindowFunction::Hann => 0.5 - 0.5 * x.cos(),
+            WindowFunction::Hamming => 0.54 - 0.46 * x.cos(),
+            WindowFunction::Blackman => 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos(),
+        }
+    }
+}
+
+/// Magnitude spectrum calculator for frames of a fixed size
+#[derive(Debug, Clone)]
+pub struct Fft {
+    size: usize,
+    window: Vec<f32>,
+    /// Scales magnitudes so a full-scale sine peaks at 1.0
+    normalisation: f32,
+    twiddles: Vec<(f32, f32)>,
+    real: Vec<f32>,
+    imaginary: Vec<f32>,
+    magnitudes: Vec<f32>,
+}
+
+impl Fft {
+    pub fn new(size: usize, window: WindowFunction) -> AnalysisResult<Self> {
+        if !size.is_power_of_two() || !(16..=65536).contains(&size) {
+            return Err(AnalysisError::InvalidFftSize(size));
+        }
+
+        let window: Vec<f32> = (0..size).map(|i| window.coefficient(i, size)).collect();
+        let normalisation = 2.0 / window.iter().sum::<f32>();
+        let twiddles = (0..size / 2)
+            .map(|k| {
+                let angle = -TAU * k as f32 / size as f32;
+                (angle.cos(), angle.sin())
+            })
+            .collect();
+
+        Ok(Self {
+            size,
+            window,
+            normalisation,
+            twiddles,
+            real: vec![0.0; size],
+            imaginary: vec![0.0; size],
+            magnitudes: vec![0.0; size / 2 + 1],
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Centre frequency of bin `index`
+    pub fn bin_frequency(&self, index: usize, sample_rate: u32) -> f32 {
+        index as f32 * sample_rate as f32 / self.size as f32
+    }
+
+    /// Window `frame` and return its normalised magnitude spectrum (size/2 + 1 bins).
+    ///
+    /// Shorter frames are zero-padded; longer frames are truncated.
+    pub fn magnitudes(&mut self, frame: &[f32]) -> &[f32] {
+        for (index, (real, imaginary)) in self.real.iter_mut().zip(&mut self.imaginary).enumerate() {
+            *real = frame.get(index).copied().unwrap_or(0.0) * self.window[index];
+            *imaginary = 0.0;
+        }
+
+        self.transform();
+
+        for (index, magnitude) in self.magnitudes.iter_mut().enumerate() {
+            let (re, im) = (self.real[index], self.imaginary[index]);
+            *magnitude = (re * re + im * im).sqrt() * self.normalisation;
+        }
+        // DC and Nyquist have no mirrored half
+        self.magnitudes[0] *= 0.5;
+        self.magnitudes[self.size / 2] *= 0.5;
+
+        &self.magnitudes
+    }
+
+    fn transform(&mut self) {
+        let n = self.size;
+
+        // Bit-reversal permutation
+        let bits = n.trailing_zeros();
+        for i in 0..n {
+            let j = i.reverse_bits() >> (usize::BITS - bits);
+            if j > i {
+                self.real.swap(i, j);
+                self.imaginary.swap(i, j);
+            }
+        }
+
+        let mut length = 2;
+        while length <= n {
+            let stride = n / length;
+            for start in (0..n).step_by(length) {
+                for k in 0..length / 2 {
+                    let (wr, wi) = self.twiddles[k * stride];
+                    let (a, b) = (start + k, start + k + length / 2);
+                    let tr = self.real[b] * wr - self.imaginary[b] * wi;
+                    let ti = self.real[b] * wi + self.imaginary[b] * wr;
+                    self.real[b] = self.real[a] - tr;
+                    self.imaginary[b] = self.imaginary[a] - ti;
+                    self.real[a] += tr;
+                    self.imaginary[a] += ti;
+                }
+            }
+            length *= 2;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sine_peaks_at_its_bin_with_unit_magnitude() {
+        let mut fft = Fft::new(1024, WindowFunction::Hann).unwrap();
+        let frame: Vec<f32> = (0..1024).map(|i| (TAU * 32.0 * i as f32 / 1024.0).sin() * 0.5).collect();
+        let magnitudes = fft.magnitudes(&frame);
+
+        let peak = (0..magnitudes.len()).max_by(|a, b| magnitudes[*a].total_cmp(&magnitudes[*b])).unwrap();
+        assert_eq!(peak, 32);
+        assert!((magnitudes[32] - 0.5).abs() < 0.01);
+        assert!(magnitudes[100] < 1e-4);
+        assert_eq!(fft.bin_frequency(32, 48000), 1500.0);
+    }
+
+    #[test]
+    fn test_invalid_sizes_rejected() {
+        assert!(Fft::new(1000, WindowFunction::Hann).is_err());
+        assert!(Fft::new(8, WindowFunction::Hann).is_err());
+    }
+}
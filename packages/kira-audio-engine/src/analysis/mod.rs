@@ -0,0 +1,38 @@
+/*!
+ * AUDIO ANALYSIS
+ *
+ * Real-time analysis of the engine's output for audio-reactive visuals:
+ * windowed FFT, frequency band levels and onset detection, published as
+ * named shader uniforms.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ *
+ * Features:
+ * - Radix-2 FFT with Hann, Hamming, Blackman and rectangular windows
+ * - Smoothed band levels normalised to 0.0-1.0
+ * - Spectral-flux onset detection with adaptive threshold
+ * - Non-blocking tap on the synthesizer output
+ */
+
+mod analyzer;
+mod fft;
+mod onset;
+mod tap;
+
+pub use analyzer::*;
+pub use fft::*;
+pub use onset::*;
+pub use tap::*;
+
+// Analysis-related error types
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum AnalysisError {
+    #[error("Invalid FFT size: {0} (must be a power of two, 16-65536)")]
+    InvalidFftSize(usize),
+
+    #[error("Invalid analysis settings: {0}")]
+    InvalidSettings(String),
+}
+
+pub type AnalysisResult<T> = std::result::Result<T, AnalysisError>;
@@ -0,0 +1,102 @@
+/*!
+ * ONSET DETECTION
+ *
+ * Spectral-flux onset detector: the rise in magnitude between frames is
+ * compared with an adaptive threshold from recent history, with a short
+ * refractory period so one hit is reported once.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use std::collections::VecDeque;
+
+/// Frames of flux history used for the adaptive threshold
+const FLUX_HISTORY: usize = 16;
+
+/// Flux must exceed the recent mean by this factor to count as an onset
+const THRESHOLD_RATIO: f32 = 1.5;
+
+/// Absolute flux floor, so near-silence never triggers
+const MINIMUM_FLUX: f32 = 0.01;
+
+/// Spectral-flux onset detector over successive magnitude spectra
+#[derive(Debug, Clone)]
+pub struct OnsetDetector {
+    previous: Vec<f32>,
+    history: VecDeque<f32>,
+    /// Frames to ignore after an onset
+    refractory_frames: u32,
+    frames_since_onset: u32,
+    last_flux: f32,
+}
+
+impl OnsetDetector {
+    pub fn new(refractory_frames: u32) -> Self {
+        Self {
+            previous: Vec::new(),
+            history: VecDeque::with_capacity(FLUX_HISTORY),
+            refractory_frames,
+            frames_since_onset: u32::MAX,
+            last_flux: 0.0,
+        }
+    }
+
+    /// Spectral flux of the most recent frame
+    pub fn flux(&self) -> f32 {
+        self.last_flux
+    }
+
+    /// Feed the next magnitude spectrum; returns true when it starts an onset
+    pub fn process(&mut self, magnitudes: &[f32]) -> bool {
+        if self.previous.len() != magnitudes.len() {
+            self.previous = vec![0.0; magnitudes.len()];
+        }
+
+        let flux: f32 = magnitudes
+            .iter()
+            .zip(&self.previous)
+            .map(|(current, previous)| (current - previous).max(0.0))
+            .sum();
+        self.previous.copy_from_slice(magnitudes);
+
+        let mean = if self.history.is_empty() {
+            0.0
+        } else {
+            self.history.iter().sum::<f32>() / self.history.len() as f32
+        };
+        if self.history.len() == FLUX_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(flux);
+        self.last_flux = flux;
+
+        self.frames_since_onset = self.frames_since_onset.saturating_add(1);
+        let is_onset = flux > MINIMUM_FLUX
+            && flux > mean * THRESHOLD_RATIO
+            && self.frames_since_onset > self.refractory_frames;
+        if is_onset {
+            self.frames_since_onset = 0;
+        }
+        is_onset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_onset_reported_once_per_hit() {
+        let mut detector = OnsetDetector::new(2);
+        let silence = vec![0.0; 64];
+        let mut tone = vec![0.0; 64];
+        tone[10] = 0.8;
+
+        let hits: Vec<bool> = [&silence, &silence, &tone, &tone, &tone, &silence, &silence, &tone]
+            .iter()
+            .map(|frame| detector.process(frame))
+            .collect();
+        assert_eq!(hits, vec![false, false, true, false, false, false, false, true]);
+    }
+}
@@ -0,0 +1,75 @@
+/*!
+ * ANALYSIS TAP
+ *
+ * Shared mono ring buffer that the audio thread writes its output into
+ * and the analysis side drains. The audio thread only ever `try_lock`s,
+ * so a busy reader costs a skipped buffer, never a blocked callback.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Mono samples kept when the reader falls behind
+pub const DEFAULT_TAP_CAPACITY: usize = 16384;
+
+/// Cloneable handle to a bounded mono sample buffer
+#[derive(Debug, Clone)]
+pub struct AnalysisTap {
+    buffer: Arc<Mutex<VecDeque<f32>>>,
+    capacity: usize,
+}
+
+impl Default for AnalysisTap {
+    fn default() -> Self {
+        Self::new(DEFAULT_TAP_CAPACITY)
+    }
+}
+
+impl AnalysisTap {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            buffer: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    /// Append interleaved audio, mixed to mono; dropped if the reader holds the lock
+    pub fn push_interleaved(&self, samples: &[f32], channels: usize) {
+        let Ok(mut buffer) = self.buffer.try_lock() else {
+            return;
+        };
+
+        let channels = channels.max(1);
+        for frame in samples.chunks(channels) {
+            if buffer.len() == self.capacity {
+                buffer.pop_front();
+            }
+            buffer.push_back(frame.iter().sum::<f32>() / frame.len() as f32);
+        }
+    }
+
+    /// Take every buffered sample
+    pub fn drain(&self) -> Vec<f32> {
+        match self.buffer.lock() {
+            Ok(mut buffer) => buffer.drain(..).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tap_downmixes_and_keeps_latest_samples() {
+        let tap = AnalysisTap::new(3);
+        tap.push_interleaved(&[1.0, 0.0, 0.5, 0.5, 0.0, 0.0, -1.0, -1.0], 2);
+        assert_eq!(tap.drain(), vec![0.5, 0.0, -1.0]);
+        assert!(tap.drain().is_empty());
+    }
+}
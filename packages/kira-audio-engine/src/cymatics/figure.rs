@@ -0,0 +1,171 @@
+/*!
+ * CHLADNI FIGURES
+ *
+ * Superposition of plate modes, rendered either as a greyscale texture
+ * bright along the nodal lines or as a sand point field: scattered
+ * grains are moved towards zero displacement with damped Newton steps,
+ * so they gather on the lines as real sand does.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::PlateMode;
+use serde::{Deserialize, Serialize};
+
+/// Half-width of a drawn nodal line, as a fraction of peak displacement
+pub const NODAL_LINE_WIDTH: f32 = 0.08;
+
+/// Fixed seed so the same figure always scatters the same sand
+const SAND_SEED: u64 = 0x0C1A_D4A1_0144_0099;
+
+/// Settling iterations per grain
+const SAND_STEPS: usize = 24;
+
+/// Fraction of each Newton step taken, to keep grains from overshooting lines
+const SAND_DAMPING: f32 = 0.5;
+
+/// Maximum distance a grain moves in one step
+const SAND_MAX_STEP: f32 = 0.02;
+
+/// Finite-difference step for the displacement gradient
+const GRADIENT_DELTA: f32 = 1e-3;
+
+/// Weighted plate modes sounding together
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChladniFigure {
+    /// Distinct modes with weights whose magnitudes sum to 1.0
+    pub components: Vec<(PlateMode, f32)>,
+}
+
+impl ChladniFigure {
+    /// Merge repeated modes and normalise the weights
+    pub fn from_modes(modes: &[(PlateMode, f32)]) -> Self {
+        let mut components: Vec<(PlateMode, f32)> = Vec::with_capacity(modes.len());
+        for (mode, weight) in modes {
+            match components.iter_mut().find(|(existing, _)| existing == mode) {
+                Some((_, total)) => *total += weight,
+                None => components.push((*mode, *weight)),
+            }
+        }
+
+        let total: f32 = components.iter().map(|(_, weight)| weight.abs()).sum();
+        if total > 0.0 {
+            for (_, weight) in &mut components {
+                *weight /= total;
+            }
+        }
+        Self { components }
+    }
+
+    /// Displacement at (x, y) in [0, 1]², within -2.0 to 2.0
+    pub fn displacement(&self, x: f32, y: f32) -> f32 {
+        self.components.iter().map(|(mode, weight)| mode.displacement(x, y) * weight).sum()
+    }
+
+    /// Row-major greyscale texture of `resolution`², 255 on nodal lines fading to 0
+    pub fn texture(&self, resolution: usize) -> Vec<u8> {
+        let samples = self.sample_grid(resolution);
+        let peak = samples.iter().fold(0.0f32, |peak, value| peak.max(value.abs()));
+        if peak == 0.0 {
+            return vec![0; samples.len()];
+        }
+
+        samples
+            .iter()
+            .map(|value| {
+                let intensity = (1.0 - value.abs() / (peak * NODAL_LINE_WIDTH)).max(0.0);
+                (intensity * 255.0).round() as u8
+            })
+            .collect()
+    }
+
+    /// `count` sand grains settled onto the nodal lines, as (x, y) in [0, 1]²
+    pub fn sand_points(&self, count: usize) -> Vec<(f32, f32)> {
+        let mut state = SAND_SEED;
+        let mut next_unit = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 40) as f32 / (1u32 << 24) as f32
+        };
+
+        (0..count)
+            .map(|_| {
+                let (x, y) = (next_unit(), next_unit());
+                self.settle(x, y)
+            })
+            .collect()
+    }
+
+    fn settle(&self, mut x: f32, mut y: f32) -> (f32, f32) {
+        for _ in 0..SAND_STEPS {
+            let z = self.displacement(x, y);
+            let gx = (self.displacement(x + GRADIENT_DELTA, y) - self.displacement(x - GRADIENT_DELTA, y))
+                / (2.0 * GRADIENT_DELTA);
+            let gy = (self.displacement(x, y + GRADIENT_DELTA) - self.displacement(x, y - GRADIENT_DELTA))
+                / (2.0 * GRADIENT_DELTA);
+            let slope = gx * gx + gy * gy;
+            if slope < 1e-9 {
+                break;
+            }
+
+            // Newton step to the zero of the local linearisation, damped and capped
+            let (mut dx, mut dy) = (-z * gx / slope * SAND_DAMPING, -z * gy / slope * SAND_DAMPING);
+            let length = (dx * dx + dy * dy).sqrt();
+            if length > SAND_MAX_STEP {
+                dx *= SAND_MAX_STEP / length;
+                dy *= SAND_MAX_STEP / length;
+            }
+            x = (x + dx).clamp(0.0, 1.0);
+            y = (y + dy).clamp(0.0, 1.0);
+        }
+        (x, y)
+    }
+
+    fn sample_grid(&self, resolution: usize) -> Vec<f32> {
+        let scale = if resolution > 1 { 1.0 / (resolution - 1) as f32 } else { 0.0 };
+        (0..resolution * resolution)
+            .map(|index| {
+                let (row, column) = (index / resolution, index % resolution);
+                self.displacement(column as f32 * scale, row as f32 * scale)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeated_modes_merge_and_normalise() {
+        let mode = PlateMode { n: 3, m: 1 };
+        let figure = ChladniFigure::from_modes(&[(mode, 0.5), (PlateMode { n: 4, m: 2 }, 1.0), (mode, 0.5)]);
+        assert_eq!(figure.components, vec![(mode, 0.5), (PlateMode { n: 4, m: 2 }, 0.5)]);
+    }
+
+    #[test]
+    fn test_texture_is_bright_on_the_nodal_diagonal() {
+        let figure = ChladniFigure::from_modes(&[(PlateMode { n: 4, m: 2 }, 1.0)]);
+        let texture = figure.texture(33);
+        assert_eq!(texture.len(), 33 * 33);
+        for i in 0..33 {
+            assert_eq!(texture[i * 33 + i], 255);
+        }
+        assert_eq!(texture[16 * 33], 0);
+    }
+
+    #[test]
+    fn test_sand_settles_on_nodal_lines() {
+        let figure = ChladniFigure::from_modes(&[(PlateMode { n: 5, m: 2 }, 1.0)]);
+        let points = figure.sand_points(200);
+        assert_eq!(points, figure.sand_points(200));
+
+        let settled = points
+            .iter()
+            .filter(|(x, y)| figure.displacement(*x, *y).abs() < 0.05)
+            .count();
+        assert!(settled >= 190, "only {} of 200 grains settled", settled);
+    }
+}
@@ -0,0 +1,34 @@
+/*!
+ * CYMATICS
+ *
+ * Chladni-plate simulation turning frequencies and tones into the nodal
+ * figures sand traces on a vibrating plate, for visualization alongside
+ * the tone generator.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ *
+ * Features:
+ * - Square-plate eigenmodes chosen by nearest resonant frequency
+ * - Multi-partial figures weighted by a tone's harmonic spectrum
+ * - Greyscale nodal-line textures at any resolution
+ * - Reproducible sand point fields settled onto the nodal lines
+ */
+
+mod figure;
+mod plate;
+
+pub use figure::*;
+pub use plate::*;
+
+// Cymatics-related error types
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum CymaticsError {
+    #[error("Invalid plate frequency: {0} Hz")]
+    InvalidFrequency(f64),
+
+    #[error("Invalid plate settings: {0}")]
+    InvalidPlate(String),
+}
+
+pub type CymaticsResult<T> = std::result::Result<T, CymaticsError>;
@@ -0,0 +1,184 @@
+/*!
+ * CHLADNI PLATE
+ *
+ * A square plate over [0, 1]² whose mode (n, m) has the displacement
+ * cos(nπx)·cos(mπy) − cos(mπx)·cos(nπy) and resonates at
+ * fundamental · (n² + m²). A driving frequency excites the mode whose
+ * resonance is nearest, as with a bowed or speaker-driven plate.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::{ChladniFigure, CymaticsError, CymaticsResult};
+use crate::synthesis::ToneSpec;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// Plate constant giving (4, 2) at 400 Hz, so the solfeggio range lands on clear mid-order figures
+pub const DEFAULT_PLATE_FUNDAMENTAL: f64 = 20.0;
+
+/// Highest mode index considered by default
+pub const DEFAULT_MAX_MODE: u32 = 24;
+
+/// Highest mode index a plate accepts; finer figures than this alias on any texture
+pub const MAX_PLATE_MODE: u32 = 256;
+
+/// One standing-wave mode of the plate, with `n > m`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PlateMode {
+    pub n: u32,
+    pub m: u32,
+}
+
+impl PlateMode {
+    /// Displacement at (x, y) in [0, 1]², within -2.0 to 2.0
+    pub fn displacement(&self, x: f32, y: f32) -> f32 {
+        let (n, m) = (self.n as f32 * PI, self.m as f32 * PI);
+        (n * x).cos() * (m * y).cos() - (m * x).cos() * (n * y).cos()
+    }
+}
+
+/// Square plate model mapping frequencies onto modes
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ChladniPlate {
+    /// Resonance scale in Hz: mode (n, m) sounds at fundamental · (n² + m²)
+    pub fundamental: f64,
+    /// Largest n considered, at most `MAX_PLATE_MODE`; higher frequencies use the highest available mode
+    pub max_mode: u32,
+}
+
+impl Default for ChladniPlate {
+    fn default() -> Self {
+        Self { fundamental: DEFAULT_PLATE_FUNDAMENTAL, max_mode: DEFAULT_MAX_MODE }
+    }
+}
+
+impl ChladniPlate {
+    pub fn new(fundamental: f64, max_mode: u32) -> CymaticsResult<Self> {
+        if !fundamental.is_finite() || fundamental <= 0.0 {
+            return Err(CymaticsError::InvalidPlate(format!("fundamental must be positive, got {}", fundamental)));
+        }
+        if !(1..=MAX_PLATE_MODE).contains(&max_mode) {
+            return Err(CymaticsError::InvalidPlate(format!(
+                "max_mode must be 1-{}, got {}",
+                MAX_PLATE_MODE, max_mode
+            )));
+        }
+        Ok(Self { fundamental, max_mode })
+    }
+
+    /// Resonant frequency of a mode
+    pub fn mode_frequency(&self, mode: PlateMode) -> f64 {
+        let (n, m) = (mode.n as f64, mode.m as f64);
+        self.fundamental * (n * n + m * m)
+    }
+
+    /// Mode whose resonance is nearest `frequency`; ties prefer the lower n, then the lower m
+    pub fn mode_for_frequency(&self, frequency: f64) -> CymaticsResult<PlateMode> {
+        if !frequency.is_finite() || frequency <= 0.0 {
+            return Err(CymaticsError::InvalidFrequency(frequency));
+        }
+
+        // For each n only the m either side of the exact solution can be nearest
+        let target = frequency / self.fundamental;
+        let mut best = PlateMode { n: 1, m: 0 };
+        for n in 1..=self.max_mode.min(MAX_PLATE_MODE) {
+            let exact = (target - (n as f64).powi(2)).max(0.0).sqrt();
+            let below = (exact.floor() as u32).min(n - 1);
+            let above = (exact.ceil() as u32).min(n - 1);
+            for m in [below, above] {
+                let mode = PlateMode { n, m };
+                if (self.mode_frequency(mode) - frequency).abs() < (self.mode_frequency(best) - frequency).abs() {
+                    best = mode;
+                }
+            }
+        }
+        Ok(best)
+    }
+
+    /// Figure for a pure tone
+    pub fn figure(&self, frequency: f64) -> CymaticsResult<ChladniFigure> {
+        Ok(ChladniFigure::from_modes(&[(self.mode_for_frequency(frequency)?, 1.0)]))
+    }
+
+    /// Figure for every partial of a tone, weighted by partial amplitude
+    pub fn figure_for_tone(&self, tone: &ToneSpec) -> CymaticsResult<ChladniFigure> {
+        let mut components = Vec::with_capacity(tone.partials.len());
+        for partial in &tone.partials {
+            components.push((self.mode_for_frequency(partial.frequency)?, partial.amplitude));
+        }
+        if components.is_empty() {
+            return self.figure(tone.fundamental);
+        }
+        Ok(ChladniFigure::from_modes(&components))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthesis::HarmonicRolloff;
+
+    #[test]
+    fn test_frequency_selects_nearest_mode() {
+        let plate = ChladniPlate::default();
+        assert_eq!(plate.mode_for_frequency(400.0).unwrap(), PlateMode { n: 4, m: 2 });
+        assert_eq!(plate.mode_for_frequency(396.0).unwrap(), PlateMode { n: 4, m: 2 });
+        assert_eq!(plate.mode_for_frequency(1.0).unwrap(), PlateMode { n: 1, m: 0 });
+        assert_eq!(plate.mode_for_frequency(1e6).unwrap(), PlateMode { n: 24, m: 23 });
+        assert!(plate.mode_for_frequency(-5.0).is_err());
+        assert!(ChladniPlate::new(0.0, 8).is_err());
+    }
+
+    #[test]
+    fn test_search_matches_exhaustive_scan() {
+        let plate = ChladniPlate::new(3.0, 40).unwrap();
+        for frequency in (1..400).map(|step| step as f64 * 37.5) {
+            let mut expected = PlateMode { n: 1, m: 0 };
+            for n in 1..=40 {
+                for m in 0..n {
+                    let mode = PlateMode { n, m };
+                    let distance = (plate.mode_frequency(mode) - frequency).abs();
+                    if distance < (plate.mode_frequency(expected) - frequency).abs() {
+                        expected = mode;
+                    }
+                }
+            }
+            assert_eq!(plate.mode_for_frequency(frequency).unwrap(), expected, "{} Hz", frequency);
+        }
+    }
+
+    #[test]
+    fn test_large_modes_are_capped() {
+        assert!(ChladniPlate::new(20.0, MAX_PLATE_MODE).is_ok());
+        assert!(ChladniPlate::new(20.0, 46341).is_err());
+
+        let mode = PlateMode { n: 70_000, m: 69_999 };
+        assert!(ChladniPlate::default().mode_frequency(mode) > 1e11);
+
+        let unchecked = ChladniPlate { fundamental: 20.0, max_mode: u32::MAX };
+        let highest = unchecked.mode_for_frequency(1e12).unwrap();
+        assert_eq!(highest, PlateMode { n: MAX_PLATE_MODE, m: MAX_PLATE_MODE - 1 });
+    }
+
+    #[test]
+    fn test_mode_has_nodal_diagonal() {
+        let mode = PlateMode { n: 5, m: 2 };
+        for step in 0..=10 {
+            let t = step as f32 / 10.0;
+            assert!(mode.displacement(t, t).abs() < 1e-6);
+        }
+        assert!(mode.displacement(0.0, 1.0).abs() > 1.0);
+    }
+
+    #[test]
+    fn test_tone_figure_has_one_component_per_distinct_mode() {
+        let plate = ChladniPlate::default();
+        let tone = ToneSpec::harmonic_series(400.0, 3, HarmonicRolloff::Inverse).unwrap();
+        let figure = plate.figure_for_tone(&tone).unwrap();
+        assert_eq!(figure.components.len(), 3);
+        assert_eq!(figure.components[0].0, PlateMode { n: 4, m: 2 });
+        assert!(figure.components[0].1 > figure.components[2].1);
+    }
+}
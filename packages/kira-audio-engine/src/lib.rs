@@ -16,19 +16,49 @@
  * - WebAssembly integration for web applications
  * - Godot GDNative bindings for game integration
  * - Professional effects processing chain
+ * - Strudel-style pattern language for rhythm and melody
+ * - Real-time polyphonic synthesis via cpal
+ * - Solfeggio and harmonic-series tones with offline WAV rendering
+ * - Binaural beat and isochronic entrainment sessions
+ * - Standard MIDI File export of pattern compositions
+ * - OSC client/server for SuperCollider and live-coding control
+ * - Instrument plugin hosting with VST3 discovery and loading
+ * - Offline WAV/FLAC bouncing with selectable sample rate and dithering
+ * - Sample-accurate scheduling with tempo maps and swing
+ * - FFT band levels and onset detection published as shader uniforms
+ * - Modes, chords and planetary/elemental correspondences for generated music
+ * - Chladni-plate cymatics figures for played tones
  */
 
+pub mod analysis;
 pub mod core;
+pub mod cymatics;
 pub mod engines;
 pub mod effects;
+pub mod midi;
+pub mod osc;
+pub mod pattern;
+pub mod plugin;
+pub mod render;
+pub mod scheduler;
 pub mod spatial;
 pub mod synthesis;
+pub mod theory;
 
+pub use analysis::*;
 pub use core::*;
+pub use cymatics::*;
 pub use engines::*;
 pub use effects::*;
+pub use midi::*;
+pub use osc::*;
+pub use pattern::*;
+pub use plugin::*;
+pub use render::*;
+pub use scheduler::*;
 pub use spatial::*;
 pub use synthesis::*;
+pub use theory::*;
 
 // Re-export commonly used types
 pub use kira::sound::SoundId;
@@ -136,6 +166,7 @@ pub fn start() {
 #[cfg(feature = "godot")]
 pub mod godot {
     use godot::prelude::*;
+    use godot::classes::AudioStreamGeneratorPlayback;
     use crate::*;
 
     /// GDNative wrapper for Kira Audio Engine
@@ -184,6 +215,373 @@ pub mod godot {
                 Err(_) => -1,
             }
         }
+
+        /// Start the real-time synthesizer on its own audio thread
+        #[func]
+        pub fn start_synth_godot(&mut self) -> i32 {
+            if self.synth.is_some() {
+                return 0;
+            }
+            match RealtimeSynth::start() {
+                Ok(synth) => {
+                    self.synth = Some(synth);
+                    0
+                },
+                Err(_) => -1,
+            }
+        }
+
+        /// Start a note on the real-time synthesizer, returning its voice ID
+        #[func]
+        pub fn synth_note_on_godot(&mut self, frequency: f64, velocity: f64, duration: f64) -> i64 {
+            let Some(synth) = self.synth.as_ref() else {
+                return -1;
+            };
+            let duration = if duration > 0.0 { Some(duration) } else { None };
+            match synth.controller().schedule_note(NoteSettings::new(frequency, velocity as f32), 0.0, duration) {
+                Ok(voice_id) => voice_id as i64,
+                Err(_) => -1,
+            }
+        }
+
+        /// Release a note started with `synth_note_on_godot`
+        #[func]
+        pub fn synth_note_off_godot(&mut self, voice_id: i64) -> i32 {
+            match self.synth.as_ref().map(|synth| synth.controller().note_off(voice_id as u64)) {
+                Some(Ok(())) => 0,
+                _ => -1,
+            }
+        }
+
+        /// Start a transport at `bpm`, with cycle 0 beginning 100 ms from now.
+        /// `swing` (0.0 up to 1.0) delays off-beat eighth notes.
+        #[func]
+        pub fn synth_set_tempo_godot(&mut self, bpm: f64, beats_per_cycle: i32, swing: f64) -> i32 {
+            let Some(synth) = self.synth.as_ref() else {
+                return -1;
+            };
+            let Ok(mut clock) = MusicalClock::new(bpm, synth.controller().sample_rate())
+                .and_then(|clock| clock.with_beats_per_cycle(beats_per_cycle.clamp(1, 255) as u8))
+            else {
+                return -1;
+            };
+            if swing > 0.0 {
+                match Swing::new(swing, clock.beats_per_cycle() as u32 * 2) {
+                    Ok(swing) => clock = clock.with_swing(swing),
+                    Err(_) => return -1,
+                }
+            }
+            self.transport = Some(synth.controller().start_transport(clock, 0.0, 0.1));
+            0
+        }
+
+        /// Current transport position in cycles, or -1 without a transport
+        #[func]
+        pub fn synth_current_cycle_godot(&self) -> f64 {
+            match (self.synth.as_ref(), self.transport.as_ref()) {
+                (Some(synth), Some(transport)) => transport.cycle_at(synth.controller().sample_clock()),
+                _ => -1.0,
+            }
+        }
+
+        /// Play one cycle of a mini-notation pattern on the real-time synthesizer.
+        /// With a transport the cycle plays at its place on the shared clock
+        /// (`cycles_per_second` is ignored); otherwise it starts immediately.
+        #[func]
+        pub fn synth_play_pattern_godot(&mut self, pattern: String, cycle: i64, cycles_per_second: f64) -> i32 {
+            let Some(synth) = self.synth.as_ref() else {
+                return -1;
+            };
+            let Ok(pattern) = Pattern::parse(&pattern) else {
+                return -1;
+            };
+            let events = pattern.query_cycle(cycle);
+            let template = NoteSettings::new(440.0, 0.8);
+            let result = match self.transport.as_ref() {
+                Some(transport) => synth.controller().play_scheduled(&transport.schedule(&events), template),
+                None => synth.controller().play_pattern_events(&events, cycles_per_second, template),
+            };
+            match result {
+                Ok(voice_ids) => voice_ids.len() as i32,
+                Err(_) => -1,
+            }
+        }
+
+        /// Play the solfeggio tone for a Codex 144:99 node for `duration` seconds
+        /// (at most `MAX_TONE_SECONDS`)
+        #[func]
+        pub fn play_codex_node_tone_godot(&mut self, node: i32, duration: f64) -> i32 {
+            let Some(synth) = self.synth.as_ref() else {
+                return -1;
+            };
+            if !(0.0..=MAX_TONE_SECONDS).contains(&duration) {
+                return -1;
+            }
+            let Ok(node) = u16::try_from(node) else {
+                return -1;
+            };
+            let Ok(tone) = ToneSpec::for_codex_node(node) else {
+                return -1;
+            };
+            match tone.play(synth.controller(), duration) {
+                Ok(_) => 0,
+                Err(_) => -1,
+            }
+        }
+
+        /// Chladni figure of a pure tone as a `resolution`² greyscale texture,
+        /// also playing the tone for `duration` seconds if the synth is started
+        /// (empty on error)
+        #[func]
+        pub fn play_cymatic_tone_godot(&mut self, frequency: f64, duration: f64, resolution: i32) -> PackedByteArray {
+            let Ok(tone) = ToneSpec::solfeggio(frequency) else {
+                return PackedByteArray::new();
+            };
+            let Ok(figure) = ChladniPlate::default().figure_for_tone(&tone) else {
+                return PackedByteArray::new();
+            };
+            if let Some(synth) = self.synth.as_ref() {
+                if tone.play(synth.controller(), duration).is_err() {
+                    return PackedByteArray::new();
+                }
+            }
+            PackedByteArray::from(figure.texture(resolution.clamp(1, 2048) as usize).as_slice())
+        }
+
+        /// Up to 100,000 sand grain positions (0.0-1.0 on each axis) settled on
+        /// the Chladni figure of a Codex 144:99 node's tone, harmonics included
+        #[func]
+        pub fn codex_node_cymatics_godot(node: i32, count: i32) -> PackedVector2Array {
+            let Ok(node) = u16::try_from(node) else {
+                return PackedVector2Array::new();
+            };
+            let Ok(tone) = ToneSpec::for_codex_node(node) else {
+                return PackedVector2Array::new();
+            };
+            match ChladniPlate::default().figure_for_tone(&tone) {
+                Ok(figure) => figure
+                    .sand_points(count.clamp(0, 100_000) as usize)
+                    .into_iter()
+                    .map(|(x, y)| Vector2::new(x, y))
+                    .collect(),
+                Err(_) => PackedVector2Array::new(),
+            }
+        }
+
+        /// Start a preset binaural or isochronic session, returning its voice ID.
+        /// Stop it early (with a fade) via `synth_note_off_godot`.
+        #[func]
+        pub fn play_entrainment_godot(&mut self, preset: String, isochronic: bool, duration: f64) -> i64 {
+            let Some(synth) = self.synth.as_ref() else {
+                return -1;
+            };
+            let Some(preset) = EntrainmentPreset::from_name(&preset) else {
+                return -1;
+            };
+            let mode = if isochronic { EntrainmentMode::Isochronic } else { EntrainmentMode::Binaural };
+            match EntrainmentSession::preset(preset, mode, duration) {
+                Ok(session) => match session.play(synth.controller()) {
+                    Ok(voice_id) => voice_id as i64,
+                    Err(_) => -1,
+                },
+                Err(_) => -1,
+            }
+        }
+
+        /// Play the diatonic triad on `degree` of the mode for a planet, element
+        /// or mode name, rooted on MIDI note `root`. Returns the voice IDs, which
+        /// `synth_note_off_godot` releases; with `duration` <= 0 the chord is held
+        /// until then (empty on error, with no voice left sounding)
+        #[func]
+        pub fn synth_play_correspondence_chord_godot(
+            &mut self,
+            name: String,
+            root: i32,
+            degree: i32,
+            duration: f64,
+        ) -> PackedInt64Array {
+            let Some(synth) = self.synth.as_ref() else {
+                return PackedInt64Array::new();
+            };
+            let Some(mode) = mode_for_correspondence(&name) else {
+                return PackedInt64Array::new();
+            };
+            // Ten octaves of a seven-note mode either side of the root covers the MIDI range
+            let Some(chord) = mode.scale(root.clamp(0, 127) as u8).triad(degree.clamp(-70, 70)) else {
+                return PackedInt64Array::new();
+            };
+            let duration = if duration > 0.0 { Some(duration) } else { None };
+            match chord.play(synth.controller(), NoteSettings::new(440.0, 0.6), duration) {
+                Ok(voice_ids) => voice_ids.into_iter().map(|voice_id| voice_id as i64).collect(),
+                Err(_) => PackedInt64Array::new(),
+            }
+        }
+
+        /// Export `cycles` cycles (1 to `MAX_QUERY_CYCLES`) of a mini-notation pattern to a MIDI file
+        #[func]
+        pub fn export_pattern_midi_godot(pattern: String, cycles: i64, bpm: f64, path: String) -> i32 {
+            let Ok(pattern) = Pattern::parse(&pattern) else {
+                return -1;
+            };
+            let cycles = cycles.clamp(1, MAX_QUERY_CYCLES as i64) as u32;
+            let Ok(events) = pattern.query_cycles(cycles) else {
+                return -1;
+            };
+            let Ok(mut composition) = MidiComposition::new("Cathedral Pattern", bpm) else {
+                return -1;
+            };
+            let Ok(mut track) = MidiTrack::new("Pattern", 0) else {
+                return -1;
+            };
+            track.add_pattern_events(&events, None);
+            composition.add_track(track);
+            match composition.save(&path) {
+                Ok(()) => 0,
+                Err(_) => -1,
+            }
+        }
+
+        /// Render `duration` seconds (at most `MAX_TONE_SECONDS`) of the solfeggio
+        /// tone for a Codex 144:99 node to a WAV file
+        #[func]
+        pub fn render_codex_node_tone_godot(node: i32, duration: f64, path: String) -> i32 {
+            if !(0.0..=MAX_TONE_SECONDS).contains(&duration) {
+                return -1;
+            }
+            let Ok(node) = u16::try_from(node) else {
+                return -1;
+            };
+            match ToneSpec::for_codex_node(node)
+                .and_then(|tone| tone.render_to_wav(&path, duration, constants::SAMPLE_RATE))
+            {
+                Ok(()) => 0,
+                Err(_) => -1,
+            }
+        }
+
+        /// Bounce `cycles` cycles (1 to `MAX_QUERY_CYCLES`) of a mini-notation
+        /// pattern to a WAV or FLAC file
+        #[func]
+        pub fn render_pattern_godot(pattern: String, cycles: i64, cycles_per_second: f64, path: String) -> i32 {
+            let Some(cycles) = u32::try_from(cycles).ok().filter(|cycles| (1..=MAX_QUERY_CYCLES).contains(cycles)) else {
+                return -1;
+            };
+            let Ok(pattern) = Pattern::parse(&pattern) else {
+                return -1;
+            };
+            let settings = RenderSettings::default();
+            match render_pattern(&pattern, cycles, cycles_per_second, NoteSettings::new(440.0, 0.8), settings)
+                .and_then(|samples| save_render(&path, &samples, &settings))
+            {
+                Ok(()) => 0,
+                Err(_) => -1,
+            }
+        }
+
+        /// Analyse the synthesizer output since the last call and return the
+        /// `u_audio_*` uniforms, ready to pass to `ShaderMaterial.set_shader_parameter`
+        #[func]
+        pub fn process_shader_uniforms_godot(&mut self) -> Dictionary {
+            let mut uniforms = Dictionary::new();
+            let Some(synth) = self.synth.as_ref() else {
+                return uniforms;
+            };
+            if self.analysis.is_none() {
+                let settings = AnalysisSettings {
+                    sample_rate: synth.controller().sample_rate(),
+                    ..AnalysisSettings::default()
+                };
+                match (AudioAnalyzer::new(settings), synth.controller().attach_analysis_tap()) {
+                    (Ok(analyzer), Ok(tap)) => self.analysis = Some((analyzer, tap)),
+                    _ => return uniforms,
+                }
+            }
+
+            if let Some((analyzer, tap)) = self.analysis.as_mut() {
+                for (name, value) in analyzer.process_shader_uniforms(&tap.drain()) {
+                    uniforms.set(name, value);
+                }
+            }
+            uniforms
+        }
+
+        /// Paths of the VST3 bundles installed in the standard folders that are
+        /// instruments, or that ship no moduleinfo.json and may be
+        #[func]
+        pub fn scan_vst3_instruments_godot() -> PackedStringArray {
+            scan_vst3_bundles(&vst3_search_paths())
+                .iter()
+                .filter(|bundle| bundle.kind() != Vst3BundleKind::Effect)
+                .map(|bundle| GString::from(bundle.path.display().to_string()))
+                .collect()
+        }
+
+        /// Load a VST3 instrument to stream through an `AudioStreamGenerator`
+        /// whose `mix_rate` is `sample_rate`, replacing any loaded instrument
+        #[func]
+        pub fn load_vst3_instrument_godot(&mut self, path: String, sample_rate: i32) -> i32 {
+            let Ok(sample_rate) = u32::try_from(sample_rate) else {
+                return -1;
+            };
+            match Vst3Bundle::open(&path)
+                .and_then(|bundle| bundle.instantiate())
+                .and_then(|plugin| host_plugin(plugin, sample_rate))
+            {
+                Ok(hosted) => {
+                    self.plugin = Some(hosted);
+                    0
+                },
+                Err(_) => -1,
+            }
+        }
+
+        /// Start a MIDI key on the loaded instrument, returning its note ID;
+        /// a positive `duration` releases it automatically
+        #[func]
+        pub fn plugin_note_on_godot(&mut self, key: i32, velocity: f64, duration: f64) -> i64 {
+            let (Some((handle, _)), Ok(key)) = (self.plugin.as_ref(), u8::try_from(key)) else {
+                return -1;
+            };
+            let duration = if duration > 0.0 { Some(duration) } else { None };
+            match handle.note_on(key, velocity as f32, 0.0, duration) {
+                Ok(note_id) => note_id as i64,
+                Err(_) => -1,
+            }
+        }
+
+        /// Release a note started by `plugin_note_on_godot`
+        #[func]
+        pub fn plugin_note_off_godot(&mut self, note_id: i64, key: i32) -> i32 {
+            let (Some((handle, _)), Ok(note_id), Ok(key)) =
+                (self.plugin.as_ref(), u64::try_from(note_id), u8::try_from(key))
+            else {
+                return -1;
+            };
+            match handle.note_off(note_id, key) {
+                Ok(()) => 0,
+                Err(_) => -1,
+            }
+        }
+
+        /// Render the loaded instrument into every free frame of `playback`
+        /// (from an `AudioStreamPlayer` playing an `AudioStreamGenerator`).
+        /// Call it each frame from `_process`; returns the frames pushed
+        #[func]
+        pub fn fill_plugin_stream_godot(&mut self, mut playback: Gd<AudioStreamGeneratorPlayback>) -> i32 {
+            let Some((_, source)) = self.plugin.as_mut() else {
+                return -1;
+            };
+            let available = playback.get_frames_available().max(0);
+            let frames: PackedVector2Array = (0..available)
+                .map(|_| {
+                    // A failed plugin goes silent rather than starving the stream
+                    let (left, right) = source.next_frame().unwrap_or((0.0, 0.0));
+                    Vector2::new(left, right)
+                })
+                .collect();
+            playback.push_buffer(&frames);
+            available
+        }
     }
 
     /// Type registration for Godot
@@ -200,6 +598,18 @@ pub mod godot {
     pub struct KiraAudioEngineGodot {
         #[base]
         base: Base<Node>,
+
+        /// Real-time synthesizer, started on demand
+        synth: Option<RealtimeSynth>,
+
+        /// Shared musical clock, set by `synth_set_tempo_godot`
+        transport: Option<Transport>,
+
+        /// Analyzer fed from the synthesizer output, set up on first use
+        analysis: Option<(AudioAnalyzer, AnalysisTap)>,
+
+        /// VST3 instrument streamed by `fill_plugin_stream_godot`
+        plugin: Option<(PluginHandle, PluginSource)>,
     }
 }
 
@@ -214,6 +624,20 @@ pub mod constants {
     pub const FREQUENCY_ORDER: f64 = 852.0;       // A - Returning to spiritual order
     pub const FREQUENCY_CONSCIOUSNESS: f64 = 963.0; // B - Divine consciousness/oneness
 
+    /// The seven solfeggio frequencies in ascending order
+    pub const SOLFEGGIO_FREQUENCIES: [f64; 7] = [
+        FREQUENCY_LIBERATION,
+        FREQUENCY_CHANGE,
+        FREQUENCY_TRANSFORMATION,
+        FREQUENCY_CONNECTION,
+        FREQUENCY_EXPRESSION,
+        FREQUENCY_ORDER,
+        FREQUENCY_CONSCIOUSNESS,
+    ];
+
+    /// Number of nodes in the Codex 144:99
+    pub const CODEX_NODE_COUNT: u16 = 144;
+
     /// Consciousness level base frequencies
     pub const CONSCIOUSNESS_FREQUENCIES: [f64; 22] = [
         963.0,  // Level 0: The Fool
@@ -258,6 +682,17 @@ pub mod constants {
     pub const QUALITY_MEDIUM: f32 = 0.5;
     pub const QUALITY_HIGH: f32 = 0.75;
     pub const QUALITY_PROFESSIONAL: f32 = 1.0;
+
+    /// ND-safe output limits: peak amplitude (about -6 dBFS) and
+    /// minimum fade time so tones never start or stop abruptly
+    pub const ND_SAFE_MAX_AMPLITUDE: f32 = 0.5;
+    pub const ND_SAFE_MIN_FADE_SECONDS: f64 = 0.05;
+
+    /// Brainwave entrainment safety caps: beat range (delta to gamma) and
+    /// highest carrier at which binaural beats are still perceived
+    pub const ENTRAINMENT_MIN_BEAT: f64 = 0.5;
+    pub const ENTRAINMENT_MAX_BEAT: f64 = 40.0;
+    pub const ENTRAINMENT_MAX_CARRIER: f64 = 1000.0;
 }
 
 /// Error types for the audio engine
@@ -289,6 +724,15 @@ pub enum AudioEngineError {
     
     #[error("Godot integration error: {0}")]
     GodotError(String),
+
+    #[error("Invalid codex node: {0} (valid range: 1-144)")]
+    InvalidCodexNode(u16),
+
+    #[error("Audio render failed: {0}")]
+    RenderError(String),
+
+    #[error("Safety limit exceeded: {0}")]
+    SafetyLimitExceeded(String),
 }
 
 pub type Result<T> = std::result::Result<T, AudioEngineError>;
@@ -308,7 +752,8 @@ pub fn get_consciousness_frequency(level: u8) -> Result<f64> {
 
 /// Validate frequency for audio synthesis
 pub fn validate_frequency(frequency: f64) -> Result<f64> {
-    if frequency < 20.0 || frequency > 20000.0 {
+    // Written as a range check so NaN is rejected too
+    if !(20.0..=20000.0).contains(&frequency) {
         return Err(AudioEngineError::InvalidFrequency(frequency));
     }
     Ok(frequency)
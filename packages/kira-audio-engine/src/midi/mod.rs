@@ -0,0 +1,39 @@
+/*!
+ * MIDI EXPORT
+ *
+ * Standard MIDI File output for compositions generated by the pattern
+ * engine, so Cathedral music can be opened and arranged in any DAW.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ *
+ * Features:
+ * - Multi-track type 1 files with a conductor track
+ * - Tempo maps with changes at any cycle position
+ * - Pattern events resolved as note names, MIDI numbers or scale degrees
+ */
+
+mod writer;
+
+pub use writer::*;
+
+// MIDI-related error types
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum MidiError {
+    #[error("Invalid MIDI channel: {0} (valid range: 0-15)")]
+    InvalidChannel(u8),
+
+    #[error("Invalid tempo: {0} BPM")]
+    InvalidTempo(f64),
+
+    #[error("Invalid time: {0} cycles")]
+    InvalidTime(f64),
+
+    #[error("Composition is too long for a MIDI file")]
+    TooLong,
+
+    #[error("Failed to write MIDI file: {0}")]
+    WriteError(String),
+}
+
+pub type MidiResult<T> = std::result::Result<T, MidiError>;
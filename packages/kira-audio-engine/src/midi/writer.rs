@@ -0,0 +1,338 @@
+/*!
+ * STANDARD MIDI FILE WRITER
+ *
+ * Compositions are laid out in cycles, the pattern engine's unit of
+ * time, and converted to ticks when written. By default one cycle is
+ * one 4/4 bar.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::{MidiError, MidiResult};
+use crate::pattern::PatternEvent;
+use crate::scheduler::{MusicalClock, TempoChange};
+use crate::theory::Scale;
+use midly::num::{u15, u24, u28, u4, u7};
+use midly::{Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Default resolution in ticks per quarter note
+pub const DEFAULT_TICKS_PER_QUARTER: u16 = 480;
+
+/// Default velocity for notes created from pattern events
+pub const DEFAULT_MIDI_VELOCITY: u8 = 100;
+
+/// A single note, timed in cycles
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MidiNote {
+    pub start: f64,
+    pub duration: f64,
+    pub key: u8,
+    pub velocity: u8,
+}
+
+/// One instrument track
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MidiTrack {
+    pub name: String,
+    pub channel: u8,
+    /// General MIDI program to select at the start of the track
+    pub program: Option<u8>,
+    pub notes: Vec<MidiNote>,
+}
+
+impl MidiTrack {
+    pub fn new(name: &str, channel: u8) -> MidiResult<Self> {
+        if channel > 15 {
+            return Err(MidiError::InvalidChannel(channel));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            channel,
+            program: None,
+            notes: Vec::new(),
+        })
+    }
+
+    pub fn with_program(mut self, program: u8) -> Self {
+        self.program = Some(program.min(127));
+        self
+    }
+
+    /// Add pattern events as notes.
+    ///
+    /// With a scale, integer values are read as scale degrees; otherwise
+    /// values are note names or MIDI numbers. Other values (e.g. drum
+    /// sample names) are skipped. Returns the number of notes added.
+    pub fn add_pattern_events(&mut self, events: &[PatternEvent], scale: Option<&Scale>) -> usize {
+        let before = self.notes.len();
+
+        for event in events {
+            let key = match (scale, event.value.parse::<i32>()) {
+                (Some(scale), Ok(degree)) => scale.degree_to_midi(degree).map(f64::from),
+                _ => event.midi_note(),
+            };
+            let Some(key) = key.map(f64::round).filter(|key| (0.0..=127.0).contains(key)) else {
+                continue;
+            };
+
+            self.notes.push(MidiNote {
+                start: event.begin,
+                duration: event.duration(),
+                key: key as u8,
+                velocity: DEFAULT_MIDI_VELOCITY,
+            });
+        }
+
+        self.notes.len() - before
+    }
+}
+
+/// A multi-track composition with a tempo map
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MidiComposition {
+    pub name: String,
+    pub ticks_per_quarter: u16,
+    /// Quarter-note beats per cycle (and time-signature numerator)
+    pub beats_per_cycle: u8,
+    pub tempo_map: Vec<TempoChange>,
+    pub tracks: Vec<MidiTrack>,
+}
+
+impl MidiComposition {
+    pub fn new(name: &str, bpm: f64) -> MidiResult<Self> {
+        let mut composition = Self {
+            name: name.to_string(),
+            ticks_per_quarter: DEFAULT_TICKS_PER_QUARTER,
+            beats_per_cycle: 4,
+            tempo_map: Vec::new(),
+            tracks: Vec::new(),
+        };
+        composition.set_tempo(0.0, bpm)?;
+        Ok(composition)
+    }
+
+    /// Composition using a musical clock's tempo map and beats per cycle.
+    ///
+    /// Swing is not stored in the file; pass events through
+    /// `MusicalClock::swing_events` before adding them to tracks.
+    pub fn from_clock(name: &str, clock: &MusicalClock) -> MidiResult<Self> {
+        let mut composition = Self::new(name, clock.bpm_at(0.0))?;
+        composition.beats_per_cycle = clock.beats_per_cycle();
+        for change in clock.tempo_map() {
+            composition.set_tempo(change.cycle, change.bpm)?;
+        }
+        Ok(composition)
+    }
+
+    /// Set the tempo from `cycle` onwards, replacing any change at the same position
+    pub fn set_tempo(&mut self, cycle: f64, bpm: f64) -> MidiResult<()> {
+        if !(bpm.is_finite() && bpm > 0.0) {
+            return Err(MidiError::InvalidTempo(bpm));
+        }
+        if !(cycle.is_finite() && cycle >= 0.0) {
+            return Err(MidiError::InvalidTime(cycle));
+        }
+
+        self.tempo_map.retain(|change| change.cycle != cycle);
+        self.tempo_map.push(TempoChange { cycle, bpm });
+        self.tempo_map.sort_by(|a, b| a.cycle.total_cmp(&b.cycle));
+        Ok(())
+    }
+
+    pub fn add_track(&mut self, track: MidiTrack) {
+        self.tracks.push(track);
+    }
+
+    /// Encode as a type 1 Standard MIDI File
+    pub fn to_bytes(&self) -> MidiResult<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.to_smf()?
+            .write_std(&mut bytes)
+            .map_err(|e| MidiError::WriteError(e.to_string()))?;
+        Ok(bytes)
+    }
+
+    /// Write a type 1 Standard MIDI File to `path`
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> MidiResult<()> {
+        self.to_smf()?
+            .save(path)
+            .map_err(|e| MidiError::WriteError(e.to_string()))
+    }
+
+    fn to_smf(&self) -> MidiResult<Smf<'_>> {
+        let header = Header::new(
+            Format::Parallel,
+            Timing::Metrical(u15::new(self.ticks_per_quarter.clamp(1, 0x7FFF))),
+        );
+        let mut smf = Smf::new(header);
+
+        // Conductor track: name, time signature and tempo map
+        let mut conductor = vec![
+            (0, TrackEventKind::Meta(MetaMessage::TrackName(self.name.as_bytes()))),
+            (0, TrackEventKind::Meta(MetaMessage::TimeSignature(self.beats_per_cycle.max(1), 2, 24, 8))),
+        ];
+        for change in &self.tempo_map {
+            let micros_per_quarter = (60_000_000.0 / change.bpm).round().clamp(1.0, 0xFF_FFFF as f64) as u32;
+            conductor.push((
+                self.cycles_to_ticks(change.cycle)?,
+                TrackEventKind::Meta(MetaMessage::Tempo(u24::new(micros_per_quarter))),
+            ));
+        }
+        smf.tracks.push(to_track_events(conductor)?);
+
+        for track in &self.tracks {
+            if track.channel > 15 {
+                return Err(MidiError::InvalidChannel(track.channel));
+            }
+            let channel = u4::new(track.channel);
+
+            let mut events = vec![(0, TrackEventKind::Meta(MetaMessage::TrackName(track.name.as_bytes())))];
+            if let Some(program) = track.program {
+                events.push((0, TrackEventKind::Midi {
+                    channel,
+                    message: MidiMessage::ProgramChange { program: u7::new(program.min(127)) },
+                }));
+            }
+
+            for note in &track.notes {
+                let start = self.cycles_to_ticks(note.start)?;
+                // Every note lasts at least one tick so its note-off follows its note-on
+                let end = self.cycles_to_ticks(note.start + note.duration)?.max(start + 1);
+                let key = u7::new(note.key.min(127));
+                events.push((start, TrackEventKind::Midi {
+                    channel,
+                    message: MidiMessage::NoteOn { key, vel: u7::new(note.velocity.clamp(1, 127)) },
+                }));
+                events.push((end, TrackEventKind::Midi {
+                    channel,
+                    message: MidiMessage::NoteOff { key, vel: u7::new(0) },
+                }));
+            }
+
+            smf.tracks.push(to_track_events(events)?);
+        }
+
+        Ok(smf)
+    }
+
+    fn cycles_to_ticks(&self, cycles: f64) -> MidiResult<u64> {
+        if !(cycles.is_finite() && cycles >= 0.0) {
+            return Err(MidiError::InvalidTime(cycles));
+        }
+        let ticks = cycles * self.beats_per_cycle as f64 * self.ticks_per_quarter as f64;
+        Ok(ticks.round() as u64)
+    }
+}
+
+/// Sort absolute-time events and convert them to delta-timed track events.
+///
+/// At equal times note-offs come first, so repeated notes retrigger cleanly.
+fn to_track_events(mut events: Vec<(u64, TrackEventKind<'_>)>) -> MidiResult<Vec<TrackEvent<'_>>> {
+    let order = |kind: &TrackEventKind| match kind {
+        TrackEventKind::Meta(_) => 0,
+        TrackEventKind::Midi { message: MidiMessage::ProgramChange { .. }, .. } => 1,
+        TrackEventKind::Midi { message: MidiMessage::NoteOff { .. }, .. } => 2,
+        _ => 3,
+    };
+    events.sort_by_key(|(tick, kind)| (*tick, order(kind)));
+
+    let mut track = Vec::with_capacity(events.len() + 1);
+    let mut previous = 0;
+    for (tick, kind) in events {
+        let delta = u32::try_from(tick - previous).map_err(|_| MidiError::TooLong)?;
+        if delta > 0x0FFF_FFFF {
+            return Err(MidiError::TooLong);
+        }
+        track.push(TrackEvent { delta: u28::new(delta), kind });
+        previous = tick;
+    }
+    track.push(TrackEvent {
+        delta: u28::new(0),
+        kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+    });
+
+    Ok(track)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::Pattern;
+
+    #[test]
+    fn test_pattern_events_to_notes() {
+        let events = Pattern::parse("c4 ~ bd e4").unwrap().query_cycle(0);
+        let mut track = MidiTrack::new("lead", 0).unwrap();
+        assert_eq!(track.add_pattern_events(&events, None), 2);
+        assert_eq!(track.notes[0].key, 60);
+        assert_eq!(track.notes[1].key, 64);
+        assert_eq!(track.notes[1].start, 0.75);
+    }
+
+    #[test]
+    fn test_scale_degrees() {
+        let events = Pattern::parse("0 2 4 7").unwrap().query_cycle(0);
+        let mut track = MidiTrack::new("degrees", 1).unwrap();
+        track.add_pattern_events(&events, Some(&Scale::major(60)));
+        let keys: Vec<u8> = track.notes.iter().map(|n| n.key).collect();
+        assert_eq!(keys, vec![60, 64, 67, 72]);
+    }
+
+    #[test]
+    fn test_written_file_round_trips() {
+        let mut composition = MidiComposition::new("Codex Study", 120.0).unwrap();
+        composition.set_tempo(2.0, 90.0).unwrap();
+
+        let mut track = MidiTrack::new("lead", 2).unwrap().with_program(19);
+        track.add_pattern_events(&Pattern::parse("c4 e4").unwrap().query(0.0, 2.0).unwrap(), None);
+        composition.add_track(track);
+
+        let bytes = composition.to_bytes().unwrap();
+        let smf = Smf::parse(&bytes).unwrap();
+        assert_eq!(smf.header.format, Format::Parallel);
+        assert_eq!(smf.tracks.len(), 2);
+
+        let tempos: Vec<u32> = smf.tracks[0]
+            .iter()
+            .filter_map(|e| match e.kind {
+                TrackEventKind::Meta(MetaMessage::Tempo(t)) => Some(t.as_int()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(tempos, vec![500_000, 666_667]);
+
+        let note_ons = smf.tracks[1]
+            .iter()
+            .filter(|e| matches!(e.kind, TrackEventKind::Midi { message: MidiMessage::NoteOn { .. }, .. }))
+            .count();
+        assert_eq!(note_ons, 4);
+    }
+
+    #[test]
+    fn test_composition_from_clock_with_swing() {
+        let mut clock = MusicalClock::new(90.0, 48000)
+            .unwrap()
+            .with_beats_per_cycle(3)
+            .unwrap()
+            .with_swing(crate::scheduler::Swing::new(0.5, 6).unwrap());
+        clock.set_tempo(4.0, 120.0).unwrap();
+
+        let composition = MidiComposition::from_clock("Waltz", &clock).unwrap();
+        assert_eq!(composition.beats_per_cycle, 3);
+        assert_eq!(composition.tempo_map, clock.tempo_map());
+
+        let events = clock.swing_events(&Pattern::parse("c4 d4 e4 f4 g4 a4").unwrap().query_cycle(0));
+        let mut track = MidiTrack::new("lead", 0).unwrap();
+        track.add_pattern_events(&events, None);
+        assert_eq!(composition.cycles_to_ticks(track.notes[1].start).unwrap(), 360);
+    }
+
+    #[test]
+    fn test_invalid_inputs() {
+        assert!(MidiTrack::new("drums", 16).is_err());
+        assert!(MidiComposition::new("bad", 0.0).is_err());
+    }
+}
@@ -0,0 +1,432 @@
+/*!
+ * OSC MESSAGES AND BUNDLES
+ *
+ * Binary encoding and decoding of OSC 1.0 packets. All values are
+ * big-endian and every field is padded to a multiple of four bytes.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::{OscError, OscResult};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Seconds between the NTP epoch (1900) and the Unix epoch (1970)
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+const BUNDLE_TAG: &[u8] = b"#bundle\0";
+
+/// Deepest bundle nesting accepted when decoding
+const MAX_BUNDLE_DEPTH: usize = 8;
+
+/// A typed OSC argument
+#[derive(Debug, Clone, PartialEq)]
+pub enum OscArg {
+    Int(i32),
+    Float(f32),
+    String(String),
+    Blob(Vec<u8>),
+    Long(i64),
+    Double(f64),
+    Bool(bool),
+    Nil,
+}
+
+impl OscArg {
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            OscArg::Int(v) => Some(*v as f64),
+            OscArg::Float(v) => Some(*v as f64),
+            OscArg::Long(v) => Some(*v as f64),
+            OscArg::Double(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            OscArg::Int(v) => Some(*v as i64),
+            OscArg::Long(v) => Some(*v),
+            OscArg::Float(v) => Some(*v as i64),
+            OscArg::Double(v) => Some(*v as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            OscArg::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    fn type_tag(&self) -> char {
+        match self {
+            OscArg::Int(_) => 'i',
+            OscArg::Float(_) => 'f',
+            OscArg::String(_) => 's',
+            OscArg::Blob(_) => 'b',
+            OscArg::Long(_) => 'h',
+            OscArg::Double(_) => 'd',
+            OscArg::Bool(true) => 'T',
+            OscArg::Bool(false) => 'F',
+            OscArg::Nil => 'N',
+        }
+    }
+}
+
+impl From<i32> for OscArg {
+    fn from(value: i32) -> Self {
+        OscArg::Int(value)
+    }
+}
+
+impl From<f32> for OscArg {
+    fn from(value: f32) -> Self {
+        OscArg::Float(value)
+    }
+}
+
+impl From<&str> for OscArg {
+    fn from(value: &str) -> Self {
+        OscArg::String(value.to_string())
+    }
+}
+
+impl From<String> for OscArg {
+    fn from(value: String) -> Self {
+        OscArg::String(value)
+    }
+}
+
+/// An OSC message: address plus arguments
+#[derive(Debug, Clone, PartialEq)]
+pub struct OscMessage {
+    pub address: String,
+    pub args: Vec<OscArg>,
+}
+
+impl OscMessage {
+    pub fn new(address: &str, args: Vec<OscArg>) -> Self {
+        Self {
+            address: address.to_string(),
+            args,
+        }
+    }
+
+    /// Argument at `index`, or a `MissingArgument` error
+    pub fn arg(&self, index: usize) -> OscResult<&OscArg> {
+        self.args.get(index).ok_or_else(|| OscError::MissingArgument {
+            address: self.address.clone(),
+            index,
+        })
+    }
+}
+
+/// OSC time tag in NTP format (seconds since 1900 plus 2^-32 fractions)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OscTimeTag(pub u64);
+
+impl OscTimeTag {
+    /// Special time tag meaning "execute immediately"
+    pub const IMMEDIATELY: OscTimeTag = OscTimeTag(1);
+
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let since_unix = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let seconds = since_unix.as_secs() + NTP_UNIX_OFFSET;
+        let fraction = ((since_unix.subsec_nanos() as u64) << 32) / 1_000_000_000;
+        OscTimeTag((seconds << 32) | fraction)
+    }
+
+    pub fn now() -> Self {
+        Self::from_system_time(SystemTime::now())
+    }
+
+    pub fn to_system_time(self) -> SystemTime {
+        let seconds = (self.0 >> 32).saturating_sub(NTP_UNIX_OFFSET);
+        let nanos = ((self.0 & 0xFFFF_FFFF) * 1_000_000_000) >> 32;
+        UNIX_EPOCH + Duration::new(seconds, nanos as u32)
+    }
+
+    /// Time tag `seconds` after this one
+    pub fn offset(self, seconds: f64) -> Self {
+        let delta = (seconds.max(0.0) * 4_294_967_296.0).round() as u64;
+        OscTimeTag(self.0.saturating_add(delta))
+    }
+}
+
+/// A top-level OSC packet
+#[derive(Debug, Clone, PartialEq)]
+pub enum OscPacket {
+    Message(OscMessage),
+    Bundle {
+        time_tag: OscTimeTag,
+        content: Vec<OscPacket>,
+    },
+}
+
+impl OscPacket {
+    pub fn encode(&self) -> OscResult<Vec<u8>> {
+        let mut out = Vec::new();
+        encode_packet(self, &mut out)?;
+        Ok(out)
+    }
+
+    pub fn decode(bytes: &[u8]) -> OscResult<Self> {
+        decode_at_depth(bytes, 0)
+    }
+
+    /// All messages in this packet, flattening nested bundles
+    pub fn messages(&self) -> Vec<&OscMessage> {
+        match self {
+            OscPacket::Message(message) => vec![message],
+            OscPacket::Bundle { content, .. } => content.iter().flat_map(|packet| packet.messages()).collect(),
+        }
+    }
+}
+
+fn encode_packet(packet: &OscPacket, out: &mut Vec<u8>) -> OscResult<()> {
+    match packet {
+        OscPacket::Message(message) => {
+            if !message.address.starts_with('/') {
+                return Err(OscError::InvalidAddress(message.address.clone()));
+            }
+            write_string(&message.address, out);
+
+            let tags: String = std::iter::once(',').chain(message.args.iter().map(OscArg::type_tag)).collect();
+            write_string(&tags, out);
+
+            for arg in &message.args {
+                match arg {
+                    OscArg::Int(v) => out.extend_from_slice(&v.to_be_bytes()),
+                    OscArg::Float(v) => out.extend_from_slice(&v.to_be_bytes()),
+                    OscArg::String(v) => write_string(v, out),
+                    OscArg::Blob(v) => {
+                        out.extend_from_slice(&(v.len() as i32).to_be_bytes());
+                        out.extend_from_slice(v);
+                        pad(out);
+                    },
+                    OscArg::Long(v) => out.extend_from_slice(&v.to_be_bytes()),
+                    OscArg::Double(v) => out.extend_from_slice(&v.to_be_bytes()),
+                    OscArg::Bool(_) | OscArg::Nil => {},
+                }
+            }
+        },
+        OscPacket::Bundle { time_tag, content } => {
+            out.extend_from_slice(BUNDLE_TAG);
+            out.extend_from_slice(&time_tag.0.to_be_bytes());
+            for element in content {
+                let mut encoded = Vec::new();
+                encode_packet(element, &mut encoded)?;
+                out.extend_from_slice(&(encoded.len() as i32).to_be_bytes());
+                out.extend_from_slice(&encoded);
+            }
+        },
+    }
+    Ok(())
+}
+
+fn write_string(value: &str, out: &mut Vec<u8>) {
+    out.extend_from_slice(value.as_bytes());
+    out.push(0);
+    pad(out);
+}
+
+fn pad(out: &mut Vec<u8>) {
+    while !out.len().is_multiple_of(4) {
+        out.push(0);
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> OscResult<&'a [u8]> {
+        let end = self
+            .position
+            .checked_add(count)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| OscError::DecodeError("unexpected end of packet".to_string()))?;
+        let slice = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn array<const N: usize>(&mut self) -> OscResult<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn skip_padding(&mut self) -> OscResult<()> {
+        let padding = (4 - self.position % 4) % 4;
+        self.take(padding).map(|_| ())
+    }
+
+    fn string(&mut self) -> OscResult<String> {
+        let rest = &self.bytes[self.position..];
+        let length = rest
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or_else(|| OscError::DecodeError("unterminated string".to_string()))?;
+        let value = std::str::from_utf8(&rest[..length])
+            .map_err(|_| OscError::DecodeError("string is not valid UTF-8".to_string()))?
+            .to_string();
+        self.take(length + 1)?;
+        self.skip_padding()?;
+        Ok(value)
+    }
+}
+
+fn decode_at_depth(bytes: &[u8], depth: usize) -> OscResult<OscPacket> {
+    let mut reader = Reader { bytes, position: 0 };
+    let packet = decode_packet(&mut reader, depth)?;
+    if reader.position != bytes.len() {
+        return Err(OscError::DecodeError("trailing bytes after packet".to_string()));
+    }
+    Ok(packet)
+}
+
+fn decode_packet(reader: &mut Reader, depth: usize) -> OscResult<OscPacket> {
+    let remaining = &reader.bytes[reader.position..];
+    if remaining.starts_with(BUNDLE_TAG) {
+        if depth >= MAX_BUNDLE_DEPTH {
+            return Err(OscError::DecodeError(format!(
+                "bundles nested deeper than {} levels",
+                MAX_BUNDLE_DEPTH
+            )));
+        }
+        reader.take(BUNDLE_TAG.len())?;
+        let time_tag = OscTimeTag(u64::from_be_bytes(reader.array()?));
+
+        let mut content = Vec::new();
+        while reader.position < reader.bytes.len() {
+            let size = i32::from_be_bytes(reader.array()?);
+            if size < 0 || size % 4 != 0 {
+                return Err(OscError::DecodeError(format!("invalid bundle element size {}", size)));
+            }
+            let element = reader.take(size as usize)?;
+            content.push(decode_at_depth(element, depth + 1)?);
+        }
+        return Ok(OscPacket::Bundle { time_tag, content });
+    }
+
+    let address = reader.string()?;
+    if !address.starts_with('/') {
+        return Err(OscError::InvalidAddress(address));
+    }
+
+    // Type tags are optional in very old implementations
+    if reader.position >= reader.bytes.len() {
+        return Ok(OscPacket::Message(OscMessage { address, args: Vec::new() }));
+    }
+    let tags = reader.string()?;
+    let tags = tags
+        .strip_prefix(',')
+        .ok_or_else(|| OscError::DecodeError("type tag string must start with ','".to_string()))?;
+
+    let mut args = Vec::with_capacity(tags.len());
+    for tag in tags.chars() {
+        let arg = match tag {
+            'i' => OscArg::Int(i32::from_be_bytes(reader.array()?)),
+            'f' => OscArg::Float(f32::from_be_bytes(reader.array()?)),
+            's' | 'S' => OscArg::String(reader.string()?),
+            'b' => {
+                let size = i32::from_be_bytes(reader.array()?);
+                if size < 0 {
+                    return Err(OscError::DecodeError(format!("invalid blob size {}", size)));
+                }
+                let blob = reader.take(size as usize)?.to_vec();
+                reader.skip_padding()?;
+                OscArg::Blob(blob)
+            },
+            'h' => OscArg::Long(i64::from_be_bytes(reader.array()?)),
+            't' => OscArg::Long(u64::from_be_bytes(reader.array()?) as i64),
+            'd' => OscArg::Double(f64::from_be_bytes(reader.array()?)),
+            'T' => OscArg::Bool(true),
+            'F' => OscArg::Bool(false),
+            'N' | 'I' => OscArg::Nil,
+            other => return Err(OscError::DecodeError(format!("unsupported type tag '{}'", other))),
+        };
+        args.push(arg);
+    }
+
+    Ok(OscPacket::Message(OscMessage { address, args }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_encoding_matches_spec() {
+        // Example from the OSC 1.0 specification
+        let packet = OscPacket::Message(OscMessage::new("/oscillator/4/frequency", vec![OscArg::Float(440.0)]));
+        let bytes = packet.encode().unwrap();
+
+        assert_eq!(&bytes[..24], b"/oscillator/4/frequency\0");
+        assert_eq!(&bytes[24..28], b",f\0\0");
+        assert_eq!(&bytes[28..], &[0x43, 0xdc, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_round_trip_bundle() {
+        let packet = OscPacket::Bundle {
+            time_tag: OscTimeTag::now().offset(0.25),
+            content: vec![
+                OscPacket::Message(OscMessage::new(
+                    "/dirt/play",
+                    vec!["s".into(), "bd".into(), OscArg::Int(3), OscArg::Blob(vec![1, 2, 3]), OscArg::Bool(true)],
+                )),
+                OscPacket::Message(OscMessage::new("/cathedral/stop", vec![])),
+            ],
+        };
+
+        let decoded = OscPacket::decode(&packet.encode().unwrap()).unwrap();
+        assert_eq!(decoded, packet);
+        assert_eq!(decoded.messages().len(), 2);
+    }
+
+    #[test]
+    fn test_time_tag_conversion() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_700_000_000_500);
+        let tag = OscTimeTag::from_system_time(time);
+        let back = tag.to_system_time().duration_since(UNIX_EPOCH).unwrap();
+        assert_eq!(back.as_millis(), 1_700_000_000_500);
+    }
+
+    #[test]
+    fn test_malformed_packets_rejected() {
+        assert!(OscPacket::decode(b"/abc").is_err());
+        assert!(OscPacket::decode(b"abc\0,i\0\0\0\0\0\x01").is_err());
+        assert!(OscPacket::decode(b"/abc\0\0\0\0,i\0\0").is_err());
+    }
+
+    fn nested_bundle(depth: usize) -> OscPacket {
+        let mut packet = OscPacket::Message(OscMessage::new("/leaf", Vec::new()));
+        for _ in 0..depth {
+            packet = OscPacket::Bundle { time_tag: OscTimeTag::IMMEDIATELY, content: vec![packet] };
+        }
+        packet
+    }
+
+    #[test]
+    fn test_bundle_nesting_is_bounded() {
+        let allowed = nested_bundle(MAX_BUNDLE_DEPTH).encode().unwrap();
+        assert_eq!(OscPacket::decode(&allowed).unwrap(), nested_bundle(MAX_BUNDLE_DEPTH));
+
+        let too_deep = nested_bundle(MAX_BUNDLE_DEPTH + 1).encode().unwrap();
+        assert!(matches!(OscPacket::decode(&too_deep), Err(OscError::DecodeError(_))));
+
+        // Thousands of empty bundles, each wrapping the next
+        let mut hostile = [BUNDLE_TAG, &1u64.to_be_bytes()].concat();
+        for _ in 0..3_274 {
+            let size = (hostile.len() as i32).to_be_bytes();
+            hostile = [BUNDLE_TAG, &1u64.to_be_bytes(), &size, &hostile].concat();
+        }
+        assert!(OscPacket::decode(&hostile).is_err());
+    }
+}
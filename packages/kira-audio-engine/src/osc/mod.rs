@@ -0,0 +1,48 @@
+/*!
+ * OSC PROTOCOL SUPPORT
+ *
+ * Open Sound Control 1.0 over UDP for live-coding integration: the
+ * pattern engine can drive SuperCollider/SuperDirt, and external
+ * setups can control the synthesizer through data-driven routes.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ *
+ * Features:
+ * - OSC 1.0 message and bundle encoding/decoding with time tags
+ * - Address pattern matching (?, *, [a-z], [!abc], {foo,bar})
+ * - Route tables loaded from JSON data files
+ * - UDP client with SuperDirt pattern playback and a threaded server
+ */
+
+mod message;
+mod routing;
+mod transport;
+
+pub use message::*;
+pub use routing::*;
+pub use transport::*;
+
+// OSC-related error types
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum OscError {
+    #[error("Malformed OSC packet: {0}")]
+    DecodeError(String),
+
+    #[error("Invalid OSC address: {0}")]
+    InvalidAddress(String),
+
+    #[error("OSC message {address} is missing argument {index}")]
+    MissingArgument { address: String, index: usize },
+
+    #[error("Invalid OSC route configuration: {0}")]
+    ConfigError(String),
+
+    #[error("OSC network error: {0}")]
+    NetworkError(String),
+
+    #[error("OSC action failed: {0}")]
+    ActionError(String),
+}
+
+pub type OscResult<T> = std::result::Result<T, OscError>;
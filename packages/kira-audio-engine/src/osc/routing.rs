@@ -0,0 +1,357 @@
+/*!
+ * OSC ADDRESS ROUTING
+ *
+ * OSC 1.0 address pattern matching and data-driven route tables that
+ * map incoming messages onto synthesizer actions. Route files are JSON:
+ *
+ * {"routes": [{"address": "/cathedral/note", "action": "note_on", "waveform": "Triangle"}]}
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::{OscError, OscMessage, OscResult};
+use crate::constants::ND_SAFE_MAX_AMPLITUDE;
+use crate::pattern::Pattern;
+use crate::synthesis::{NoteSettings, SynthController, VoiceId, Waveform};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Longest mini-notation source accepted from the network
+pub const MAX_REMOTE_PATTERN_LENGTH: usize = 1024;
+
+/// Most events per cycle a remote pattern may produce
+pub const MAX_REMOTE_EVENTS_PER_CYCLE: f64 = 256.0;
+
+/// Synthesizer action triggered by a routed message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OscAction {
+    /// Args: frequency, [velocity = 0.8], [duration seconds]
+    NoteOn,
+    /// Args: voice id
+    NoteOff,
+    /// No args
+    AllNotesOff,
+    /// Args: gain, clamped to the ND-safe maximum amplitude
+    MasterGain,
+    /// Args: mini-notation pattern, [cycles per second = 0.5];
+    /// size-limited by `MAX_REMOTE_PATTERN_LENGTH` and `MAX_REMOTE_EVENTS_PER_CYCLE`
+    PlayPattern,
+}
+
+/// One route: a method address and the action it performs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OscRoute {
+    pub address: String,
+    pub action: OscAction,
+    /// Waveform used for notes started by this route
+    #[serde(default)]
+    pub waveform: Waveform,
+}
+
+/// Table of routes, usually loaded from a data file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OscRouter {
+    pub routes: Vec<OscRoute>,
+}
+
+impl OscRouter {
+    pub fn from_json(json: &str) -> OscResult<Self> {
+        let router: Self = serde_json::from_str(json).map_err(|e| OscError::ConfigError(e.to_string()))?;
+        if let Some(route) = router.routes.iter().find(|route| !is_valid_method_address(&route.address)) {
+            return Err(OscError::InvalidAddress(route.address.clone()));
+        }
+        Ok(router)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> OscResult<Self> {
+        let json = std::fs::read_to_string(path).map_err(|e| OscError::ConfigError(e.to_string()))?;
+        Self::from_json(&json)
+    }
+
+    /// Default routes under /cathedral/...
+    pub fn default_routes() -> Self {
+        let route = |address: &str, action| OscRoute {
+            address: address.to_string(),
+            action,
+            waveform: Waveform::default(),
+        };
+        Self {
+            routes: vec![
+                route("/cathedral/note", OscAction::NoteOn),
+                route("/cathedral/note/off", OscAction::NoteOff),
+                route("/cathedral/panic", OscAction::AllNotesOff),
+                route("/cathedral/gain", OscAction::MasterGain),
+                route("/cathedral/pattern", OscAction::PlayPattern),
+            ],
+        }
+    }
+
+    /// Routes whose address matches the message's address pattern
+    pub fn matching(&self, message: &OscMessage) -> Vec<&OscRoute> {
+        self.routes
+            .iter()
+            .filter(|route| address_matches(&message.address, &route.address))
+            .collect()
+    }
+
+    /// Apply every matching route to the synthesizer, returning the voices started
+    pub fn dispatch(&self, message: &OscMessage, synth: &SynthController) -> OscResult<Vec<VoiceId>> {
+        let mut voices = Vec::new();
+        for route in self.matching(message) {
+            voices.extend(apply_route(route, message, synth)?);
+        }
+        Ok(voices)
+    }
+}
+
+fn apply_route(route: &OscRoute, message: &OscMessage, synth: &SynthController) -> OscResult<Vec<VoiceId>> {
+    let number = |index: usize| -> OscResult<f64> {
+        message.arg(index)?.as_f64().ok_or_else(|| {
+            OscError::ActionError(format!("{} argument {} must be numeric", message.address, index))
+        })
+    };
+    let optional = |index: usize| message.args.get(index).and_then(|arg| arg.as_f64());
+    let action_error = |e: crate::synthesis::SynthesizerError| OscError::ActionError(e.to_string());
+
+    match route.action {
+        OscAction::NoteOn => {
+            let settings = NoteSettings {
+                waveform: route.waveform,
+                ..NoteSettings::new(number(0)?, optional(1).unwrap_or(0.8) as f32)
+            };
+            let duration = optional(2).filter(|seconds| *seconds > 0.0);
+            Ok(vec![synth.schedule_note(settings, 0.0, duration).map_err(action_error)?])
+        },
+        OscAction::NoteOff => {
+            let id = message.arg(0)?.as_i64().ok_or_else(|| {
+                OscError::ActionError(format!("{} voice id must be an integer", message.address))
+            })?;
+            synth.note_off(id as VoiceId).map_err(action_error)?;
+            Ok(Vec::new())
+        },
+        OscAction::AllNotesOff => {
+            synth.all_notes_off().map_err(action_error)?;
+            Ok(Vec::new())
+        },
+        OscAction::MasterGain => {
+            let gain = number(0)?;
+            if !gain.is_finite() {
+                return Err(OscError::ActionError(format!("{} gain must be finite", message.address)));
+            }
+            let gain = gain.clamp(0.0, ND_SAFE_MAX_AMPLITUDE as f64) as f32;
+            synth.set_master_gain(gain).map_err(action_error)?;
+            Ok(Vec::new())
+        },
+        OscAction::PlayPattern => {
+            let source = message.arg(0)?.as_str().ok_or_else(|| {
+                OscError::ActionError(format!("{} pattern must be a string", message.address))
+            })?;
+            if source.len() > MAX_REMOTE_PATTERN_LENGTH {
+                return Err(OscError::ActionError(format!(
+                    "{} pattern is {} bytes, limit is {}",
+                    message.address,
+                    source.len(),
+                    MAX_REMOTE_PATTERN_LENGTH
+                )));
+            }
+            let pattern = Pattern::parse(source).map_err(|e| OscError::ActionError(e.to_string()))?;
+            if pattern.max_events_per_cycle() > MAX_REMOTE_EVENTS_PER_CYCLE {
+                return Err(OscError::ActionError(format!(
+                    "{} pattern may produce more than {} events per cycle",
+                    message.address, MAX_REMOTE_EVENTS_PER_CYCLE
+                )));
+            }
+            let template = NoteSettings {
+                waveform: route.waveform,
+                ..NoteSettings::new(440.0, 0.8)
+            };
+            synth
+                .play_pattern_events(&pattern.query_cycle(0), optional(1).unwrap_or(0.5), template)
+                .map_err(action_error)
+        },
+    }
+}
+
+/// Method addresses may not contain pattern characters
+fn is_valid_method_address(address: &str) -> bool {
+    address.starts_with('/') && !address.contains(['?', '*', '[', ']', '{', '}', ',', ' ', '#'])
+}
+
+/// Match an OSC address pattern against a method address.
+///
+/// Supports `?`, `*`, `[abc]`, `[a-z]`, `[!abc]` and `{foo,bar}`; no wildcard crosses a `/`.
+pub fn address_matches(pattern: &str, address: &str) -> bool {
+    let pattern_parts: Vec<&str> = pattern.split('/').collect();
+    let address_parts: Vec<&str> = address.split('/').collect();
+
+    pattern_parts.len() == address_parts.len()
+        && pattern_parts
+            .iter()
+            .zip(&address_parts)
+            .all(|(p, a)| part_matches(&p.chars().collect::<Vec<_>>(), &a.chars().collect::<Vec<_>>()))
+}
+
+/// Match one address part by tracking every text position the pattern so far can reach,
+/// so any number of wildcards costs polynomial rather than exponential time
+fn part_matches(pattern: &[char], text: &[char]) -> bool {
+    let mut reachable = vec![false; text.len() + 1];
+    let mut next = vec![false; text.len() + 1];
+    reachable[0] = true;
+
+    let mut index = 0;
+    while index < pattern.len() {
+        next.fill(false);
+        match pattern[index] {
+            '*' => {
+                if let Some(first) = reachable.iter().position(|reached| *reached) {
+                    next[first..].fill(true);
+                }
+                index += 1;
+            },
+            '?' => {
+                next[1..].copy_from_slice(&reachable[..text.len()]);
+                index += 1;
+            },
+            '[' => {
+                let Some(close) = find_from(pattern, index, ']') else {
+                    return false;
+                };
+                let class = &pattern[index + 1..close];
+                for position in 0..text.len() {
+                    next[position + 1] = reachable[position] && char_class_matches(class, text[position]);
+                }
+                index = close + 1;
+            },
+            '{' => {
+                let Some(close) = find_from(pattern, index, '}') else {
+                    return false;
+                };
+                for alternative in pattern[index + 1..close].split(|c| *c == ',') {
+                    for position in 0..=text.len() {
+                        if reachable[position] && text[position..].starts_with(alternative) {
+                            next[position + alternative.len()] = true;
+                        }
+                    }
+                }
+                index = close + 1;
+            },
+            c => {
+                for position in 0..text.len() {
+                    next[position + 1] = reachable[position] && text[position] == c;
+                }
+                index += 1;
+            },
+        }
+
+        std::mem::swap(&mut reachable, &mut next);
+        if !reachable.contains(&true) {
+            return false;
+        }
+    }
+
+    reachable[text.len()]
+}
+
+fn find_from(pattern: &[char], start: usize, target: char) -> Option<usize> {
+    pattern[start..].iter().position(|c| *c == target).map(|offset| start + offset)
+}
+
+fn char_class_matches(class: &[char], c: char) -> bool {
+    let (negated, class) = match class.first() {
+        Some('!') => (true, &class[1..]),
+        _ => (false, class),
+    };
+
+    let mut matched = false;
+    let mut index = 0;
+    while index < class.len() {
+        if index + 2 < class.len() && class[index + 1] == '-' {
+            matched |= (class[index]..=class[index + 2]).contains(&c);
+            index += 3;
+        } else {
+            matched |= class[index] == c;
+            index += 1;
+        }
+    }
+
+    matched != negated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osc::OscArg;
+
+    #[test]
+    fn test_address_pattern_matching() {
+        assert!(address_matches("/cathedral/note", "/cathedral/note"));
+        assert!(address_matches("/cathedral/*", "/cathedral/note"));
+        assert!(!address_matches("/cathedral/*", "/cathedral/note/off"));
+        assert!(address_matches("/synth/?/freq", "/synth/4/freq"));
+        assert!(address_matches("/synth/[1-3]", "/synth/2"));
+        assert!(!address_matches("/synth/[!1-3]", "/synth/2"));
+        assert!(address_matches("/cathedral/{note,gain}", "/cathedral/gain"));
+        assert!(address_matches("/*/n*e", "/cathedral/note"));
+        assert!(address_matches("/cathedral/n{o,x}t?", "/cathedral/note"));
+        assert!(!address_matches("/cathedral/[n", "/cathedral/n"));
+    }
+
+    #[test]
+    fn test_many_wildcards_match_quickly() {
+        let pattern = format!("/{}x", "*".repeat(5000));
+        let address = format!("/{}", "a".repeat(60));
+        let started = std::time::Instant::now();
+        assert!(!address_matches(&pattern, &address));
+        assert!(address_matches(&format!("/{}a", "*?".repeat(20)), &address));
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_remote_limits() {
+        let (controller, _engine) = crate::synthesis::synth_channel(48000, 2);
+        let router = OscRouter::default_routes();
+
+        let dense = OscMessage::new("/cathedral/pattern", vec!["[c4*100]*100".into()]);
+        assert!(matches!(router.dispatch(&dense, &controller), Err(OscError::ActionError(_))));
+        let long = OscMessage::new("/cathedral/pattern", vec!["c4 ".repeat(400).as_str().into()]);
+        assert!(matches!(router.dispatch(&long, &controller), Err(OscError::ActionError(_))));
+        let nan_gain = OscMessage::new("/cathedral/gain", vec![OscArg::Float(f32::NAN)]);
+        assert!(matches!(router.dispatch(&nan_gain, &controller), Err(OscError::ActionError(_))));
+        let loud = OscMessage::new("/cathedral/gain", vec![OscArg::Float(1.0)]);
+        assert!(router.dispatch(&loud, &controller).is_ok());
+    }
+
+    #[test]
+    fn test_route_config_loading() {
+        let router = OscRouter::from_json(
+            r#"{"routes": [{"address": "/live/kick", "action": "note_on", "waveform": "Square"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(router.routes[0].action, OscAction::NoteOn);
+        assert_eq!(router.routes[0].waveform, Waveform::Square);
+
+        assert!(OscRouter::from_json(r#"{"routes": [{"address": "/live/*", "action": "note_on"}]}"#).is_err());
+        assert!(OscRouter::from_json(r#"{"routes": [{"address": "/x", "action": "explode"}]}"#).is_err());
+    }
+
+    #[test]
+    fn test_dispatch_to_synth() {
+        let (controller, mut engine) = crate::synthesis::synth_channel(48000, 2);
+        let router = OscRouter::default_routes();
+
+        let note = OscMessage::new("/cathedral/note", vec![OscArg::Float(528.0)]);
+        assert_eq!(router.dispatch(&note, &controller).unwrap().len(), 1);
+
+        let pattern = OscMessage::new("/cathedral/pattern", vec!["c4 e4 g4".into(), OscArg::Float(1.0)]);
+        assert_eq!(router.dispatch(&pattern, &controller).unwrap().len(), 3);
+
+        let mut buffer = vec![0.0; 64];
+        engine.render(&mut buffer);
+        assert_eq!(engine.active_voices(), 2);
+
+        let missing = OscMessage::new("/cathedral/gain", vec![]);
+        assert!(matches!(router.dispatch(&missing, &controller), Err(OscError::MissingArgument { .. })));
+    }
+}
@@ -0,0 +1,224 @@
+/*!
+ * OSC UDP TRANSPORT
+ *
+ * UDP client for sending to SuperCollider/SuperDirt and a threaded
+ * server that routes incoming messages to the synthesizer.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::{OscArg, OscError, OscMessage, OscPacket, OscResult, OscRouter, OscTimeTag};
+use crate::pattern::PatternEvent;
+use crate::synthesis::SynthController;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Default SuperDirt listening port
+pub const SUPERDIRT_PORT: u16 = 57120;
+
+/// Largest UDP datagram accepted by the server
+const MAX_PACKET_SIZE: usize = 65_507;
+
+fn network_error(error: std::io::Error) -> OscError {
+    OscError::NetworkError(error.to_string())
+}
+
+/// Sends OSC packets to one target over UDP
+#[derive(Debug)]
+pub struct OscClient {
+    socket: UdpSocket,
+    target: SocketAddr,
+}
+
+impl OscClient {
+    pub fn connect<A: ToSocketAddrs>(target: A) -> OscResult<Self> {
+        let target = target
+            .to_socket_addrs()
+            .map_err(network_error)?
+            .next()
+            .ok_or_else(|| OscError::NetworkError("target address did not resolve".to_string()))?;
+        let bind_address: SocketAddr = if target.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(bind_address).map_err(network_error)?;
+
+        Ok(Self { socket, target })
+    }
+
+    /// Client for a SuperDirt instance on this machine
+    pub fn superdirt() -> OscResult<Self> {
+        Self::connect(("127.0.0.1", SUPERDIRT_PORT))
+    }
+
+    pub fn target(&self) -> SocketAddr {
+        self.target
+    }
+
+    pub fn send(&self, packet: &OscPacket) -> OscResult<()> {
+        let bytes = packet.encode()?;
+        self.socket.send_to(&bytes, self.target).map_err(network_error)?;
+        Ok(())
+    }
+
+    pub fn send_message(&self, address: &str, args: Vec<OscArg>) -> OscResult<()> {
+        self.send(&OscPacket::Message(OscMessage::new(address, args)))
+    }
+
+    /// Send pattern events to SuperDirt as time-tagged `/dirt/play` bundles.
+    ///
+    /// Event times are relative to the earliest cycle in `events`, starting
+    /// `latency` seconds from now. Note values are sent as `note` on the
+    /// `instrument` synth; other values are sent as sample names.
+    pub fn send_superdirt_events(
+        &self,
+        events: &[PatternEvent],
+        cycles_per_second: f64,
+        latency: f64,
+        instrument: &str,
+    ) -> OscResult<()> {
+        if !(cycles_per_second.is_finite() && cycles_per_second > 0.0) {
+            return Err(OscError::ActionError(format!(
+                "cycles per second must be positive, got {}",
+                cycles_per_second
+            )));
+        }
+
+        let start = OscTimeTag::now().offset(latency);
+        let origin = events.iter().map(|e| e.begin.floor()).fold(f64::INFINITY, f64::min);
+
+        for event in events {
+            let time_tag = start.offset((event.begin - origin) / cycles_per_second);
+            let bundle = OscPacket::Bundle {
+                time_tag,
+                content: vec![OscPacket::Message(superdirt_message(event, cycles_per_second, instrument))],
+            };
+            self.send(&bundle)?;
+        }
+        Ok(())
+    }
+}
+
+/// Build a SuperDirt `/dirt/play` message for one event
+pub fn superdirt_message(event: &PatternEvent, cycles_per_second: f64, instrument: &str) -> OscMessage {
+    let mut args: Vec<OscArg> = vec![
+        "cps".into(),
+        OscArg::Float(cycles_per_second as f32),
+        "cycle".into(),
+        OscArg::Float(event.begin as f32),
+        "delta".into(),
+        OscArg::Float((event.duration() / cycles_per_second) as f32),
+    ];
+
+    match event.midi_note() {
+        // SuperDirt notes are semitones relative to middle C
+        Some(note) => args.extend(["s".into(), instrument.into(), "note".into(), OscArg::Float((note - 60.0) as f32)]),
+        None => args.extend(["s".into(), event.value.as_str().into()]),
+    }
+
+    OscMessage::new("/dirt/play", args)
+}
+
+/// Receives OSC on a background thread and routes it to the synthesizer.
+///
+/// Messages are applied on receipt; bundle time tags are not waited for.
+pub struct OscServer {
+    local_address: SocketAddr,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl OscServer {
+    pub fn bind<A: ToSocketAddrs>(address: A, router: OscRouter, synth: SynthController) -> OscResult<Self> {
+        let socket = UdpSocket::bind(address).map_err(network_error)?;
+        socket
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .map_err(network_error)?;
+        let local_address = socket.local_addr().map_err(network_error)?;
+
+        let running = Arc::new(AtomicBool::new(true));
+        let thread_running = Arc::clone(&running);
+
+        let thread = std::thread::Builder::new()
+            .name("kira-osc-server".to_string())
+            .spawn(move || {
+                let mut buffer = vec![0u8; MAX_PACKET_SIZE];
+                while thread_running.load(Ordering::Relaxed) {
+                    let Ok((size, _)) = socket.recv_from(&mut buffer) else {
+                        continue;
+                    };
+                    let result = OscPacket::decode(&buffer[..size]).and_then(|packet| {
+                        packet
+                            .messages()
+                            .into_iter()
+                            .try_for_each(|message| router.dispatch(message, &synth).map(|_| ()))
+                    });
+                    if let Err(error) = result {
+                        log::warn!("kira osc server dropped a packet: {}", error);
+                    }
+                }
+            })
+            .map_err(network_error)?;
+
+        Ok(Self {
+            local_address,
+            running,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn local_address(&self) -> SocketAddr {
+        self.local_address
+    }
+}
+
+impl Drop for OscServer {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_superdirt_message_for_note_and_sample() {
+        let events = crate::pattern::Pattern::parse("c5 bd").unwrap().query_cycle(0);
+
+        let note = superdirt_message(&events[0], 0.5, "superpiano");
+        assert_eq!(note.address, "/dirt/play");
+        assert!(note.args.ends_with(&["s".into(), "superpiano".into(), "note".into(), OscArg::Float(12.0)]));
+
+        let sample = superdirt_message(&events[1], 0.5, "superpiano");
+        assert!(sample.args.ends_with(&["s".into(), "bd".into()]));
+        assert_eq!(sample.args[5], OscArg::Float(1.0));
+    }
+
+    #[test]
+    fn test_client_server_round_trip() {
+        let (controller, mut engine) = crate::synthesis::synth_channel(48000, 1);
+        let server = OscServer::bind("127.0.0.1:0", OscRouter::default_routes(), controller).unwrap();
+        let client = OscClient::connect(server.local_address()).unwrap();
+
+        client.send_message("/cathedral/note", vec![OscArg::Float(396.0)]).unwrap();
+
+        let mut buffer = vec![0.0; 16];
+        for _ in 0..100 {
+            engine.render(&mut buffer);
+            if engine.active_voices() > 0 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(engine.active_voices(), 1);
+    }
+}
@@ -0,0 +1,394 @@
+/*!
+ * PATTERN EVALUATOR
+ *
+ * Pattern trees and cycle-based querying. Time is measured in cycles;
+ * querying a span returns every event whose onset falls inside it.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::{PatternError, PatternResult};
+use serde::{Deserialize, Serialize};
+
+/// Largest step count accepted for a euclidean rhythm
+pub const MAX_EUCLID_STEPS: u32 = 256;
+
+/// Most cycles `Pattern::query_cycles` will evaluate at once
+pub const MAX_QUERY_CYCLES: u32 = 1024;
+
+/// Most events `Pattern::query_cycles` may return, by `max_events_per_cycle`
+pub const MAX_QUERY_EVENTS: f64 = 1_048_576.0;
+
+/// A cyclic pattern of values
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Pattern {
+    /// No events ("~")
+    Silence,
+
+    /// A single value filling the whole cycle
+    Atom(String),
+
+    /// Steps played one after another within each cycle
+    Sequence(Vec<Pattern>),
+
+    /// Patterns played simultaneously
+    Stack(Vec<Pattern>),
+
+    /// One child per cycle, in turn
+    Alternate(Vec<Pattern>),
+
+    /// Pattern sped up by a factor
+    Fast(Box<Pattern>, f64),
+
+    /// Pattern slowed down by a factor
+    Slow(Box<Pattern>, f64),
+
+    /// Pattern placed on a euclidean rhythm
+    Euclid {
+        pattern: Box<Pattern>,
+        pulses: u32,
+        steps: u32,
+        rotation: i32,
+    },
+}
+
+/// A single event produced by querying a pattern
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PatternEvent {
+    /// Event value (sample name, note name, number...)
+    pub value: String,
+
+    /// Onset in cycles
+    pub begin: f64,
+
+    /// End in cycles
+    pub end: f64,
+}
+
+impl PatternEvent {
+    /// Event duration in cycles
+    pub fn duration(&self) -> f64 {
+        self.end - self.begin
+    }
+
+    fn map_time(self, f: impl Fn(f64) -> f64) -> Self {
+        Self {
+            value: self.value,
+            begin: f(self.begin),
+            end: f(self.end),
+        }
+    }
+}
+
+impl Pattern {
+    /// Speed a pattern up by `factor`
+    pub fn fast(pattern: Pattern, factor: f64) -> PatternResult<Self> {
+        if !(factor.is_finite() && factor > 0.0) {
+            return Err(PatternError::InvalidSpeed(factor));
+        }
+        Ok(Pattern::Fast(Box::new(pattern), factor))
+    }
+
+    /// Slow a pattern down by `factor`
+    pub fn slow(pattern: Pattern, factor: f64) -> PatternResult<Self> {
+        if !(factor.is_finite() && factor > 0.0) {
+            return Err(PatternError::InvalidSpeed(factor));
+        }
+        Ok(Pattern::Slow(Box::new(pattern), factor))
+    }
+
+    /// Place a pattern on a euclidean rhythm of `pulses` hits over `steps`
+    pub fn euclid(pattern: Pattern, pulses: u32, steps: u32, rotation: i32) -> PatternResult<Self> {
+        if steps == 0 || steps > MAX_EUCLID_STEPS || pulses > steps {
+            return Err(PatternError::InvalidEuclid { pulses, steps });
+        }
+        Ok(Pattern::Euclid {
+            pattern: Box::new(pattern),
+            pulses,
+            steps,
+            rotation,
+        })
+    }
+
+    /// Upper bound on the events a single-cycle query can return, without querying.
+    ///
+    /// Lets callers with tighter budgets (e.g. remote control) reject patterns before evaluating them.
+    pub fn max_events_per_cycle(&self) -> f64 {
+        // A span of `length` cycles overlaps at most ceil(length) + 1 inner cycles
+        let overlapped = |length: f64| length.ceil() + 1.0;
+        match self {
+            Pattern::Silence => 0.0,
+            Pattern::Atom(_) => 1.0,
+            Pattern::Sequence(children) | Pattern::Stack(children) => {
+                children.iter().map(Pattern::max_events_per_cycle).sum()
+            },
+            Pattern::Alternate(children) => children.iter().map(Pattern::max_events_per_cycle).fold(0.0, f64::max),
+            Pattern::Fast(pattern, factor) => overlapped(*factor) * pattern.max_events_per_cycle(),
+            Pattern::Slow(pattern, factor) => overlapped(1.0 / factor) * pattern.max_events_per_cycle(),
+            Pattern::Euclid { pattern, pulses, .. } => *pulses as f64 * pattern.max_events_per_cycle(),
+        }
+    }
+
+    /// Query all events with an onset in `[begin, end)`, sorted by onset
+    pub fn query(&self, begin: f64, end: f64) -> PatternResult<Vec<PatternEvent>> {
+        if !(begin.is_finite() && end.is_finite()) || end < begin {
+            return Err(PatternError::InvalidSpan(begin, end));
+        }
+
+        let mut events = Vec::new();
+        self.query_into(begin, end, &mut events);
+        events.sort_by(|a, b| a.begin.total_cmp(&b.begin));
+        Ok(events)
+    }
+
+    /// Query the events of a single cycle
+    pub fn query_cycle(&self, cycle: i64) -> Vec<PatternEvent> {
+        let begin = cycle as f64;
+        self.query(begin, begin + 1.0).unwrap_or_default()
+    }
+
+    /// Query cycles 0 to `cycles`, for exporting or bouncing a whole pattern.
+    ///
+    /// Refuses spans past `MAX_QUERY_CYCLES` cycles or `MAX_QUERY_EVENTS` events.
+    pub fn query_cycles(&self, cycles: u32) -> PatternResult<Vec<PatternEvent>> {
+        if cycles > MAX_QUERY_CYCLES {
+            return Err(PatternError::InvalidSpan(0.0, cycles as f64));
+        }
+        let events = self.max_events_per_cycle() * cycles as f64;
+        if events.is_nan() || events > MAX_QUERY_EVENTS {
+            return Err(PatternError::TooManyEvents(events));
+        }
+        self.query(0.0, cycles as f64)
+    }
+
+    fn query_into(&self, begin: f64, end: f64, events: &mut Vec<PatternEvent>) {
+        if end <= begin {
+            return;
+        }
+
+        match self {
+            Pattern::Silence => {},
+            Pattern::Atom(value) => {
+                let mut cycle = begin.ceil();
+                while cycle < end {
+                    events.push(PatternEvent {
+                        value: value.clone(),
+                        begin: cycle,
+                        end: cycle + 1.0,
+                    });
+                    cycle += 1.0;
+                }
+            },
+            Pattern::Sequence(steps) => query_sequence(steps, begin, end, events),
+            Pattern::Stack(layers) => {
+                for layer in layers {
+                    layer.query_into(begin, end, events);
+                }
+            },
+            Pattern::Alternate(children) => {
+                if children.is_empty() {
+                    return;
+                }
+                let count = children.len() as i64;
+                for cycle in cycles_overlapping(begin, end) {
+                    let child = &children[cycle.rem_euclid(count) as usize];
+                    // Each child sees its own cycle count advance once per turn
+                    let shift = (cycle.div_euclid(count) - cycle) as f64;
+                    let span_begin = begin.max(cycle as f64);
+                    let span_end = end.min(cycle as f64 + 1.0);
+
+                    let mut inner = Vec::new();
+                    child.query_into(span_begin + shift, span_end + shift, &mut inner);
+                    events.extend(inner.into_iter().map(|e| e.map_time(|t| t - shift)));
+                }
+            },
+            Pattern::Fast(pattern, factor) => {
+                let mut inner = Vec::new();
+                pattern.query_into(begin * factor, end * factor, &mut inner);
+                events.extend(inner.into_iter().map(|e| e.map_time(|t| t / factor)));
+            },
+            Pattern::Slow(pattern, factor) => {
+                let mut inner = Vec::new();
+                pattern.query_into(begin / factor, end / factor, &mut inner);
+                events.extend(inner.into_iter().map(|e| e.map_time(|t| t * factor)));
+            },
+            Pattern::Euclid { pattern, pulses, steps, rotation } => {
+                let steps = euclidean_rhythm(*pulses, *steps, *rotation)
+                    .into_iter()
+                    .map(|hit| if hit { (**pattern).clone() } else { Pattern::Silence })
+                    .collect::<Vec<_>>();
+                query_sequence(&steps, begin, end, events);
+            },
+        }
+    }
+}
+
+/// Query a sequence by compressing each step into its slot of every cycle
+fn query_sequence(steps: &[Pattern], begin: f64, end: f64, events: &mut Vec<PatternEvent>) {
+    if steps.is_empty() {
+        return;
+    }
+    let count = steps.len() as f64;
+
+    for cycle in cycles_overlapping(begin, end) {
+        let cycle_start = cycle as f64;
+        for (index, step) in steps.iter().enumerate() {
+            let slot_begin = cycle_start + index as f64 / count;
+            let slot_end = cycle_start + (index as f64 + 1.0) / count;
+            let span_begin = begin.max(slot_begin);
+            let span_end = end.min(slot_end);
+            if span_end <= span_begin {
+                continue;
+            }
+
+            // Map the slot onto a whole cycle of the step, keeping the cycle number
+            let to_inner = |t: f64| cycle_start + (t - slot_begin) * count;
+            let to_outer = |t: f64| slot_begin + (t - cycle_start) / count;
+
+            // Slot edges map exactly onto cycle edges, so rounding can't leak a neighbour's onset
+            let inner_begin = if span_begin == slot_begin { cycle_start } else { to_inner(span_begin) };
+            let inner_end = if span_end == slot_end { cycle_start + 1.0 } else { to_inner(span_end) };
+
+            let mut inner = Vec::new();
+            step.query_into(inner_begin, inner_end, &mut inner);
+            events.extend(inner.into_iter().map(|e| e.map_time(to_outer)));
+        }
+    }
+}
+
+/// Integer cycles overlapping `[begin, end)`
+fn cycles_overlapping(begin: f64, end: f64) -> impl Iterator<Item = i64> {
+    let first = begin.floor() as i64;
+    let last = end.ceil() as i64;
+    first..last
+}
+
+/// Bjorklund's algorithm, matching the TidalCycles step ordering, rotated left by `rotation`
+pub fn euclidean_rhythm(pulses: u32, steps: u32, rotation: i32) -> Vec<bool> {
+    if steps == 0 {
+        return Vec::new();
+    }
+    let pulses = pulses.min(steps) as usize;
+    let steps = steps as usize;
+
+    let mut front: Vec<Vec<bool>> = vec![vec![true]; pulses];
+    let mut back: Vec<Vec<bool>> = vec![vec![false]; steps - pulses];
+
+    while front.len().min(back.len()) > 1 {
+        if front.len() > back.len() {
+            let remainder = front.split_off(back.len());
+            for (group, tail) in front.iter_mut().zip(back.drain(..)) {
+                group.extend(tail);
+            }
+            back = remainder;
+        } else {
+            let remainder = back.split_off(front.len());
+            for (group, tail) in front.iter_mut().zip(back.drain(..)) {
+                group.extend(tail);
+            }
+            back = remainder;
+        }
+    }
+
+    let mut rhythm: Vec<bool> = front.into_iter().chain(back).flatten().collect();
+    let shift = (rotation as i64).rem_euclid(steps as i64) as usize;
+    rhythm.rotate_left(shift);
+    rhythm
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn onsets(pattern: &str, cycle: i64) -> Vec<(String, f64)> {
+        Pattern::parse(pattern)
+            .unwrap()
+            .query_cycle(cycle)
+            .into_iter()
+            .map(|e| (e.value, e.begin))
+            .collect()
+    }
+
+    #[test]
+    fn test_euclidean_rhythms_match_tidal() {
+        let render = |p, s, r| -> String {
+            euclidean_rhythm(p, s, r).into_iter().map(|b| if b { 'x' } else { '.' }).collect()
+        };
+        assert_eq!(render(3, 8, 0), "x..x..x.");
+        assert_eq!(render(5, 8, 0), "x.xx.xx.");
+        assert_eq!(render(3, 8, 2), ".x..x.x.");
+        assert_eq!(render(0, 4, 0), "....");
+    }
+
+    #[test]
+    fn test_sequence_subdivision() {
+        assert_eq!(
+            onsets("bd [sd sd] hh", 0),
+            vec![
+                ("bd".to_string(), 0.0),
+                ("sd".to_string(), 1.0 / 3.0),
+                ("sd".to_string(), 0.5),
+                ("hh".to_string(), 2.0 / 3.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_alternation_advances_per_cycle() {
+        assert_eq!(onsets("<c e g>", 0), vec![("c".to_string(), 0.0)]);
+        assert_eq!(onsets("<c e g>", 1), vec![("e".to_string(), 1.0)]);
+        assert_eq!(onsets("<c e g>", 5), vec![("g".to_string(), 5.0)]);
+        assert_eq!(onsets("<c <e g>>", 3), vec![("g".to_string(), 3.0)]);
+    }
+
+    #[test]
+    fn test_speed_modifiers() {
+        assert_eq!(onsets("hh*4", 0).len(), 4);
+        assert_eq!(onsets("bd/2", 0).len(), 1);
+        assert_eq!(onsets("bd/2", 1).len(), 0);
+
+        let events = Pattern::parse("bd/2").unwrap().query_cycle(0);
+        assert_eq!(events[0].duration(), 2.0);
+    }
+
+    #[test]
+    fn test_stack_and_euclid() {
+        let events = Pattern::parse("[bd(3,8), hh*2]").unwrap().query_cycle(0);
+        assert_eq!(events.len(), 5);
+        assert_eq!(events.iter().filter(|e| e.value == "bd").map(|e| e.begin).collect::<Vec<_>>(),
+            vec![0.0, 0.375, 0.75]);
+    }
+
+    #[test]
+    fn test_query_partial_span() {
+        let events = Pattern::parse("a b c d").unwrap().query(0.5, 1.25).unwrap();
+        let values: Vec<_> = events.iter().map(|e| e.value.as_str()).collect();
+        assert_eq!(values, vec!["c", "d", "a"]);
+        assert!(Pattern::Silence.query(1.0, 0.0).is_err());
+    }
+
+    #[test]
+    fn test_max_events_per_cycle_bounds_queries() {
+        for source in ["bd sd [hh hh]", "bd*3 <c e>", "bd(3,8) hh/2", "[bd, hh*4]*1.5", "~"] {
+            let pattern = Pattern::parse(source).unwrap();
+            for cycle in 0..4 {
+                assert!(pattern.query_cycle(cycle).len() as f64 <= pattern.max_events_per_cycle(), "{}", source);
+            }
+        }
+        let dense = Pattern::Fast(Box::new(Pattern::Fast(Box::new(Pattern::Atom("bd".into())), 1000.0)), 1000.0);
+        assert!(dense.max_events_per_cycle() > 1e6);
+    }
+
+    #[test]
+    fn test_query_cycles_is_bounded() {
+        let pattern = Pattern::parse("bd*2 sd").unwrap();
+        assert_eq!(pattern.query_cycles(3).unwrap().len(), 9);
+        assert!(pattern.query_cycles(0).unwrap().is_empty());
+        assert!(matches!(pattern.query_cycles(MAX_QUERY_CYCLES + 1), Err(PatternError::InvalidSpan(..))));
+
+        let dense = Pattern::parse("[bd*256]*200").unwrap();
+        assert!(dense.query_cycle(0).len() <= 65_536);
+        assert!(matches!(dense.query_cycles(MAX_QUERY_CYCLES), Err(PatternError::TooManyEvents(_))));
+    }
+}
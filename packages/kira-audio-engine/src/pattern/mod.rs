@@ -0,0 +1,48 @@
+/*!
+ * PATTERN LANGUAGE ENGINE
+ *
+ * Strudel/TidalCycles-style mini-notation parsed and evaluated in Rust,
+ * so cyclic rhythm patterns can drive the Cathedral synthesizers directly.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ *
+ * Supported mini-notation:
+ * - Sequences: "bd sd hh" (steps share one cycle)
+ * - Rests: "~"
+ * - Subdivision: "bd [sd sd]"
+ * - Stacking: "[bd, hh hh hh]"
+ * - Alternation per cycle: "<c e g>"
+ * - Speed modifiers: "hh*2", "bd/2"
+ * - Replication: "bd!3"
+ * - Euclidean rhythms: "bd(3,8)", "bd(3,8,2)"
+ */
+
+mod parser;
+mod evaluator;
+mod notes;
+
+pub use parser::*;
+pub use evaluator::*;
+pub use notes::*;
+
+// Pattern-related error types
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum PatternError {
+    #[error("Pattern syntax error at position {position}: {message}")]
+    SyntaxError { position: usize, message: String },
+
+    #[error("Invalid euclidean rhythm ({pulses},{steps}): pulses must not exceed steps and steps must be 1-256")]
+    InvalidEuclid { pulses: u32, steps: u32 },
+
+    #[error("Invalid speed modifier: {0} (must be a positive number)")]
+    InvalidSpeed(f64),
+
+    #[error("Invalid query span: {0}..{1}")]
+    InvalidSpan(f64, f64),
+
+    #[error("Pattern query could produce {0} events, more than can be evaluated at once")]
+    TooManyEvents(f64),
+}
+
+pub type PatternResult<T> = std::result::Result<T, PatternError>;
@@ -0,0 +1,106 @@
+/*!
+ * NOTE VALUES
+ *
+ * Conversion of pattern values ("c4", "eb3", "60") into MIDI note
+ * numbers and frequencies, following Strudel conventions.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::PatternEvent;
+
+/// Octave used when a note name has no octave number ("c" == "c3")
+pub const DEFAULT_NOTE_OCTAVE: i32 = 3;
+
+/// Octaves a note name may use, spanning the MIDI note range
+pub const NOTE_OCTAVE_RANGE: std::ops::RangeInclusive<i32> = -1..=9;
+
+/// Reference tuning (A4) in Hz
+pub const CONCERT_PITCH: f64 = 440.0;
+
+/// Parse a note value into a (possibly fractional) MIDI note number
+pub fn note_name_to_midi(value: &str) -> Option<f64> {
+    let value = value.trim();
+    if let Ok(number) = value.parse::<f64>() {
+        // "nan" and "inf" parse as floats but are not notes
+        return number.is_finite().then_some(number);
+    }
+
+    let mut chars = value.chars().peekable();
+    let pitch_class = match chars.next()?.to_ascii_lowercase() {
+        'c' => 0,
+        'd' => 2,
+        'e' => 4,
+        'f' => 5,
+        'g' => 7,
+        'a' => 9,
+        'b' => 11,
+        _ => return None,
+    };
+
+    let mut accidental = 0;
+    while let Some(&c) = chars.peek() {
+        match c {
+            '#' | 's' => accidental += 1,
+            'b' | 'f' => accidental -= 1,
+            _ => break,
+        }
+        chars.next();
+    }
+
+    let rest: String = chars.collect();
+    let octave = if rest.is_empty() {
+        DEFAULT_NOTE_OCTAVE
+    } else {
+        rest.parse::<i32>().ok().filter(|octave| NOTE_OCTAVE_RANGE.contains(octave))?
+    };
+
+    Some(((octave + 1) * 12 + pitch_class + accidental) as f64)
+}
+
+/// Equal-tempered frequency of a MIDI note number
+pub fn midi_to_frequency(note: f64) -> f64 {
+    CONCERT_PITCH * 2f64.powf((note - 69.0) / 12.0)
+}
+
+impl PatternEvent {
+    /// MIDI note number of this event's value, if it is a note
+    pub fn midi_note(&self) -> Option<f64> {
+        note_name_to_midi(&self.value)
+    }
+
+    /// Frequency of this event's value, if it is a note
+    pub fn frequency(&self) -> Option<f64> {
+        self.midi_note().map(midi_to_frequency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_note_names_and_numbers() {
+        assert_eq!(note_name_to_midi("a4"), Some(69.0));
+        assert_eq!(note_name_to_midi("eb3"), Some(51.0));
+        assert_eq!(note_name_to_midi("60.5"), Some(60.5));
+        assert_eq!(note_name_to_midi("c-1"), Some(0.0));
+        assert_eq!(note_name_to_midi("g9"), Some(127.0));
+    }
+
+    #[test]
+    fn test_out_of_range_octaves_are_not_notes() {
+        for value in ["c10", "c-2", "c999999999", "c-2147483648"] {
+            assert_eq!(note_name_to_midi(value), None, "{} should not be a note", value);
+        }
+    }
+
+    #[test]
+    fn test_non_finite_numbers_are_not_notes() {
+        for value in ["nan", "NaN", "inf", "-inf", "infinity"] {
+            assert_eq!(note_name_to_midi(value), None, "{} should not be a note", value);
+        }
+        assert!(crate::validate_frequency(f64::NAN).is_err());
+    }
+}
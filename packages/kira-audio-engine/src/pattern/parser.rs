@@ -0,0 +1,392 @@
+/*!
+ * MINI-NOTATION PARSER
+ *
+ * Recursive descent parser turning Strudel-style mini-notation strings
+ * into `Pattern` trees.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::{Pattern, PatternError, PatternResult, MAX_EUCLID_STEPS};
+
+/// Largest replication count accepted by "!"
+pub const MAX_REPLICATION: u32 = 256;
+
+/// Largest pattern tree, in nodes, that replication may expand a source into
+pub const MAX_PATTERN_NODES: usize = 16384;
+
+/// Largest `max_events_per_cycle` a parsed pattern may have
+pub const MAX_EVENTS_PER_CYCLE: f64 = 65_536.0;
+
+/// Parse a mini-notation string into a pattern
+pub fn parse_mini_notation(source: &str) -> PatternResult<Pattern> {
+    let mut parser = MiniNotationParser::new(source);
+    let pattern = parser.parse_stack(None)?;
+    parser.skip_whitespace();
+
+    if let Some(c) = parser.peek() {
+        return Err(parser.error(format!("unexpected '{}'", c)));
+    }
+
+    // Speed modifiers multiply density without growing the tree, so bound it directly
+    let density = pattern.max_events_per_cycle();
+    if density.is_nan() || density > MAX_EVENTS_PER_CYCLE {
+        return Err(parser.error(format!(
+            "pattern may produce {} events per cycle; the limit is {}",
+            density, MAX_EVENTS_PER_CYCLE
+        )));
+    }
+
+    Ok(pattern)
+}
+
+impl Pattern {
+    /// Parse a mini-notation string (e.g. "bd [sd sd] <hh oh>*2")
+    pub fn parse(source: &str) -> PatternResult<Self> {
+        parse_mini_notation(source)
+    }
+}
+
+impl std::str::FromStr for Pattern {
+    type Err = PatternError;
+
+    fn from_str(source: &str) -> PatternResult<Self> {
+        parse_mini_notation(source)
+    }
+}
+
+/// Internal parser state over the source characters
+struct MiniNotationParser {
+    chars: Vec<char>,
+    position: usize,
+    /// Nodes produced by replication so far, bounded by `MAX_PATTERN_NODES`
+    expanded_nodes: usize,
+}
+
+impl MiniNotationParser {
+    fn new(source: &str) -> Self {
+        Self {
+            chars: source.chars().collect(),
+            position: 0,
+            expanded_nodes: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c.is_some() {
+            self.position += 1;
+        }
+        c
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(c) if c.is_whitespace()) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> PatternResult<()> {
+        self.skip_whitespace();
+        match self.advance() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => {
+                self.position -= 1;
+                Err(self.error(format!("expected '{}' but found '{}'", expected, c)))
+            },
+            None => Err(self.error(format!("expected '{}' but reached end of pattern", expected))),
+        }
+    }
+
+    fn error(&self, message: String) -> PatternError {
+        PatternError::SyntaxError {
+            position: self.position,
+            message,
+        }
+    }
+
+    /// Parse comma-separated sequences, stacking them when there is more than one
+    fn parse_stack(&mut self, closing: Option<char>) -> PatternResult<Pattern> {
+        let mut layers = vec![self.parse_sequence(closing)?];
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(',') {
+                break;
+            }
+            self.advance();
+            layers.push(self.parse_sequence(closing)?);
+        }
+
+        Ok(if layers.len() == 1 {
+            layers.remove(0)
+        } else {
+            Pattern::Stack(layers)
+        })
+    }
+
+    /// Parse whitespace-separated steps until a delimiter
+    fn parse_sequence(&mut self, closing: Option<char>) -> PatternResult<Pattern> {
+        let mut steps = Vec::new();
+
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None => break,
+                Some(',') => break,
+                Some(c) if Some(c) == closing => break,
+                Some(c @ (']' | '>')) => {
+                    return Err(self.error(format!("unmatched '{}'", c)));
+                },
+                Some(_) => {
+                    let (step, repeats) = self.parse_step()?;
+                    self.expanded_nodes += node_count(&step) * repeats;
+                    if self.expanded_nodes > MAX_PATTERN_NODES {
+                        return Err(self.error(format!(
+                            "pattern expands to more than {} steps",
+                            MAX_PATTERN_NODES
+                        )));
+                    }
+                    for _ in 0..repeats {
+                        steps.push(step.clone());
+                    }
+                },
+            }
+        }
+
+        Ok(match steps.len() {
+            0 => Pattern::Silence,
+            1 => steps.remove(0),
+            _ => Pattern::Sequence(steps),
+        })
+    }
+
+    /// Parse a single step with its modifiers, returning it with its replication count
+    fn parse_step(&mut self) -> PatternResult<(Pattern, usize)> {
+        let mut step = self.parse_term()?;
+        let mut repeats = 1;
+
+        loop {
+            match self.peek() {
+                Some('*') => {
+                    self.advance();
+                    let factor = self.parse_number()?;
+                    step = Pattern::fast(step, factor)?;
+                },
+                Some('/') => {
+                    self.advance();
+                    let factor = self.parse_number()?;
+                    step = Pattern::slow(step, factor)?;
+                },
+                Some('(') => {
+                    self.advance();
+                    let pulses = self.parse_count(0, MAX_EUCLID_STEPS, "euclidean pulses")?;
+                    self.expect(',')?;
+                    let steps = self.parse_count(1, MAX_EUCLID_STEPS, "euclidean steps")?;
+                    self.skip_whitespace();
+                    let rotation = if self.peek() == Some(',') {
+                        self.advance();
+                        let start = self.position;
+                        let rotation = self.parse_integer()?;
+                        i32::try_from(rotation).map_err(|_| PatternError::SyntaxError {
+                            position: start,
+                            message: format!("euclidean rotation {} is out of range", rotation),
+                        })?
+                    } else {
+                        0
+                    };
+                    self.expect(')')?;
+                    step = Pattern::euclid(step, pulses, steps, rotation)?;
+                },
+                Some('!') => {
+                    self.advance();
+                    repeats = if matches!(self.peek(), Some(c) if c.is_ascii_digit()) {
+                        self.parse_count(1, MAX_REPLICATION, "replication")? as usize
+                    } else if repeats < MAX_REPLICATION as usize {
+                        repeats + 1
+                    } else {
+                        return Err(self.error(format!("replication is limited to {}", MAX_REPLICATION)));
+                    };
+                },
+                _ => break,
+            }
+        }
+
+        Ok((step, repeats))
+    }
+
+    fn parse_term(&mut self) -> PatternResult<Pattern> {
+        match self.peek() {
+            Some('[') => {
+                self.advance();
+                let inner = self.parse_stack(Some(']'))?;
+                self.expect(']')?;
+                Ok(inner)
+            },
+            Some('<') => {
+                self.advance();
+                let inner = self.parse_sequence(Some('>'))?;
+                self.expect('>')?;
+                Ok(match inner {
+                    Pattern::Sequence(steps) => Pattern::Alternate(steps),
+                    other => other,
+                })
+            },
+            Some('~') => {
+                self.advance();
+                Ok(Pattern::Silence)
+            },
+            Some(c) if is_word_char(c) => {
+                let start = self.position;
+                while matches!(self.peek(), Some(c) if is_word_char(c)) {
+                    self.advance();
+                }
+                Ok(Pattern::Atom(self.chars[start..self.position].iter().collect()))
+            },
+            Some(c) => Err(self.error(format!("unexpected '{}'", c))),
+            None => Err(self.error("unexpected end of pattern".to_string())),
+        }
+    }
+
+    fn parse_number(&mut self) -> PatternResult<f64> {
+        let start = self.position;
+        while matches!(self.peek(), Some(c) if c.is_ascii_digit() || c == '.') {
+            self.advance();
+        }
+
+        let text: String = self.chars[start..self.position].iter().collect();
+        text.parse::<f64>().map_err(|_| PatternError::SyntaxError {
+            position: start,
+            message: format!("expected a number but found '{}'", text),
+        })
+    }
+
+    fn parse_integer(&mut self) -> PatternResult<i64> {
+        self.skip_whitespace();
+        let start = self.position;
+        if self.peek() == Some('-') {
+            self.advance();
+        }
+        while matches!(self.peek(), Some(c) if c.is_ascii_digit()) {
+            self.advance();
+        }
+
+        let text: String = self.chars[start..self.position].iter().collect();
+        text.parse::<i64>().map_err(|_| PatternError::SyntaxError {
+            position: start,
+            message: format!("expected an integer but found '{}'", text),
+        })
+    }
+
+    /// Parse an integer within `min..=max`
+    fn parse_count(&mut self, min: u32, max: u32, what: &str) -> PatternResult<u32> {
+        self.skip_whitespace();
+        let start = self.position;
+        let value = self.parse_integer()?;
+        u32::try_from(value)
+            .ok()
+            .filter(|count| (min..=max).contains(count))
+            .ok_or_else(|| PatternError::SyntaxError {
+                position: start,
+                message: format!("{} must be between {} and {}, got {}", what, min, max, value),
+            })
+    }
+}
+
+/// Nodes in a pattern tree, counting euclidean steps as the copies they expand to
+fn node_count(pattern: &Pattern) -> usize {
+    match pattern {
+        Pattern::Silence | Pattern::Atom(_) => 1,
+        Pattern::Sequence(children) | Pattern::Stack(children) | Pattern::Alternate(children) => {
+            1 + children.iter().map(node_count).sum::<usize>()
+        },
+        Pattern::Fast(inner, _) | Pattern::Slow(inner, _) => 1 + node_count(inner),
+        Pattern::Euclid { pattern, steps, .. } => 1 + node_count(pattern) * *steps as usize,
+    }
+}
+
+/// Characters allowed in atom names ("bd:3", "c#4", "-0.5")
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '.' | '#' | '-' | '_' | ':')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn atom(name: &str) -> Pattern {
+        Pattern::Atom(name.to_string())
+    }
+
+    #[test]
+    fn test_parse_sequence_with_rest() {
+        let pattern = Pattern::parse("bd ~ sd").unwrap();
+        assert_eq!(pattern, Pattern::Sequence(vec![atom("bd"), Pattern::Silence, atom("sd")]));
+    }
+
+    #[test]
+    fn test_parse_nested_stack_and_alternation() {
+        let pattern = Pattern::parse("[bd, hh hh] <c e>").unwrap();
+        assert_eq!(
+            pattern,
+            Pattern::Sequence(vec![
+                Pattern::Stack(vec![atom("bd"), Pattern::Sequence(vec![atom("hh"), atom("hh")])]),
+                Pattern::Alternate(vec![atom("c"), atom("e")]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_replication() {
+        let pattern = Pattern::parse("bd!3 sd").unwrap();
+        assert_eq!(pattern, Pattern::Sequence(vec![atom("bd"), atom("bd"), atom("bd"), atom("sd")]));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(Pattern::parse("[bd sd"), Err(PatternError::SyntaxError { .. })));
+        assert!(matches!(Pattern::parse("bd ]"), Err(PatternError::SyntaxError { .. })));
+        assert!(matches!(Pattern::parse("bd(5,3)"), Err(PatternError::InvalidEuclid { .. })));
+        assert!(matches!(Pattern::parse("bd*0"), Err(PatternError::InvalidSpeed(_))));
+    }
+
+    #[test]
+    fn test_parse_rejects_negative_and_oversized_counts() {
+        for source in ["bd(3,-8)", "bd(-1,8)", "bd(3,4294967297)", "bd(3,100000)", "bd!0", "bd!100000", "bd(3,8,99999999999)"] {
+            assert!(
+                matches!(Pattern::parse(source), Err(PatternError::SyntaxError { .. })),
+                "{} should be rejected",
+                source
+            );
+        }
+        assert!(Pattern::parse("bd(3,8,-2)").is_ok());
+        assert!(Pattern::parse(&format!("bd{}", "!".repeat(300))).is_err());
+    }
+
+    #[test]
+    fn test_parse_rejects_nested_replication_blowup() {
+        assert!(Pattern::parse("[bd!200]!50").is_ok());
+        assert!(matches!(Pattern::parse("[[bd!256]!256]!256"), Err(PatternError::SyntaxError { .. })));
+        assert!(matches!(Pattern::parse("[bd(200,256)]!256"), Err(PatternError::SyntaxError { .. })));
+    }
+
+    #[test]
+    fn test_parse_rejects_dense_nested_speed() {
+        assert!(Pattern::parse("[bd*16]*16").is_ok());
+        assert!(matches!(Pattern::parse("[[[bd*256]*256]*256]"), Err(PatternError::SyntaxError { .. })));
+        assert!(matches!(Pattern::parse("bd*100000"), Err(PatternError::SyntaxError { .. })));
+        // Overflowing and underflowing factors are rejected one way or another
+        for source in [format!("bd*{}", "9".repeat(400)), format!("~/0.{}1 bd", "0".repeat(320))] {
+            assert!(
+                Pattern::parse(&source).is_err(),
+                "{} should be rejected",
+                source
+            );
+        }
+    }
+}
@@ -0,0 +1,469 @@
+/*!
+ * PLUGIN HOST
+ *
+ * Runs an instrument plugin as a streaming source on the synthesizer's
+ * audio thread. A cloneable handle sends note events stamped with
+ * absolute frames on the plugin clock, so a batch keeps its timing no
+ * matter when the audio thread drains it; the source renders the plugin
+ * in fixed blocks and hands the audio to the synth mixer frame by frame.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::{InstrumentEvent, InstrumentEventKind, InstrumentPlugin, NoteId, PluginError, PluginResult};
+use crate::pattern::PatternEvent;
+use crate::synthesis::{StereoSource, SynthController, VoiceId};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+
+/// Frames rendered per plugin process call
+pub const PLUGIN_BLOCK_SIZE: usize = 256;
+
+/// Level below which a released plugin counts as silent
+const SILENCE_THRESHOLD: f32 = 1.0e-5;
+
+/// An event waiting for its frame on the plugin clock
+#[derive(Debug, Clone, Copy)]
+struct ScheduledEvent {
+    frame: u64,
+    kind: InstrumentEventKind,
+}
+
+impl ScheduledEvent {
+    /// Queue order: by frame, with releases ahead of note starts on the same frame
+    fn order(&self) -> (u64, bool) {
+        (self.frame, matches!(self.kind, InstrumentEventKind::NoteOn { .. }))
+    }
+
+    fn belongs_to(&self, id: NoteId) -> bool {
+        match self.kind {
+            InstrumentEventKind::NoteOn { note_id, .. } | InstrumentEventKind::NoteOff { note_id, .. } => note_id == id,
+            InstrumentEventKind::AllNotesOff => false,
+        }
+    }
+}
+
+/// Control handle for sending notes to a hosted plugin
+#[derive(Debug, Clone)]
+pub struct PluginHandle {
+    events: Sender<ScheduledEvent>,
+    next_note_id: Arc<AtomicU64>,
+    frame_clock: Arc<AtomicU64>,
+    failed: Arc<AtomicBool>,
+    name: String,
+    sample_rate: u32,
+}
+
+impl PluginHandle {
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Frames the plugin had rendered at the end of its last block
+    pub fn frame_clock(&self) -> u64 {
+        self.frame_clock.load(Ordering::Acquire)
+    }
+
+    /// Start `key` after `delay` seconds, releasing it after `duration` seconds
+    pub fn note_on(&self, key: u8, velocity: f32, delay: f64, duration: Option<f64>) -> PluginResult<NoteId> {
+        let start_frame = self.frame_clock().saturating_add(self.seconds_to_samples(delay));
+        self.note_on_at(key, velocity, start_frame, duration.map(|seconds| self.seconds_to_samples(seconds)))
+    }
+
+    /// Start `key` at absolute frame `start_frame` on the plugin clock, releasing it after `duration_frames`
+    pub fn note_on_at(
+        &self,
+        key: u8,
+        velocity: f32,
+        start_frame: u64,
+        duration_frames: Option<u64>,
+    ) -> PluginResult<NoteId> {
+        if key > 127 {
+            return Err(PluginError::InvalidNote(format!("MIDI key {} is out of range", key)));
+        }
+
+        let note_id = self.next_note_id.fetch_add(1, Ordering::Relaxed);
+        self.send(start_frame, InstrumentEventKind::NoteOn { note_id, key, velocity: velocity.clamp(0.0, 1.0) })?;
+        if let Some(duration) = duration_frames {
+            // At least one frame long, so the release never sorts ahead of its own start
+            let end_frame = start_frame.saturating_add(duration.max(1));
+            self.send(end_frame, InstrumentEventKind::NoteOff { note_id, key })?;
+        }
+        Ok(note_id)
+    }
+
+    /// Release a note now, cancelling its start and release if they are still queued
+    pub fn note_off(&self, note_id: NoteId, key: u8) -> PluginResult<()> {
+        self.send(0, InstrumentEventKind::NoteOff { note_id, key })
+    }
+
+    /// Release every note now and drop everything still queued
+    pub fn all_notes_off(&self) -> PluginResult<()> {
+        self.send(0, InstrumentEventKind::AllNotesOff)
+    }
+
+    /// Play pattern events, timed relative to the first event's cycle and
+    /// anchored once to the plugin clock so the whole batch stays in time.
+    ///
+    /// Events whose values are not notes are skipped.
+    pub fn play_pattern_events(
+        &self,
+        events: &[PatternEvent],
+        cycles_per_second: f64,
+        velocity: f32,
+    ) -> PluginResult<Vec<NoteId>> {
+        if !(cycles_per_second.is_finite() && cycles_per_second > 0.0) {
+            return Err(PluginError::InvalidNote(format!(
+                "cycles per second must be positive, got {}",
+                cycles_per_second
+            )));
+        }
+
+        let origin = events.iter().map(|e| e.begin.floor()).fold(f64::INFINITY, f64::min);
+        let now = self.frame_clock();
+        let mut ids = Vec::new();
+        for event in events {
+            let Some(note) = event.midi_note() else {
+                continue;
+            };
+            let key = note.round();
+            if !(0.0..=127.0).contains(&key) {
+                continue;
+            }
+            let start_frame = now.saturating_add(self.seconds_to_samples((event.begin - origin) / cycles_per_second));
+            let duration_frames = self.seconds_to_samples(event.duration() / cycles_per_second);
+            ids.push(self.note_on_at(key as u8, velocity, start_frame, Some(duration_frames))?);
+        }
+        Ok(ids)
+    }
+
+    fn seconds_to_samples(&self, seconds: f64) -> u64 {
+        (seconds.max(0.0) * self.sample_rate as f64).round() as u64
+    }
+
+    /// Queue an event for absolute `frame`; frames already rendered play at the next block
+    fn send(&self, frame: u64, kind: InstrumentEventKind) -> PluginResult<()> {
+        if self.failed.load(Ordering::Acquire) {
+            return Err(PluginError::ProcessError(format!("plugin '{}' stopped after a processing error", self.name)));
+        }
+        self.events.send(ScheduledEvent { frame, kind }).map_err(|_| PluginError::HostStopped)
+    }
+}
+
+/// Audio-thread side: renders the plugin in blocks as a stereo source
+#[derive(Debug)]
+pub struct PluginSource {
+    plugin: Box<dyn InstrumentPlugin>,
+    events: Receiver<ScheduledEvent>,
+    scheduled: Vec<ScheduledEvent>,
+    block_events: Vec<InstrumentEvent>,
+    left: Vec<f32>,
+    right: Vec<f32>,
+    position: usize,
+    frame_clock: u64,
+    /// Frame clock shared with the handles
+    published_clock: Arc<AtomicU64>,
+    releasing: bool,
+    failed: Arc<AtomicBool>,
+}
+
+impl PluginSource {
+    /// Frames rendered so far
+    pub fn frame_clock(&self) -> u64 {
+        self.frame_clock
+    }
+
+    fn render_block(&mut self) {
+        for event in self.events.try_iter() {
+            // A release that is already due cancels whatever its note still has queued
+            if event.frame <= self.frame_clock {
+                match event.kind {
+                    InstrumentEventKind::NoteOff { note_id, .. } => {
+                        self.scheduled.retain(|queued| !queued.belongs_to(note_id));
+                    },
+                    InstrumentEventKind::AllNotesOff => self.scheduled.clear(),
+                    InstrumentEventKind::NoteOn { .. } => {},
+                }
+            }
+            self.scheduled.push(event);
+        }
+
+        let block_end = self.frame_clock + PLUGIN_BLOCK_SIZE as u64;
+        self.scheduled.sort_unstable_by_key(ScheduledEvent::order);
+        let due = self.scheduled.partition_point(|event| event.frame < block_end);
+        self.block_events.clear();
+        self.block_events.extend(self.scheduled.drain(..due).map(|event| InstrumentEvent {
+            sample_offset: event.frame.saturating_sub(self.frame_clock) as usize,
+            kind: event.kind,
+        }));
+
+        self.left.fill(0.0);
+        self.right.fill(0.0);
+        if let Err(error) = self.plugin.process(&self.block_events, &mut self.left, &mut self.right) {
+            log::error!("kira plugin '{}' failed: {}", self.plugin.name(), error);
+            self.failed.store(true, Ordering::Release);
+        }
+
+        self.frame_clock = block_end;
+        self.published_clock.store(block_end, Ordering::Release);
+        self.position = 0;
+    }
+
+    fn block_is_silent(&self) -> bool {
+        self.left
+            .iter()
+            .chain(&self.right)
+            .all(|sample| sample.abs() < SILENCE_THRESHOLD)
+    }
+}
+
+impl StereoSource for PluginSource {
+    fn next_frame(&mut self) -> Option<(f32, f32)> {
+        if self.position >= PLUGIN_BLOCK_SIZE {
+            self.render_block();
+            let finished = self.releasing && self.scheduled.is_empty() && self.block_is_silent();
+            if finished || self.failed.load(Ordering::Relaxed) {
+                return None;
+            }
+        }
+
+        let frame = (self.left[self.position], self.right[self.position]);
+        self.position += 1;
+        Some(frame)
+    }
+
+    /// Release every note and finish once the plugin's tail has decayed
+    fn release(&mut self) {
+        self.releasing = true;
+        self.scheduled.clear();
+        self.scheduled.push(ScheduledEvent { frame: self.frame_clock, kind: InstrumentEventKind::AllNotesOff });
+    }
+}
+
+/// Prepare `plugin` and create a connected handle/source pair
+pub fn host_plugin(
+    mut plugin: Box<dyn InstrumentPlugin>,
+    sample_rate: u32,
+) -> PluginResult<(PluginHandle, PluginSource)> {
+    plugin.prepare(sample_rate, PLUGIN_BLOCK_SIZE)?;
+
+    let (sender, receiver) = mpsc::channel();
+    let frame_clock = Arc::new(AtomicU64::new(0));
+    let failed = Arc::new(AtomicBool::new(false));
+    let handle = PluginHandle {
+        events: sender,
+        next_note_id: Arc::new(AtomicU64::new(1)),
+        frame_clock: Arc::clone(&frame_clock),
+        failed: Arc::clone(&failed),
+        name: plugin.name().to_string(),
+        sample_rate,
+    };
+    let source = PluginSource {
+        plugin,
+        events: receiver,
+        scheduled: Vec::new(),
+        block_events: Vec::with_capacity(PLUGIN_BLOCK_SIZE),
+        left: vec![0.0; PLUGIN_BLOCK_SIZE],
+        right: vec![0.0; PLUGIN_BLOCK_SIZE],
+        // Render a fresh block on the first frame
+        position: PLUGIN_BLOCK_SIZE,
+        frame_clock: 0,
+        published_clock: frame_clock,
+        releasing: false,
+        failed,
+    };
+    Ok((handle, source))
+}
+
+/// Host `plugin` on a running synthesizer.
+///
+/// Returns the note handle and the source's voice ID; `note_off` on that ID
+/// releases the plugin and removes it once its tail has decayed.
+pub fn host_plugin_on_synth(
+    plugin: Box<dyn InstrumentPlugin>,
+    synth: &SynthController,
+) -> PluginResult<(PluginHandle, VoiceId)> {
+    let (handle, source) = host_plugin(plugin, synth.sample_rate())?;
+    let voice_id = synth
+        .play_source(Box::new(source))
+        .map_err(|e| PluginError::ProcessError(e.to_string()))?;
+    Ok((handle, voice_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::BuiltinInstrument;
+    use crate::synthesis::NoteSettings;
+
+    fn builtin() -> Box<dyn InstrumentPlugin> {
+        Box::new(BuiltinInstrument::new("Test", NoteSettings::new(440.0, 1.0)))
+    }
+
+    #[test]
+    fn test_events_land_on_their_frame() {
+        let (handle, mut source) = host_plugin(builtin(), 1000).unwrap();
+        handle.note_on(69, 1.0, 0.3, Some(0.1)).unwrap();
+
+        let frames: Vec<(f32, f32)> = (0..600).map(|_| source.next_frame().unwrap()).collect();
+        assert!(frames[..300].iter().all(|(l, r)| *l == 0.0 && *r == 0.0));
+        assert!(frames[300..400].iter().any(|(l, _)| *l != 0.0));
+        assert_eq!(source.frame_clock(), 768);
+    }
+
+    #[test]
+    fn test_events_are_stamped_when_sent() {
+        let (handle, mut source) = host_plugin(builtin(), 1000).unwrap();
+        for _ in 0..300 {
+            source.next_frame().unwrap();
+        }
+        assert_eq!(handle.frame_clock(), 512);
+
+        // Queued at clock 512 for frame 612, then drained two blocks later: must still start at 612
+        handle.note_on(69, 1.0, 0.1, Some(0.1)).unwrap();
+        let frames: Vec<(f32, f32)> = (300..1024).map(|_| source.next_frame().unwrap()).collect();
+        let first_sound = frames.iter().position(|(l, _)| *l != 0.0).unwrap() + 300;
+        assert!((612..620).contains(&first_sound), "note started at {}", first_sound);
+    }
+
+    #[derive(Debug)]
+    struct FailingPlugin;
+
+    impl InstrumentPlugin for FailingPlugin {
+        fn name(&self) -> &str {
+            "Failing"
+        }
+
+        fn prepare(&mut self, _sample_rate: u32, _max_block_size: usize) -> PluginResult<()> {
+            Ok(())
+        }
+
+        fn process(&mut self, _events: &[InstrumentEvent], _left: &mut [f32], _right: &mut [f32]) -> PluginResult<()> {
+            Err(PluginError::ProcessError("boom".to_string()))
+        }
+    }
+
+    /// Records the events it receives, producing no audio
+    #[derive(Debug, Default)]
+    struct RecordingPlugin {
+        received: Arc<std::sync::Mutex<Vec<InstrumentEventKind>>>,
+    }
+
+    impl InstrumentPlugin for RecordingPlugin {
+        fn name(&self) -> &str {
+            "Recording"
+        }
+
+        fn prepare(&mut self, _sample_rate: u32, _max_block_size: usize) -> PluginResult<()> {
+            Ok(())
+        }
+
+        fn process(&mut self, events: &[InstrumentEvent], _left: &mut [f32], _right: &mut [f32]) -> PluginResult<()> {
+            self.received.lock().unwrap().extend(events.iter().map(|event| event.kind));
+            Ok(())
+        }
+    }
+
+    fn recording() -> (PluginHandle, PluginSource, Arc<std::sync::Mutex<Vec<InstrumentEventKind>>>) {
+        let plugin = RecordingPlugin::default();
+        let received = Arc::clone(&plugin.received);
+        let (handle, source) = host_plugin(Box::new(plugin), 1000).unwrap();
+        (handle, source, received)
+    }
+
+    #[test]
+    fn test_note_off_cancels_queued_events() {
+        let (handle, mut source, received) = recording();
+        let cancelled = handle.note_on(60, 1.0, 0.5, Some(0.1)).unwrap();
+        let kept = handle.note_on(64, 1.0, 0.5, Some(0.1)).unwrap();
+        handle.note_off(cancelled, 60).unwrap();
+
+        for _ in 0..1000 {
+            source.next_frame().unwrap();
+        }
+        let received = received.lock().unwrap();
+        assert_eq!(received[0], InstrumentEventKind::NoteOff { note_id: cancelled, key: 60 });
+        assert_eq!(
+            received[1..],
+            [
+                InstrumentEventKind::NoteOn { note_id: kept, key: 64, velocity: 1.0 },
+                InstrumentEventKind::NoteOff { note_id: kept, key: 64 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_all_notes_off_clears_queue() {
+        let (handle, mut source, received) = recording();
+        handle.note_on(60, 1.0, 0.5, Some(0.1)).unwrap();
+        handle.note_on(64, 1.0, 0.7, None).unwrap();
+        handle.all_notes_off().unwrap();
+
+        for _ in 0..1000 {
+            source.next_frame().unwrap();
+        }
+        assert_eq!(*received.lock().unwrap(), [InstrumentEventKind::AllNotesOff]);
+    }
+
+    #[test]
+    fn test_release_sorts_ahead_of_a_start_on_the_same_frame() {
+        let (handle, mut source, received) = recording();
+        let first = handle.note_on_at(60, 1.0, 100, Some(100)).unwrap();
+        let second = handle.note_on_at(60, 1.0, 200, Some(1)).unwrap();
+
+        for _ in 0..512 {
+            source.next_frame().unwrap();
+        }
+        assert_eq!(
+            *received.lock().unwrap(),
+            [
+                InstrumentEventKind::NoteOn { note_id: first, key: 60, velocity: 1.0 },
+                InstrumentEventKind::NoteOff { note_id: first, key: 60 },
+                InstrumentEventKind::NoteOn { note_id: second, key: 60, velocity: 1.0 },
+                InstrumentEventKind::NoteOff { note_id: second, key: 60 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_processing_failure_reaches_the_handle() {
+        let (handle, mut source) = host_plugin(Box::new(FailingPlugin), 1000).unwrap();
+        assert!(source.next_frame().is_none());
+        assert!(matches!(handle.note_on(60, 1.0, 0.0, None), Err(PluginError::ProcessError(_))));
+    }
+
+    #[test]
+    fn test_pattern_routing_and_release() {
+        let (handle, mut source) = host_plugin(builtin(), 8000).unwrap();
+        let events = crate::pattern::Pattern::parse("c4 bd e4").unwrap().query_cycle(0);
+        assert_eq!(handle.play_pattern_events(&events, 1.0, 0.8).unwrap().len(), 2);
+        assert!(handle.note_on(200, 1.0, 0.0, None).is_err());
+
+        for _ in 0..1000 {
+            source.next_frame().unwrap();
+        }
+        source.release();
+        let tail = (0..100_000).take_while(|_| source.next_frame().is_some()).count();
+        assert!(tail < 100_000);
+    }
+
+    #[test]
+    fn test_plugin_mixes_into_synth_engine() {
+        let (controller, mut engine) = crate::synthesis::synth_channel(48000, 2);
+        let (handle, voice_id) = host_plugin_on_synth(builtin(), &controller).unwrap();
+        handle.note_on(60, 1.0, 0.0, None).unwrap();
+
+        let mut buffer = vec![0.0; 2048];
+        engine.render(&mut buffer);
+        assert_eq!(engine.active_sources(), 1);
+        assert!(buffer.iter().any(|sample| *sample != 0.0));
+
+        controller.note_off(voice_id).unwrap();
+        for _ in 0..100 {
+            engine.render(&mut buffer);
+        }
+        assert_eq!(engine.active_sources(), 0);
+    }
+}
@@ -0,0 +1,151 @@
+/*!
+ * INSTRUMENT PLUGIN INTERFACE
+ *
+ * The contract every hosted instrument implements: prepare once for a
+ * sample rate, then render stereo blocks with the note events that fall
+ * inside each block. Mirrors the VST3 process call so external plugins
+ * and built-in instruments are interchangeable.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::{PluginError, PluginResult};
+use crate::pattern::midi_to_frequency;
+use crate::synthesis::{NoteSettings, VoiceAllocator, DEFAULT_MAX_VOICES};
+
+/// Host-assigned identifier for a sounding note
+pub type NoteId = u64;
+
+/// What a note event does
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InstrumentEventKind {
+    NoteOn { note_id: NoteId, key: u8, velocity: f32 },
+    NoteOff { note_id: NoteId, key: u8 },
+    AllNotesOff,
+}
+
+/// A note event positioned within the block being processed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InstrumentEvent {
+    /// Frame offset from the start of the block
+    pub sample_offset: usize,
+    pub kind: InstrumentEventKind,
+}
+
+/// A hosted instrument rendering stereo audio in blocks
+pub trait InstrumentPlugin: Send + std::fmt::Debug {
+    fn name(&self) -> &str;
+
+    /// Called once before processing, and again if the sample rate changes
+    fn prepare(&mut self, sample_rate: u32, max_block_size: usize) -> PluginResult<()>;
+
+    /// Render one block; `events` are sorted by `sample_offset` and lie within the block
+    fn process(&mut self, events: &[InstrumentEvent], left: &mut [f32], right: &mut [f32]) -> PluginResult<()>;
+}
+
+/// Oscillator instrument built on the synthesizer's voice allocator
+#[derive(Debug, Clone)]
+pub struct BuiltinInstrument {
+    name: String,
+    template: NoteSettings,
+    allocator: VoiceAllocator,
+    scratch: Vec<f32>,
+    frame_clock: u64,
+}
+
+impl BuiltinInstrument {
+    /// Instrument whose notes use `template`'s waveform, envelope and pan
+    pub fn new(name: &str, template: NoteSettings) -> Self {
+        Self {
+            name: name.to_string(),
+            template,
+            allocator: VoiceAllocator::new(DEFAULT_MAX_VOICES, crate::constants::SAMPLE_RATE),
+            scratch: Vec::new(),
+            frame_clock: 0,
+        }
+    }
+
+    pub fn active_voices(&self) -> usize {
+        self.allocator.active_voices()
+    }
+
+    /// Render `frames` frames into the block starting at `start`
+    fn render_span(&mut self, left: &mut [f32], right: &mut [f32], start: usize, frames: usize) {
+        if frames == 0 {
+            return;
+        }
+        self.scratch.resize(frames * 2, 0.0);
+        self.allocator.render(&mut self.scratch, 2, 1.0);
+        for (index, frame) in self.scratch.chunks_exact(2).enumerate() {
+            left[start + index] = frame[0];
+            right[start + index] = frame[1];
+        }
+        self.frame_clock += frames as u64;
+    }
+}
+
+impl InstrumentPlugin for BuiltinInstrument {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn prepare(&mut self, sample_rate: u32, max_block_size: usize) -> PluginResult<()> {
+        if sample_rate == 0 {
+            return Err(PluginError::ProcessError("sample rate must be positive".to_string()));
+        }
+        self.allocator = VoiceAllocator::new(DEFAULT_MAX_VOICES, sample_rate);
+        self.scratch = Vec::with_capacity(max_block_size * 2);
+        self.frame_clock = 0;
+        Ok(())
+    }
+
+    fn process(&mut self, events: &[InstrumentEvent], left: &mut [f32], right: &mut [f32]) -> PluginResult<()> {
+        let frames = left.len().min(right.len());
+        let mut position = 0;
+
+        for event in events {
+            let offset = event.sample_offset.min(frames);
+            self.render_span(left, right, position, offset.saturating_sub(position));
+            position = position.max(offset);
+
+            match event.kind {
+                InstrumentEventKind::NoteOn { note_id, key, velocity } => {
+                    let settings = NoteSettings {
+                        frequency: midi_to_frequency(key as f64),
+                        velocity,
+                        ..self.template
+                    };
+                    self.allocator.note_on(note_id, settings, None, self.frame_clock);
+                },
+                InstrumentEventKind::NoteOff { note_id, .. } => self.allocator.note_off(note_id),
+                InstrumentEventKind::AllNotesOff => self.allocator.all_notes_off(),
+            }
+        }
+
+        self.render_span(left, right, position, frames - position);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_instrument_starts_notes_at_offset() {
+        let mut instrument = BuiltinInstrument::new("Test", NoteSettings::new(440.0, 1.0));
+        instrument.prepare(48000, 64).unwrap();
+
+        let events = [InstrumentEvent {
+            sample_offset: 32,
+            kind: InstrumentEventKind::NoteOn { note_id: 1, key: 69, velocity: 1.0 },
+        }];
+        let (mut left, mut right) = (vec![0.0; 64], vec![0.0; 64]);
+        instrument.process(&events, &mut left, &mut right).unwrap();
+
+        assert!(left[..32].iter().all(|sample| *sample == 0.0));
+        assert!(left[32..].iter().any(|sample| *sample != 0.0));
+        assert_eq!(instrument.active_voices(), 1);
+    }
+}
@@ -0,0 +1,52 @@
+/*!
+ * INSTRUMENT PLUGIN HOSTING
+ *
+ * Host-side bridge for external instruments: pattern-engine notes are
+ * routed to a plugin as sample-offset events, and the plugin's rendered
+ * audio is streamed back through the real-time synthesizer's output.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ *
+ * Features:
+ * - Block-based instrument plugin interface with sample-accurate events
+ * - Plugins hosted as streaming sources on the synth audio thread
+ * - Built-in oscillator instrument implementing the plugin interface
+ * - VST3 bundle discovery in the platform's standard plugin folders
+ * - VST3 instruments loaded through the plugin's COM interfaces
+ */
+
+mod host;
+mod instrument;
+mod vst3;
+mod vst3_com;
+mod vst3_module;
+
+pub use host::*;
+pub use instrument::*;
+pub use vst3::*;
+pub use vst3_module::*;
+
+// Plugin-related error types
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum PluginError {
+    #[error("Plugin not found: {0}")]
+    NotFound(String),
+
+    #[error("Invalid plugin bundle: {0}")]
+    InvalidBundle(String),
+
+    #[error("Unsupported plugin: {0}")]
+    Unsupported(String),
+
+    #[error("Invalid plugin note: {0}")]
+    InvalidNote(String),
+
+    #[error("Plugin processing failed: {0}")]
+    ProcessError(String),
+
+    #[error("Plugin host has stopped")]
+    HostStopped,
+}
+
+pub type PluginResult<T> = std::result::Result<T, PluginError>;
@@ -0,0 +1,323 @@
+/*!
+ * VST3 BUNDLE DISCOVERY
+ *
+ * Locates VST3 bundles in the platform's standard plugin folders,
+ * resolves the module binary for the current architecture and reads the
+ * bundle's moduleinfo.json to tell instruments from effects. Bundles
+ * without one are reported as unknown: only loading the module can say
+ * what they contain.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::{InstrumentPlugin, PluginError, PluginResult, Vst3Module};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Bundle extension used by VST3 plugins
+pub const VST3_EXTENSION: &str = "vst3";
+
+/// Class category of processors in moduleinfo.json
+pub(super) const AUDIO_MODULE_CATEGORY: &str = "Audio Module Class";
+
+/// One exported class from a bundle's moduleinfo.json
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Vst3ClassInfo {
+    #[serde(rename = "CID")]
+    pub cid: String,
+    #[serde(rename = "Category")]
+    pub category: String,
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Sub Categories", default)]
+    pub sub_categories: Vec<String>,
+}
+
+impl Vst3ClassInfo {
+    pub fn is_instrument(&self) -> bool {
+        self.category == AUDIO_MODULE_CATEGORY && self.sub_categories.iter().any(|sub| sub.starts_with("Instrument"))
+    }
+}
+
+/// Contents of a bundle's Contents/Resources/moduleinfo.json
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Vst3ModuleInfo {
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Version", default)]
+    pub version: String,
+    #[serde(rename = "Classes", default)]
+    pub classes: Vec<Vst3ClassInfo>,
+}
+
+/// What a bundle contains, as far as its moduleinfo.json tells
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Vst3BundleKind {
+    Instrument,
+    /// Lists its classes, none of them instruments
+    Effect,
+    /// No readable moduleinfo.json; may still contain an instrument
+    Unknown,
+}
+
+/// A VST3 bundle on disk
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vst3Bundle {
+    pub name: String,
+    pub path: PathBuf,
+    /// Module binary for this platform and architecture
+    pub binary: PathBuf,
+    /// Parsed moduleinfo.json, when the bundle ships one
+    pub module_info: Option<Vst3ModuleInfo>,
+}
+
+impl Vst3Bundle {
+    /// Open the bundle at `path`, checking it contains a binary for this platform
+    pub fn open<P: AsRef<Path>>(path: P) -> PluginResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Err(PluginError::NotFound(path.display().to_string()));
+        }
+        if path.extension().and_then(|ext| ext.to_str()) != Some(VST3_EXTENSION) {
+            return Err(PluginError::InvalidBundle(format!("{} is not a .vst3 bundle", path.display())));
+        }
+
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| PluginError::InvalidBundle(path.display().to_string()))?
+            .to_string();
+
+        let binary = bundle_binary_path(path, &name);
+        if !binary.is_file() {
+            return Err(PluginError::InvalidBundle(format!(
+                "{} has no module binary at {}",
+                path.display(),
+                binary.display()
+            )));
+        }
+
+        let module_info = std::fs::read_to_string(path.join("Contents").join("Resources").join("moduleinfo.json"))
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok());
+
+        Ok(Self {
+            name,
+            path: path.to_path_buf(),
+            binary,
+            module_info,
+        })
+    }
+
+    /// Instrument classes listed in moduleinfo.json
+    pub fn instrument_classes(&self) -> Vec<&Vst3ClassInfo> {
+        self.module_info
+            .iter()
+            .flat_map(|info| &info.classes)
+            .filter(|class| class.is_instrument())
+            .collect()
+    }
+
+    /// Whether the bundle is known to contain an instrument
+    pub fn is_instrument(&self) -> bool {
+        !self.instrument_classes().is_empty()
+    }
+
+    pub fn kind(&self) -> Vst3BundleKind {
+        match &self.module_info {
+            None => Vst3BundleKind::Unknown,
+            Some(_) if self.is_instrument() => Vst3BundleKind::Instrument,
+            Some(_) => Vst3BundleKind::Effect,
+        }
+    }
+
+    /// Load the module and create its instrument processor, ready for `host_plugin`
+    pub fn instantiate(&self) -> PluginResult<Box<dyn InstrumentPlugin>> {
+        Ok(Box::new(Vst3Module::load(self)?.create_instrument()?))
+    }
+}
+
+/// Module binary location inside a bundle, per the VST3 bundle layout
+fn bundle_binary_path(bundle: &Path, name: &str) -> PathBuf {
+    let contents = bundle.join("Contents");
+    if cfg!(target_os = "macos") {
+        contents.join("MacOS").join(name)
+    } else if cfg!(target_os = "windows") {
+        contents
+            .join(format!("{}-win", bundle_architecture()))
+            .join(format!("{}.{}", name, VST3_EXTENSION))
+    } else {
+        contents
+            .join(format!("{}-linux", bundle_architecture()))
+            .join(format!("{}.so", name))
+    }
+}
+
+/// Architecture folder prefix: Windows names follow Visual Studio, Linux follows `uname -m`
+fn bundle_architecture() -> &'static str {
+    match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("windows", "aarch64") => "arm64",
+        ("windows", arch) => arch,
+        (_, "x86") => "i386",
+        (_, "arm") => "armv7l",
+        (_, arch) => arch,
+    }
+}
+
+/// Standard VST3 folders for this platform, user folder first
+pub fn vst3_search_paths() -> Vec<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+
+    if cfg!(target_os = "macos") {
+        home.map(|home| home.join("Library/Audio/Plug-Ins/VST3"))
+            .into_iter()
+            .chain([PathBuf::from("/Library/Audio/Plug-Ins/VST3")])
+            .collect()
+    } else if cfg!(target_os = "windows") {
+        std::env::var_os("COMMONPROGRAMFILES")
+            .map(|common| PathBuf::from(common).join("VST3"))
+            .into_iter()
+            .collect()
+    } else {
+        home.map(|home| home.join(".vst3"))
+            .into_iter()
+            .chain([PathBuf::from("/usr/lib/vst3"), PathBuf::from("/usr/local/lib/vst3")])
+            .collect()
+    }
+}
+
+/// Find every valid bundle in `directories`, searching subfolders
+pub fn scan_vst3_bundles<P: AsRef<Path>>(directories: &[P]) -> Vec<Vst3Bundle> {
+    let mut bundles = Vec::new();
+    let mut visited = HashSet::new();
+    for directory in directories {
+        collect_bundles(directory.as_ref(), &mut visited, &mut bundles);
+    }
+    bundles.sort_by(|a, b| a.name.cmp(&b.name));
+    bundles
+}
+
+/// Walk `directory`, visiting each real folder once so symlink loops terminate
+/// and bundles linked from several places are listed once
+fn collect_bundles(directory: &Path, visited: &mut HashSet<PathBuf>, bundles: &mut Vec<Vst3Bundle>) {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return;
+    };
+
+    for path in entries.flatten().map(|entry| entry.path()) {
+        let is_bundle = path.extension().and_then(|ext| ext.to_str()) == Some(VST3_EXTENSION);
+        if !is_bundle && !path.is_dir() {
+            continue;
+        }
+        let Ok(canonical) = path.canonicalize() else {
+            continue;
+        };
+        if visited.contains(&canonical) {
+            continue;
+        }
+
+        if is_bundle {
+            // Only a bundle that opens claims its folder, so a broken alias can't hide it
+            if let Ok(bundle) = Vst3Bundle::open(&path) {
+                visited.insert(canonical);
+                bundles.push(bundle);
+            }
+        } else {
+            visited.insert(canonical);
+            collect_bundles(&path, visited, bundles);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_bundle(root: &Path, name: &str, module_info: Option<&str>) -> PathBuf {
+        let bundle = root.join(format!("{}.vst3", name));
+        let binary = bundle_binary_path(&bundle, name);
+        std::fs::create_dir_all(binary.parent().unwrap()).unwrap();
+        std::fs::write(&binary, b"").unwrap();
+        if let Some(json) = module_info {
+            let resources = bundle.join("Contents").join("Resources");
+            std::fs::create_dir_all(&resources).unwrap();
+            std::fs::write(resources.join("moduleinfo.json"), json).unwrap();
+        }
+        bundle
+    }
+
+    #[test]
+    fn test_scan_finds_bundles_and_instruments() {
+        let root = std::env::temp_dir().join(format!("kira_vst3_scan_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+
+        make_bundle(
+            &root.join("Vendor"),
+            "Choir",
+            Some(
+                r#"{"Name": "Choir", "Version": "1.0", "Classes": [
+                    {"CID": "ABCD", "Category": "Audio Module Class", "Name": "Choir", "Sub Categories": ["Instrument", "Synth"]}
+                ]}"#,
+            ),
+        );
+        make_bundle(
+            &root,
+            "Delay",
+            Some(
+                r#"{"Name": "Delay", "Classes": [
+                    {"CID": "EF01", "Category": "Audio Module Class", "Name": "Delay", "Sub Categories": ["Fx", "Delay"]}
+                ]}"#,
+            ),
+        );
+        make_bundle(&root, "Reverb", None);
+        std::fs::create_dir_all(root.join("Broken.vst3")).unwrap();
+
+        let bundles = scan_vst3_bundles(&[&root]);
+        let _ = std::fs::remove_dir_all(&root);
+
+        let kinds: Vec<(&str, Vst3BundleKind)> = bundles.iter().map(|b| (b.name.as_str(), b.kind())).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("Choir", Vst3BundleKind::Instrument),
+                ("Delay", Vst3BundleKind::Effect),
+                ("Reverb", Vst3BundleKind::Unknown),
+            ]
+        );
+        assert!(bundles[0].is_instrument());
+        // The placeholder binary is not a loadable module
+        assert!(matches!(bundles[0].instantiate(), Err(PluginError::InvalidBundle(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_survives_symlink_loops() {
+        let root = std::env::temp_dir().join(format!("kira_vst3_loop_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        make_bundle(&root.join("Vendor"), "Choir", None);
+        std::os::unix::fs::symlink(&root, root.join("Vendor").join("loop")).unwrap();
+        std::os::unix::fs::symlink(root.join("Vendor").join("Choir.vst3"), root.join("Alias.vst3")).unwrap();
+
+        let bundles = scan_vst3_bundles(&[&root]);
+        let _ = std::fs::remove_dir_all(&root);
+        assert_eq!(bundles.len(), 1);
+    }
+
+    #[test]
+    fn test_architecture_folders() {
+        let binary = bundle_binary_path(Path::new("Synth.vst3"), "Synth");
+        let folder = binary.parent().unwrap().file_name().unwrap().to_str().unwrap().to_string();
+        if cfg!(all(target_os = "windows", target_arch = "aarch64")) {
+            assert_eq!(folder, "arm64-win");
+        } else if cfg!(all(target_os = "linux", target_arch = "x86_64")) {
+            assert_eq!(folder, "x86_64-linux");
+        }
+    }
+
+    #[test]
+    fn test_open_rejects_missing_bundles() {
+        assert!(matches!(Vst3Bundle::open("/nonexistent/Missing.vst3"), Err(PluginError::NotFound(_))));
+    }
+}
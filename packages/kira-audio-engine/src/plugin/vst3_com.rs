@@ -0,0 +1,570 @@
+/*!
+ * VST3 COM INTERFACES
+ *
+ * The slice of the VST3 binary interface a host needs to run an
+ * instrument: interface IDs, vtable layouts for the plugin factory,
+ * component and audio processor, the structs passed to the process call,
+ * and the host-side objects (application context, event list, empty
+ * parameter changes) handed to the plugin. Layouts follow the SDK's
+ * pluginterfaces headers; every call uses the platform's COM calling
+ * convention.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use std::ffi::{c_char, c_void};
+use std::ptr::NonNull;
+
+pub type TResult = i32;
+
+/// 16-byte interface or class identifier
+pub type Tuid = [u8; 16];
+
+pub const RESULT_OK: TResult = 0;
+
+#[cfg(windows)]
+pub const NO_INTERFACE: TResult = 0x8000_4002_u32 as i32;
+#[cfg(not(windows))]
+pub const NO_INTERFACE: TResult = -1;
+
+#[cfg(windows)]
+pub const NOT_IMPLEMENTED: TResult = 0x8000_4001_u32 as i32;
+#[cfg(not(windows))]
+pub const NOT_IMPLEMENTED: TResult = 3;
+
+/// Identifier in the SDK's byte order: the COM GUID layout on Windows, big-endian elsewhere
+pub const fn uid(l1: u32, l2: u32, l3: u32, l4: u32) -> Tuid {
+    let (a, b, c, d) = (l1.to_be_bytes(), l2.to_be_bytes(), l3.to_be_bytes(), l4.to_be_bytes());
+    if cfg!(windows) {
+        [a[3], a[2], a[1], a[0], b[1], b[0], b[3], b[2], c[0], c[1], c[2], c[3], d[0], d[1], d[2], d[3]]
+    } else {
+        [a[0], a[1], a[2], a[3], b[0], b[1], b[2], b[3], c[0], c[1], c[2], c[3], d[0], d[1], d[2], d[3]]
+    }
+}
+
+pub const FUNKNOWN_IID: Tuid = uid(0x0000_0000, 0x0000_0000, 0xC000_0000, 0x0000_0046);
+pub const IPLUGIN_FACTORY_IID: Tuid = uid(0x7A4D_811C, 0x5211_4A1F, 0xAED9_D2EE, 0x0B43_BF9F);
+pub const IPLUGIN_FACTORY2_IID: Tuid = uid(0x0007_B650, 0xF24B_4C0B, 0xA464_EDB9, 0xF00B_2ABB);
+pub const ICOMPONENT_IID: Tuid = uid(0xE831_FF31, 0xF2D5_4301, 0x928E_BBEE, 0x2569_7802);
+pub const IAUDIO_PROCESSOR_IID: Tuid = uid(0x4204_3F99, 0xB7DA_453C, 0xA569_E79D, 0x9AAE_C33D);
+pub const IHOST_APPLICATION_IID: Tuid = uid(0x58E5_95CC, 0xDB2D_4969, 0x8B6A_AF8C, 0x36A6_64E5);
+pub const IEVENT_LIST_IID: Tuid = uid(0x3A2C_4214, 0x3463_49FE, 0xB2C4_F397, 0xB969_5A44);
+pub const IPARAMETER_CHANGES_IID: Tuid = uid(0xA477_9663, 0x0BB6_4A56, 0xB443_84A8, 0x466F_EB9D);
+
+/// Media types and bus directions for the bus queries
+pub const MEDIA_AUDIO: i32 = 0;
+pub const MEDIA_EVENT: i32 = 1;
+pub const BUS_INPUT: i32 = 0;
+pub const BUS_OUTPUT: i32 = 1;
+
+/// Process modes and sample sizes for `ProcessSetup`
+pub const PROCESS_REALTIME: i32 = 0;
+pub const SAMPLE_32: i32 = 0;
+
+/// Speaker arrangement with left and right speakers
+pub const SPEAKER_STEREO: u64 = 0x3;
+
+/// Event types
+pub const NOTE_ON_EVENT: u16 = 0;
+pub const NOTE_OFF_EVENT: u16 = 1;
+
+/// Name the host reports through IHostApplication
+const HOST_NAME: &str = "Kira Audio Engine";
+
+#[repr(C)]
+pub struct FUnknownVtbl {
+    pub query_interface: unsafe extern "system" fn(*mut c_void, *const Tuid, *mut *mut c_void) -> TResult,
+    pub add_ref: unsafe extern "system" fn(*mut c_void) -> u32,
+    pub release: unsafe extern "system" fn(*mut c_void) -> u32,
+}
+
+#[repr(C)]
+pub struct IPluginFactoryVtbl {
+    pub unknown: FUnknownVtbl,
+    pub get_factory_info: unsafe extern "system" fn(*mut c_void, *mut c_void) -> TResult,
+    pub count_classes: unsafe extern "system" fn(*mut c_void) -> i32,
+    pub get_class_info: unsafe extern "system" fn(*mut c_void, i32, *mut PClassInfo) -> TResult,
+    pub create_instance:
+        unsafe extern "system" fn(*mut c_void, *const c_char, *const c_char, *mut *mut c_void) -> TResult,
+}
+
+#[repr(C)]
+pub struct IPluginFactory2Vtbl {
+    pub factory: IPluginFactoryVtbl,
+    pub get_class_info2: unsafe extern "system" fn(*mut c_void, i32, *mut PClassInfo2) -> TResult,
+}
+
+#[repr(C)]
+pub struct IComponentVtbl {
+    pub unknown: FUnknownVtbl,
+    // IPluginBase
+    pub initialize: unsafe extern "system" fn(*mut c_void, *mut c_void) -> TResult,
+    pub terminate: unsafe extern "system" fn(*mut c_void) -> TResult,
+    // IComponent
+    pub get_controller_class_id: unsafe extern "system" fn(*mut c_void, *mut Tuid) -> TResult,
+    pub set_io_mode: unsafe extern "system" fn(*mut c_void, i32) -> TResult,
+    pub get_bus_count: unsafe extern "system" fn(*mut c_void, i32, i32) -> i32,
+    pub get_bus_info: unsafe extern "system" fn(*mut c_void, i32, i32, i32, *mut BusInfo) -> TResult,
+    pub get_routing_info: unsafe extern "system" fn(*mut c_void, *mut c_void, *mut c_void) -> TResult,
+    pub activate_bus: unsafe extern "system" fn(*mut c_void, i32, i32, i32, u8) -> TResult,
+    pub set_active: unsafe extern "system" fn(*mut c_void, u8) -> TResult,
+    pub set_state: unsafe extern "system" fn(*mut c_void, *mut c_void) -> TResult,
+    pub get_state: unsafe extern "system" fn(*mut c_void, *mut c_void) -> TResult,
+}
+
+#[repr(C)]
+pub struct IAudioProcessorVtbl {
+    pub unknown: FUnknownVtbl,
+    pub set_bus_arrangements: unsafe extern "system" fn(*mut c_void, *mut u64, i32, *mut u64, i32) -> TResult,
+    pub get_bus_arrangement: unsafe extern "system" fn(*mut c_void, i32, i32, *mut u64) -> TResult,
+    pub can_process_sample_size: unsafe extern "system" fn(*mut c_void, i32) -> TResult,
+    pub get_latency_samples: unsafe extern "system" fn(*mut c_void) -> u32,
+    pub setup_processing: unsafe extern "system" fn(*mut c_void, *mut ProcessSetup) -> TResult,
+    pub set_processing: unsafe extern "system" fn(*mut c_void, u8) -> TResult,
+    pub process: unsafe extern "system" fn(*mut c_void, *mut ProcessData) -> TResult,
+    pub get_tail_samples: unsafe extern "system" fn(*mut c_void) -> u32,
+}
+
+#[repr(C)]
+pub struct IEventListVtbl {
+    pub unknown: FUnknownVtbl,
+    pub get_event_count: unsafe extern "system" fn(*mut c_void) -> i32,
+    pub get_event: unsafe extern "system" fn(*mut c_void, i32, *mut Event) -> TResult,
+    pub add_event: unsafe extern "system" fn(*mut c_void, *mut Event) -> TResult,
+}
+
+#[repr(C)]
+pub struct IParameterChangesVtbl {
+    pub unknown: FUnknownVtbl,
+    pub get_parameter_count: unsafe extern "system" fn(*mut c_void) -> i32,
+    pub get_parameter_data: unsafe extern "system" fn(*mut c_void, i32) -> *mut c_void,
+    pub add_parameter_data: unsafe extern "system" fn(*mut c_void, *const u32, *mut i32) -> *mut c_void,
+}
+
+#[repr(C)]
+pub struct IHostApplicationVtbl {
+    pub unknown: FUnknownVtbl,
+    pub get_name: unsafe extern "system" fn(*mut c_void, *mut [u16; 128]) -> TResult,
+    pub create_instance: unsafe extern "system" fn(*mut c_void, *mut Tuid, *mut Tuid, *mut *mut c_void) -> TResult,
+}
+
+/// Class description from `IPluginFactory::getClassInfo`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PClassInfo {
+    pub cid: Tuid,
+    pub cardinality: i32,
+    pub category: [u8; 32],
+    pub name: [u8; 64],
+}
+
+/// Class description from `IPluginFactory2::getClassInfo2`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PClassInfo2 {
+    pub cid: Tuid,
+    pub cardinality: i32,
+    pub category: [u8; 32],
+    pub name: [u8; 64],
+    pub class_flags: u32,
+    /// '|'-separated, e.g. "Instrument|Synth"
+    pub sub_categories: [u8; 128],
+    pub vendor: [u8; 64],
+    pub version: [u8; 64],
+    pub sdk_version: [u8; 64],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct BusInfo {
+    pub media_type: i32,
+    pub direction: i32,
+    pub channel_count: i32,
+    pub name: [u16; 128],
+    pub bus_type: i32,
+    pub flags: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ProcessSetup {
+    pub process_mode: i32,
+    pub symbolic_sample_size: i32,
+    pub max_samples_per_block: i32,
+    pub sample_rate: f64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct AudioBusBuffers {
+    pub num_channels: i32,
+    pub silence_flags: u64,
+    /// `Sample32**`: one pointer per channel
+    pub channel_buffers: *mut *mut f32,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct ProcessData {
+    pub process_mode: i32,
+    pub symbolic_sample_size: i32,
+    pub num_samples: i32,
+    pub num_inputs: i32,
+    pub num_outputs: i32,
+    pub inputs: *mut AudioBusBuffers,
+    pub outputs: *mut AudioBusBuffers,
+    pub input_parameter_changes: *mut c_void,
+    pub output_parameter_changes: *mut c_void,
+    pub input_events: *mut c_void,
+    pub output_events: *mut c_void,
+    pub process_context: *mut c_void,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoteOnEvent {
+    pub channel: i16,
+    pub pitch: i16,
+    pub tuning: f32,
+    pub velocity: f32,
+    pub length: i32,
+    pub note_id: i32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoteOffEvent {
+    pub channel: i16,
+    pub pitch: i16,
+    pub velocity: f32,
+    pub note_id: i32,
+    pub tuning: f32,
+}
+
+/// Payload of an `Event`; sized for the largest SDK event type
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union EventData {
+    pub note_on: NoteOnEvent,
+    pub note_off: NoteOffEvent,
+    pub raw: [u64; 3],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Event {
+    pub bus_index: i32,
+    pub sample_offset: i32,
+    pub ppq_position: f64,
+    pub flags: u16,
+    pub event_type: u16,
+    pub data: EventData,
+}
+
+impl Event {
+    pub fn note_on(sample_offset: i32, note_id: i32, pitch: i16, velocity: f32) -> Self {
+        Self::new(
+            sample_offset,
+            NOTE_ON_EVENT,
+            EventData {
+                note_on: NoteOnEvent { channel: 0, pitch, tuning: 0.0, velocity, length: 0, note_id },
+            },
+        )
+    }
+
+    pub fn note_off(sample_offset: i32, note_id: i32, pitch: i16) -> Self {
+        Self::new(
+            sample_offset,
+            NOTE_OFF_EVENT,
+            EventData {
+                note_off: NoteOffEvent { channel: 0, pitch, velocity: 0.0, note_id, tuning: 0.0 },
+            },
+        )
+    }
+
+    fn new(sample_offset: i32, event_type: u16, data: EventData) -> Self {
+        Self { bus_index: 0, sample_offset, ppq_position: 0.0, flags: 0, event_type, data }
+    }
+}
+
+/// Owned reference to a plugin interface, released on drop
+pub struct ComPtr<V> {
+    /// Points at the object, whose first field is its vtable pointer
+    object: NonNull<*const V>,
+}
+
+impl<V> ComPtr<V> {
+    /// Take ownership of one reference to `object`
+    ///
+    /// # Safety
+    /// `object` must be null or a live COM object whose vtable starts with `V`'s layout.
+    pub unsafe fn from_raw(object: *mut c_void) -> Option<Self> {
+        NonNull::new(object as *mut *const V).map(|object| Self { object })
+    }
+
+    pub fn as_raw(&self) -> *mut c_void {
+        self.object.as_ptr() as *mut c_void
+    }
+
+    pub fn vtbl(&self) -> &V {
+        // Every interface vtable is static for the lifetime of the object
+        unsafe { &**self.object.as_ptr() }
+    }
+
+    /// Ask the object for another of its interfaces
+    pub fn query<U>(&self, iid: &Tuid) -> Option<ComPtr<U>> {
+        let mut object = std::ptr::null_mut();
+        let result = unsafe { (self.unknown().query_interface)(self.as_raw(), iid, &mut object) };
+        if result != RESULT_OK {
+            return None;
+        }
+        unsafe { ComPtr::from_raw(object) }
+    }
+
+    fn unknown(&self) -> &FUnknownVtbl {
+        // All interface vtables begin with FUnknown's three methods
+        unsafe { &*(*self.object.as_ptr() as *const FUnknownVtbl) }
+    }
+}
+
+impl<V> Drop for ComPtr<V> {
+    fn drop(&mut self) {
+        unsafe {
+            (self.unknown().release)(self.as_raw());
+        }
+    }
+}
+
+/// Host context passed to `IPluginBase::initialize`
+#[repr(C)]
+pub struct HostApplication {
+    vtbl: *const IHostApplicationVtbl,
+}
+
+impl HostApplication {
+    pub fn new() -> Box<Self> {
+        Box::new(Self { vtbl: &HOST_APPLICATION_VTBL })
+    }
+
+    pub fn as_raw(&self) -> *mut c_void {
+        self as *const Self as *mut c_void
+    }
+}
+
+/// Note events for one process call
+#[repr(C)]
+pub struct HostEventList {
+    vtbl: *const IEventListVtbl,
+    pub events: Vec<Event>,
+}
+
+impl HostEventList {
+    pub fn with_capacity(capacity: usize) -> Box<Self> {
+        Box::new(Self { vtbl: &EVENT_LIST_VTBL, events: Vec::with_capacity(capacity) })
+    }
+
+    pub fn as_raw(&self) -> *mut c_void {
+        self as *const Self as *mut c_void
+    }
+}
+
+/// Parameter changes for a host that never automates parameters
+#[repr(C)]
+pub struct HostParameterChanges {
+    vtbl: *const IParameterChangesVtbl,
+}
+
+impl HostParameterChanges {
+    pub fn new() -> Box<Self> {
+        Box::new(Self { vtbl: &PARAMETER_CHANGES_VTBL })
+    }
+
+    pub fn as_raw(&self) -> *mut c_void {
+        self as *const Self as *mut c_void
+    }
+}
+
+// Host objects are owned by the host and outlive every call that receives
+// them, so reference counting is a no-op
+
+unsafe extern "system" fn host_add_ref(_this: *mut c_void) -> u32 {
+    1
+}
+
+unsafe extern "system" fn host_release(_this: *mut c_void) -> u32 {
+    1
+}
+
+unsafe fn host_query(this: *mut c_void, iid: *const Tuid, own_iid: &Tuid, object: *mut *mut c_void) -> TResult {
+    if object.is_null() {
+        return NO_INTERFACE;
+    }
+    if !iid.is_null() && (*iid == FUNKNOWN_IID || *iid == *own_iid) {
+        *object = this;
+        RESULT_OK
+    } else {
+        *object = std::ptr::null_mut();
+        NO_INTERFACE
+    }
+}
+
+unsafe extern "system" fn host_application_query(
+    this: *mut c_void,
+    iid: *const Tuid,
+    object: *mut *mut c_void,
+) -> TResult {
+    host_query(this, iid, &IHOST_APPLICATION_IID, object)
+}
+
+unsafe extern "system" fn host_application_name(_this: *mut c_void, name: *mut [u16; 128]) -> TResult {
+    if name.is_null() {
+        return NOT_IMPLEMENTED;
+    }
+    let name = &mut *name;
+    name.fill(0);
+    for (slot, unit) in name.iter_mut().zip(HOST_NAME.encode_utf16()) {
+        *slot = unit;
+    }
+    RESULT_OK
+}
+
+unsafe extern "system" fn host_application_create(
+    _this: *mut c_void,
+    _cid: *mut Tuid,
+    _iid: *mut Tuid,
+    object: *mut *mut c_void,
+) -> TResult {
+    if !object.is_null() {
+        *object = std::ptr::null_mut();
+    }
+    NOT_IMPLEMENTED
+}
+
+static HOST_APPLICATION_VTBL: IHostApplicationVtbl = IHostApplicationVtbl {
+    unknown: FUnknownVtbl {
+        query_interface: host_application_query,
+        add_ref: host_add_ref,
+        release: host_release,
+    },
+    get_name: host_application_name,
+    create_instance: host_application_create,
+};
+
+unsafe extern "system" fn event_list_query(this: *mut c_void, iid: *const Tuid, object: *mut *mut c_void) -> TResult {
+    host_query(this, iid, &IEVENT_LIST_IID, object)
+}
+
+unsafe extern "system" fn event_list_count(this: *mut c_void) -> i32 {
+    (*(this as *const HostEventList)).events.len() as i32
+}
+
+unsafe extern "system" fn event_list_get(this: *mut c_void, index: i32, event: *mut Event) -> TResult {
+    let list = &*(this as *const HostEventList);
+    match usize::try_from(index).ok().and_then(|index| list.events.get(index)) {
+        Some(found) if !event.is_null() => {
+            *event = *found;
+            RESULT_OK
+        },
+        _ => NOT_IMPLEMENTED,
+    }
+}
+
+unsafe extern "system" fn event_list_add(_this: *mut c_void, _event: *mut Event) -> TResult {
+    // Input events are written by the host only
+    NOT_IMPLEMENTED
+}
+
+static EVENT_LIST_VTBL: IEventListVtbl = IEventListVtbl {
+    unknown: FUnknownVtbl {
+        query_interface: event_list_query,
+        add_ref: host_add_ref,
+        release: host_release,
+    },
+    get_event_count: event_list_count,
+    get_event: event_list_get,
+    add_event: event_list_add,
+};
+
+unsafe extern "system" fn parameter_changes_query(
+    this: *mut c_void,
+    iid: *const Tuid,
+    object: *mut *mut c_void,
+) -> TResult {
+    host_query(this, iid, &IPARAMETER_CHANGES_IID, object)
+}
+
+unsafe extern "system" fn parameter_changes_count(_this: *mut c_void) -> i32 {
+    0
+}
+
+unsafe extern "system" fn parameter_changes_get(_this: *mut c_void, _index: i32) -> *mut c_void {
+    std::ptr::null_mut()
+}
+
+unsafe extern "system" fn parameter_changes_add(_this: *mut c_void, _id: *const u32, _index: *mut i32) -> *mut c_void {
+    std::ptr::null_mut()
+}
+
+static PARAMETER_CHANGES_VTBL: IParameterChangesVtbl = IParameterChangesVtbl {
+    unknown: FUnknownVtbl {
+        query_interface: parameter_changes_query,
+        add_ref: host_add_ref,
+        release: host_release,
+    },
+    get_parameter_count: parameter_changes_count,
+    get_parameter_data: parameter_changes_get,
+    add_parameter_data: parameter_changes_add,
+};
+
+/// Text of a NUL-terminated fixed-size string field
+pub fn fixed_string(bytes: &[u8]) -> String {
+    let length = bytes.iter().position(|byte| *byte == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..length]).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_struct_layouts_match_the_sdk() {
+        assert_eq!(std::mem::size_of::<PClassInfo>(), 116);
+        assert_eq!(std::mem::size_of::<PClassInfo2>(), 440);
+        assert_eq!(std::mem::size_of::<BusInfo>(), 276);
+        assert_eq!(std::mem::size_of::<ProcessSetup>(), 24);
+        if cfg!(target_pointer_width = "64") {
+            assert_eq!(std::mem::size_of::<Event>(), 48);
+            assert_eq!(std::mem::size_of::<AudioBusBuffers>(), 24);
+            assert_eq!(std::mem::size_of::<ProcessData>(), 80);
+        }
+    }
+
+    #[test]
+    fn test_uid_byte_order() {
+        let unknown = FUNKNOWN_IID;
+        assert_eq!(&unknown[8..], &[0xC0, 0, 0, 0, 0, 0, 0, 0x46]);
+        if cfg!(windows) {
+            assert_eq!(&IPLUGIN_FACTORY_IID[..8], &[0x1C, 0x81, 0x4D, 0x7A, 0x11, 0x52, 0x1F, 0x4A]);
+        } else {
+            assert_eq!(&IPLUGIN_FACTORY_IID[..8], &[0x7A, 0x4D, 0x81, 0x1C, 0x52, 0x11, 0x4A, 0x1F]);
+        }
+    }
+
+    #[test]
+    fn test_host_event_list_serves_events() {
+        let mut list = HostEventList::with_capacity(4);
+        list.events.push(Event::note_on(12, 7, 60, 0.5));
+        let vtbl = unsafe { &*list.vtbl };
+
+        let mut event = Event::note_off(0, 0, 0);
+        unsafe {
+            assert_eq!((vtbl.get_event_count)(list.as_raw()), 1);
+            assert_eq!((vtbl.get_event)(list.as_raw(), 0, &mut event), RESULT_OK);
+            assert_ne!((vtbl.get_event)(list.as_raw(), 1, &mut event), RESULT_OK);
+            assert_eq!(event.event_type, NOTE_ON_EVENT);
+            assert_eq!(event.data.note_on.pitch, 60);
+        }
+        assert_eq!(event.sample_offset, 12);
+    }
+}
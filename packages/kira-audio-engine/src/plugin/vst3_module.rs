@@ -0,0 +1,930 @@
+/*!
+ * VST3 INSTRUMENT LOADING
+ *
+ * Loads a bundle's module binary, enters it through the platform entry
+ * point and creates the instrument's processor through the plugin
+ * factory. The processor runs behind the `InstrumentPlugin` interface, so
+ * the host, the synth mixer and the Godot stream treat it like any
+ * built-in instrument.
+ *
+ * Only the processor component is created: the edit controller and its
+ * GUI are not, so plugins play with their default parameter state.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::vst3_com::*;
+use super::{InstrumentEvent, InstrumentEventKind, InstrumentPlugin, PluginError, PluginResult, Vst3Bundle, Vst3ClassInfo};
+use std::ffi::{c_char, c_void};
+
+/// Note events buffered per process call before the list grows
+const EVENT_CAPACITY: usize = 512;
+
+type ExitFn = unsafe extern "C" fn() -> bool;
+
+/// A loaded VST3 module and its plugin factory
+pub struct Vst3Module {
+    name: String,
+    factory: Option<ComPtr<IPluginFactoryVtbl>>,
+    exit: Option<ExitFn>,
+    #[cfg(target_os = "macos")]
+    bundle_ref: *const c_void,
+    /// Keeps the binary mapped until the factory is released; dropped last
+    _library: Option<libloading::Library>,
+}
+
+impl std::fmt::Debug for Vst3Module {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Vst3Module").field("name", &self.name).finish()
+    }
+}
+
+impl Vst3Module {
+    /// Load the bundle's binary and obtain its plugin factory
+    pub fn load(bundle: &Vst3Bundle) -> PluginResult<Self> {
+        let load_error = |error: String| PluginError::InvalidBundle(format!("{}: {}", bundle.binary.display(), error));
+
+        // Loading runs the module's static initialisers; there is no way to vet them first
+        let library = unsafe { libloading::Library::new(&bundle.binary) }.map_err(|e| load_error(e.to_string()))?;
+        let get_factory: unsafe extern "system" fn() -> *mut c_void = unsafe {
+            *library
+                .get(b"GetPluginFactory\0")
+                .map_err(|_| load_error("does not export GetPluginFactory".to_string()))?
+        };
+
+        let entered = unsafe { enter_module(library, bundle)? };
+        let mut module = Self {
+            name: bundle.name.clone(),
+            factory: None,
+            exit: entered.exit,
+            #[cfg(target_os = "macos")]
+            bundle_ref: entered.bundle_ref,
+            _library: Some(entered.library),
+        };
+
+        // Dropping `module` on error still calls the module's exit function
+        let returned: Option<ComPtr<IPluginFactoryVtbl>> = unsafe { ComPtr::from_raw(get_factory()) };
+        // Keep a reference the object itself confirms is an IPluginFactory
+        module.factory = returned.as_ref().and_then(|factory| factory.query(&IPLUGIN_FACTORY_IID));
+        if module.factory.is_none() {
+            return Err(load_error("returned no plugin factory".to_string()));
+        }
+        Ok(module)
+    }
+
+    /// Wrap a factory that lives in this process, for tests
+    #[cfg(test)]
+    pub(crate) unsafe fn from_factory(name: &str, factory: *mut c_void) -> Self {
+        Self {
+            name: name.to_string(),
+            factory: ComPtr::from_raw(factory),
+            exit: None,
+            #[cfg(target_os = "macos")]
+            bundle_ref: std::ptr::null(),
+            _library: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Classes the factory exports; sub-categories need IPluginFactory2
+    pub fn classes(&self) -> Vec<Vst3ClassInfo> {
+        self.class_ids().into_iter().map(|(_, class)| class).collect()
+    }
+
+    /// Create, initialise and wrap the module's first instrument processor
+    pub fn create_instrument(self) -> PluginResult<Vst3Instrument> {
+        let Some((cid, class)) = self.class_ids().into_iter().find(|(_, class)| class.is_instrument()) else {
+            return Err(PluginError::Unsupported(format!("{} exports no instrument class", self.name)));
+        };
+
+        let mut object = std::ptr::null_mut();
+        let result = unsafe {
+            (self.factory().vtbl().create_instance)(
+                self.factory().as_raw(),
+                cid.as_ptr() as *const c_char,
+                ICOMPONENT_IID.as_ptr() as *const c_char,
+                &mut object,
+            )
+        };
+        let component = match unsafe { ComPtr::<IComponentVtbl>::from_raw(object) } {
+            Some(component) if result == RESULT_OK => component,
+            _ => {
+                return Err(PluginError::Unsupported(format!(
+                    "{} could not create '{}' (result {})",
+                    self.name, class.name, result
+                )))
+            },
+        };
+
+        let host = HostApplication::new();
+        let result = unsafe { (component.vtbl().initialize)(component.as_raw(), host.as_raw()) };
+        if result != RESULT_OK {
+            return Err(PluginError::ProcessError(format!("'{}' failed to initialise (result {})", class.name, result)));
+        }
+
+        let Some(processor) = component.query::<IAudioProcessorVtbl>(&IAUDIO_PROCESSOR_IID) else {
+            unsafe {
+                (component.vtbl().terminate)(component.as_raw());
+            }
+            return Err(PluginError::Unsupported(format!("'{}' has no audio processor", class.name)));
+        };
+
+        Ok(Vst3Instrument {
+            name: class.name,
+            processor,
+            component,
+            _host: host,
+            events: HostEventList::with_capacity(EVENT_CAPACITY),
+            parameters: HostParameterChanges::new(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            input_headers: Vec::new(),
+            output_headers: Vec::new(),
+            max_block_size: 0,
+            active_notes: Vec::with_capacity(EVENT_CAPACITY),
+            active: false,
+            processing: false,
+            _module: self,
+        })
+    }
+
+    fn factory(&self) -> &ComPtr<IPluginFactoryVtbl> {
+        self.factory.as_ref().expect("factory is set once the module is loaded")
+    }
+
+    fn class_ids(&self) -> Vec<(Tuid, Vst3ClassInfo)> {
+        let factory = self.factory();
+        let factory2 = factory.query::<IPluginFactory2Vtbl>(&IPLUGIN_FACTORY2_IID);
+        let count = unsafe { (factory.vtbl().count_classes)(factory.as_raw()) };
+
+        (0..count.max(0))
+            .filter_map(|index| unsafe {
+                if let Some(factory2) = &factory2 {
+                    let mut info = std::mem::zeroed::<PClassInfo2>();
+                    if (factory2.vtbl().get_class_info2)(factory2.as_raw(), index, &mut info) == RESULT_OK {
+                        return Some((info.cid, class_info(&info.cid, &info.category, &info.name, &info.sub_categories)));
+                    }
+                }
+                let mut info = std::mem::zeroed::<PClassInfo>();
+                ((factory.vtbl().get_class_info)(factory.as_raw(), index, &mut info) == RESULT_OK)
+                    .then(|| (info.cid, class_info(&info.cid, &info.category, &info.name, &[])))
+            })
+            .collect()
+    }
+}
+
+impl Drop for Vst3Module {
+    fn drop(&mut self) {
+        // The factory must go before the module is exited and unmapped
+        self.factory = None;
+        if let Some(exit) = self.exit {
+            unsafe {
+                exit();
+            }
+        }
+        #[cfg(target_os = "macos")]
+        if !self.bundle_ref.is_null() {
+            unsafe { core_foundation::CFRelease(self.bundle_ref) };
+        }
+    }
+}
+
+fn class_info(cid: &Tuid, category: &[u8], name: &[u8], sub_categories: &[u8]) -> Vst3ClassInfo {
+    let sub_categories = fixed_string(sub_categories);
+    Vst3ClassInfo {
+        cid: cid.iter().map(|byte| format!("{:02X}", byte)).collect(),
+        category: fixed_string(category),
+        name: fixed_string(name),
+        sub_categories: sub_categories.split('|').filter(|sub| !sub.is_empty()).map(str::to_string).collect(),
+    }
+}
+
+/// What entering a module leaves to clean up on exit
+struct EnteredModule {
+    library: libloading::Library,
+    exit: Option<ExitFn>,
+    #[cfg(target_os = "macos")]
+    bundle_ref: *const c_void,
+}
+
+/// Linux: `ModuleEntry` receives the dlopen handle
+#[cfg(all(unix, not(target_os = "macos")))]
+unsafe fn enter_module(library: libloading::Library, bundle: &Vst3Bundle) -> PluginResult<EnteredModule> {
+    let handle = libloading::os::unix::Library::from(library).into_raw();
+    let library = libloading::Library::from(libloading::os::unix::Library::from_raw(handle));
+
+    let entry: unsafe extern "C" fn(*mut c_void) -> bool = *library
+        .get(b"ModuleEntry\0")
+        .map_err(|_| PluginError::InvalidBundle(format!("{} does not export ModuleEntry", bundle.binary.display())))?;
+    let exit = library.get::<ExitFn>(b"ModuleExit\0").ok().map(|symbol| *symbol);
+    if !entry(handle) {
+        return Err(PluginError::InvalidBundle(format!("{} refused ModuleEntry", bundle.binary.display())));
+    }
+    Ok(EnteredModule { library, exit })
+}
+
+/// Windows: `InitDll` and `ExitDll` are optional
+#[cfg(windows)]
+unsafe fn enter_module(library: libloading::Library, bundle: &Vst3Bundle) -> PluginResult<EnteredModule> {
+    if let Ok(init) = library.get::<unsafe extern "C" fn() -> bool>(b"InitDll\0") {
+        if !init() {
+            return Err(PluginError::InvalidBundle(format!("{} refused InitDll", bundle.binary.display())));
+        }
+    }
+    let exit = library.get::<ExitFn>(b"ExitDll\0").ok().map(|symbol| *symbol);
+    Ok(EnteredModule { library, exit })
+}
+
+/// macOS: `bundleEntry` receives the bundle as a CFBundle
+#[cfg(target_os = "macos")]
+unsafe fn enter_module(library: libloading::Library, bundle: &Vst3Bundle) -> PluginResult<EnteredModule> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let entry: unsafe extern "C" fn(*const c_void) -> bool = *library
+        .get(b"bundleEntry\0")
+        .map_err(|_| PluginError::InvalidBundle(format!("{} does not export bundleEntry", bundle.binary.display())))?;
+    let exit = library.get::<ExitFn>(b"bundleExit\0").ok().map(|symbol| *symbol);
+
+    let path = bundle.path.as_os_str().as_bytes();
+    let url = core_foundation::CFURLCreateFromFileSystemRepresentation(
+        std::ptr::null(),
+        path.as_ptr(),
+        path.len() as isize,
+        1,
+    );
+    let bundle_ref = if url.is_null() { std::ptr::null() } else { core_foundation::CFBundleCreate(std::ptr::null(), url) };
+    if !url.is_null() {
+        core_foundation::CFRelease(url);
+    }
+    if bundle_ref.is_null() || !entry(bundle_ref) {
+        if !bundle_ref.is_null() {
+            core_foundation::CFRelease(bundle_ref);
+        }
+        return Err(PluginError::InvalidBundle(format!("{} refused bundleEntry", bundle.binary.display())));
+    }
+    Ok(EnteredModule { library, exit, bundle_ref })
+}
+
+#[cfg(target_os = "macos")]
+mod core_foundation {
+    use std::ffi::c_void;
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        pub fn CFURLCreateFromFileSystemRepresentation(
+            allocator: *const c_void,
+            buffer: *const u8,
+            length: isize,
+            is_directory: u8,
+        ) -> *const c_void;
+        pub fn CFBundleCreate(allocator: *const c_void, url: *const c_void) -> *const c_void;
+        pub fn CFRelease(object: *const c_void);
+    }
+}
+
+/// Channel buffers for one audio bus, with the pointer table the plugin reads
+struct BusBuffers {
+    channels: Vec<Vec<f32>>,
+    pointers: Vec<*mut f32>,
+}
+
+impl BusBuffers {
+    fn new(channel_count: usize, frames: usize) -> Self {
+        let mut channels = vec![vec![0.0; frames]; channel_count];
+        let pointers = channels.iter_mut().map(|channel| channel.as_mut_ptr()).collect();
+        Self { channels, pointers }
+    }
+
+    fn header(&mut self) -> AudioBusBuffers {
+        AudioBusBuffers {
+            num_channels: self.channels.len() as i32,
+            silence_flags: 0,
+            channel_buffers: self.pointers.as_mut_ptr(),
+        }
+    }
+}
+
+/// A VST3 instrument processor running as an `InstrumentPlugin`
+pub struct Vst3Instrument {
+    name: String,
+    // Interfaces are released in declaration order, before the host objects and the module
+    processor: ComPtr<IAudioProcessorVtbl>,
+    component: ComPtr<IComponentVtbl>,
+    /// Context the component was initialised with; must outlive it
+    _host: Box<HostApplication>,
+    events: Box<HostEventList>,
+    parameters: Box<HostParameterChanges>,
+    inputs: Vec<BusBuffers>,
+    outputs: Vec<BusBuffers>,
+    /// Bus headers handed to `process`, pointing into `inputs` and `outputs`
+    input_headers: Vec<AudioBusBuffers>,
+    output_headers: Vec<AudioBusBuffers>,
+    max_block_size: usize,
+    /// Sounding (note ID, pitch) pairs, so `AllNotesOff` can release them
+    active_notes: Vec<(i32, i16)>,
+    active: bool,
+    processing: bool,
+    _module: Vst3Module,
+}
+
+// SAFETY: the VST3 threading model lets the processor be set up on one
+// thread and processed on another; the instrument is only ever used from
+// one thread at a time, which `&mut self` on every call guarantees.
+unsafe impl Send for Vst3Instrument {}
+
+impl std::fmt::Debug for Vst3Instrument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Vst3Instrument")
+            .field("name", &self.name)
+            .field("active", &self.active)
+            .finish()
+    }
+}
+
+impl Vst3Instrument {
+    fn deactivate(&mut self) {
+        unsafe {
+            if self.processing {
+                (self.processor.vtbl().set_processing)(self.processor.as_raw(), 0);
+                self.processing = false;
+            }
+            if self.active {
+                (self.component.vtbl().set_active)(self.component.as_raw(), 0);
+                self.active = false;
+            }
+        }
+    }
+
+    /// Channel counts of every audio bus in `direction`, after asking for stereo
+    fn bus_channels(&self, direction: i32) -> Vec<usize> {
+        let component = &self.component;
+        let count = unsafe { (component.vtbl().get_bus_count)(component.as_raw(), MEDIA_AUDIO, direction) };
+        (0..count.max(0))
+            .map(|index| {
+                let mut info = unsafe { std::mem::zeroed::<BusInfo>() };
+                let result =
+                    unsafe { (component.vtbl().get_bus_info)(component.as_raw(), MEDIA_AUDIO, direction, index, &mut info) };
+                if result == RESULT_OK {
+                    info.channel_count.max(0) as usize
+                } else {
+                    2
+                }
+            })
+            .collect()
+    }
+
+    fn queue_event(&mut self, event: &InstrumentEvent) {
+        let offset = event.sample_offset as i32;
+        match event.kind {
+            InstrumentEventKind::NoteOn { note_id, key, velocity } => {
+                let note_id = vst3_note_id(note_id);
+                self.active_notes.push((note_id, key as i16));
+                self.events.events.push(Event::note_on(offset, note_id, key as i16, velocity));
+            },
+            InstrumentEventKind::NoteOff { note_id, key } => {
+                let note_id = vst3_note_id(note_id);
+                self.active_notes.retain(|(active, _)| *active != note_id);
+                self.events.events.push(Event::note_off(offset, note_id, key as i16));
+            },
+            InstrumentEventKind::AllNotesOff => {
+                for (note_id, pitch) in self.active_notes.drain(..) {
+                    self.events.events.push(Event::note_off(offset, note_id, pitch));
+                }
+            },
+        }
+    }
+}
+
+/// VST3 note IDs are non-negative i32s
+fn vst3_note_id(note_id: u64) -> i32 {
+    (note_id & i32::MAX as u64) as i32
+}
+
+impl InstrumentPlugin for Vst3Instrument {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn prepare(&mut self, sample_rate: u32, max_block_size: usize) -> PluginResult<()> {
+        self.deactivate();
+
+        let input_channels = self.bus_channels(BUS_INPUT);
+        let output_channels = self.bus_channels(BUS_OUTPUT);
+        if output_channels.is_empty() {
+            return Err(PluginError::Unsupported(format!("'{}' has no audio output", self.name)));
+        }
+
+        unsafe {
+            // Ask for stereo everywhere; plugins that refuse keep their own layout
+            let mut input_arrangements = vec![SPEAKER_STEREO; input_channels.len()];
+            let mut output_arrangements = vec![SPEAKER_STEREO; output_channels.len()];
+            (self.processor.vtbl().set_bus_arrangements)(
+                self.processor.as_raw(),
+                input_arrangements.as_mut_ptr(),
+                input_arrangements.len() as i32,
+                output_arrangements.as_mut_ptr(),
+                output_arrangements.len() as i32,
+            );
+
+            let component = &self.component;
+            (component.vtbl().activate_bus)(component.as_raw(), MEDIA_AUDIO, BUS_OUTPUT, 0, 1);
+            if (component.vtbl().get_bus_count)(component.as_raw(), MEDIA_EVENT, BUS_INPUT) > 0 {
+                (component.vtbl().activate_bus)(component.as_raw(), MEDIA_EVENT, BUS_INPUT, 0, 1);
+            }
+
+            let mut setup = ProcessSetup {
+                process_mode: PROCESS_REALTIME,
+                symbolic_sample_size: SAMPLE_32,
+                max_samples_per_block: max_block_size as i32,
+                sample_rate: sample_rate as f64,
+            };
+            let result = (self.processor.vtbl().setup_processing)(self.processor.as_raw(), &mut setup);
+            if result != RESULT_OK {
+                return Err(PluginError::ProcessError(format!("'{}' rejected the process setup (result {})", self.name, result)));
+            }
+            let result = (component.vtbl().set_active)(component.as_raw(), 1);
+            if result != RESULT_OK {
+                return Err(PluginError::ProcessError(format!("'{}' failed to activate (result {})", self.name, result)));
+            }
+        }
+        self.active = true;
+
+        // Channel counts may have changed with the arrangement
+        let input_channels = self.bus_channels(BUS_INPUT);
+        let output_channels = self.bus_channels(BUS_OUTPUT);
+        self.inputs = input_channels.iter().map(|channels| BusBuffers::new(*channels, max_block_size)).collect();
+        self.outputs = output_channels.iter().map(|channels| BusBuffers::new(*channels, max_block_size)).collect();
+        self.input_headers = self.inputs.iter_mut().map(BusBuffers::header).collect();
+        self.output_headers = self.outputs.iter_mut().map(BusBuffers::header).collect();
+        self.max_block_size = max_block_size;
+        Ok(())
+    }
+
+    fn process(&mut self, events: &[InstrumentEvent], left: &mut [f32], right: &mut [f32]) -> PluginResult<()> {
+        let frames = left.len().min(right.len());
+        if !self.active || frames > self.max_block_size {
+            return Err(PluginError::ProcessError(format!("'{}' was not prepared for {} frames", self.name, frames)));
+        }
+        if !self.processing {
+            // Plugins that don't implement setProcessing report an error here; that's fine
+            unsafe { (self.processor.vtbl().set_processing)(self.processor.as_raw(), 1) };
+            self.processing = true;
+        }
+
+        self.events.events.clear();
+        for event in events {
+            self.queue_event(event);
+        }
+        for bus in &mut self.inputs {
+            bus.channels.iter_mut().for_each(|channel| channel.fill(0.0));
+        }
+        for header in &mut self.input_headers {
+            header.silence_flags = u64::MAX;
+        }
+        for header in &mut self.output_headers {
+            header.silence_flags = 0;
+        }
+
+        let mut data = ProcessData {
+            process_mode: PROCESS_REALTIME,
+            symbolic_sample_size: SAMPLE_32,
+            num_samples: frames as i32,
+            num_inputs: self.input_headers.len() as i32,
+            num_outputs: self.output_headers.len() as i32,
+            inputs: self.input_headers.as_mut_ptr(),
+            outputs: self.output_headers.as_mut_ptr(),
+            input_parameter_changes: self.parameters.as_raw(),
+            output_parameter_changes: std::ptr::null_mut(),
+            input_events: self.events.as_raw(),
+            output_events: std::ptr::null_mut(),
+            process_context: std::ptr::null_mut(),
+        };
+
+        let result = unsafe { (self.processor.vtbl().process)(self.processor.as_raw(), &mut data) };
+        if result != RESULT_OK {
+            return Err(PluginError::ProcessError(format!("'{}' process returned {}", self.name, result)));
+        }
+
+        // Main output bus; a mono bus feeds both sides
+        let main = &self.outputs[0].channels;
+        match main.len() {
+            0 => {
+                left[..frames].fill(0.0);
+                right[..frames].fill(0.0);
+            },
+            1 => {
+                left[..frames].copy_from_slice(&main[0][..frames]);
+                right[..frames].copy_from_slice(&main[0][..frames]);
+            },
+            _ => {
+                left[..frames].copy_from_slice(&main[0][..frames]);
+                right[..frames].copy_from_slice(&main[1][..frames]);
+            },
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Vst3Instrument {
+    fn drop(&mut self) {
+        self.deactivate();
+        unsafe {
+            (self.component.vtbl().terminate)(self.component.as_raw());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::vst3::AUDIO_MODULE_CATEGORY;
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    const EFFECT_CID: Tuid = uid(1, 2, 3, 4);
+    const INSTRUMENT_CID: Tuid = uid(5, 6, 7, 8);
+
+    /// In-process stand-in for a plugin module: a factory exporting one
+    /// effect and one instrument whose output level counts sounding notes
+    #[repr(C)]
+    struct FakeFactory {
+        vtbl: *const IPluginFactory2Vtbl,
+        refs: AtomicU32,
+        released: Arc<AtomicBool>,
+    }
+
+    #[repr(C)]
+    struct FakePlugin {
+        component: *const IComponentVtbl,
+        processor: *const IAudioProcessorVtbl,
+        refs: AtomicU32,
+        host_seen: bool,
+        active: bool,
+        terminated: bool,
+        sounding: Vec<i32>,
+        released: Arc<AtomicBool>,
+    }
+
+    unsafe fn plugin_from_processor(this: *mut c_void) -> *mut FakePlugin {
+        (this as *mut u8).sub(std::mem::offset_of!(FakePlugin, processor)) as *mut FakePlugin
+    }
+
+    unsafe extern "system" fn factory_query(this: *mut c_void, iid: *const Tuid, object: *mut *mut c_void) -> TResult {
+        if [FUNKNOWN_IID, IPLUGIN_FACTORY_IID, IPLUGIN_FACTORY2_IID].contains(&*iid) {
+            factory_add_ref(this);
+            *object = this;
+            RESULT_OK
+        } else {
+            NO_INTERFACE
+        }
+    }
+
+    unsafe extern "system" fn factory_add_ref(this: *mut c_void) -> u32 {
+        (*(this as *const FakeFactory)).refs.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    unsafe extern "system" fn factory_release(this: *mut c_void) -> u32 {
+        let remaining = (*(this as *const FakeFactory)).refs.fetch_sub(1, Ordering::SeqCst) - 1;
+        if remaining == 0 {
+            drop(Box::from_raw(this as *mut FakeFactory));
+        }
+        remaining
+    }
+
+    unsafe extern "system" fn factory_info(_this: *mut c_void, _info: *mut c_void) -> TResult {
+        NOT_IMPLEMENTED
+    }
+
+    unsafe extern "system" fn factory_count(_this: *mut c_void) -> i32 {
+        2
+    }
+
+    unsafe extern "system" fn factory_class_info(_this: *mut c_void, _index: i32, _info: *mut PClassInfo) -> TResult {
+        NOT_IMPLEMENTED
+    }
+
+    unsafe extern "system" fn factory_class_info2(_this: *mut c_void, index: i32, info: *mut PClassInfo2) -> TResult {
+        let (cid, name, sub_categories) = match index {
+            0 => (EFFECT_CID, "Fake Reverb", "Fx|Reverb"),
+            1 => (INSTRUMENT_CID, "Fake Synth", "Instrument|Synth"),
+            _ => return NOT_IMPLEMENTED,
+        };
+        let info = &mut *info;
+        info.cid = cid;
+        info.category[..AUDIO_MODULE_CATEGORY.len()].copy_from_slice(AUDIO_MODULE_CATEGORY.as_bytes());
+        info.name[..name.len()].copy_from_slice(name.as_bytes());
+        info.sub_categories[..sub_categories.len()].copy_from_slice(sub_categories.as_bytes());
+        RESULT_OK
+    }
+
+    unsafe extern "system" fn factory_create(
+        this: *mut c_void,
+        cid: *const c_char,
+        iid: *const c_char,
+        object: *mut *mut c_void,
+    ) -> TResult {
+        let (cid, iid) = (&*(cid as *const Tuid), &*(iid as *const Tuid));
+        if *cid != INSTRUMENT_CID || *iid != ICOMPONENT_IID {
+            return NO_INTERFACE;
+        }
+        let plugin = Box::new(FakePlugin {
+            component: &COMPONENT_VTBL,
+            processor: &PROCESSOR_VTBL,
+            refs: AtomicU32::new(1),
+            host_seen: false,
+            active: false,
+            terminated: false,
+            sounding: Vec::new(),
+            released: Arc::clone(&(*(this as *const FakeFactory)).released),
+        });
+        *object = Box::into_raw(plugin) as *mut c_void;
+        RESULT_OK
+    }
+
+    static FACTORY_VTBL: IPluginFactory2Vtbl = IPluginFactory2Vtbl {
+        factory: IPluginFactoryVtbl {
+            unknown: FUnknownVtbl {
+                query_interface: factory_query,
+                add_ref: factory_add_ref,
+                release: factory_release,
+            },
+            get_factory_info: factory_info,
+            count_classes: factory_count,
+            get_class_info: factory_class_info,
+            create_instance: factory_create,
+        },
+        get_class_info2: factory_class_info2,
+    };
+
+    unsafe extern "system" fn plugin_query(this: *mut c_void, iid: *const Tuid, object: *mut *mut c_void) -> TResult {
+        let plugin = this as *mut FakePlugin;
+        if *iid == FUNKNOWN_IID || *iid == ICOMPONENT_IID {
+            *object = plugin as *mut c_void;
+        } else if *iid == IAUDIO_PROCESSOR_IID {
+            *object = std::ptr::addr_of_mut!((*plugin).processor) as *mut c_void;
+        } else {
+            return NO_INTERFACE;
+        }
+        (*plugin).refs.fetch_add(1, Ordering::SeqCst);
+        RESULT_OK
+    }
+
+    unsafe extern "system" fn plugin_add_ref(this: *mut c_void) -> u32 {
+        (*(this as *const FakePlugin)).refs.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    unsafe extern "system" fn plugin_release(this: *mut c_void) -> u32 {
+        let plugin = this as *mut FakePlugin;
+        let remaining = (*plugin).refs.fetch_sub(1, Ordering::SeqCst) - 1;
+        if remaining == 0 {
+            let plugin = Box::from_raw(plugin);
+            plugin.released.store(plugin.terminated, Ordering::SeqCst);
+        }
+        remaining
+    }
+
+    unsafe extern "system" fn plugin_initialize(this: *mut c_void, context: *mut c_void) -> TResult {
+        let host = ComPtr::<IHostApplicationVtbl>::from_raw(context).unwrap();
+        let mut name = [0u16; 128];
+        (host.vtbl().get_name)(host.as_raw(), &mut name);
+        (*(this as *mut FakePlugin)).host_seen = String::from_utf16_lossy(&name).starts_with("Kira");
+        // The host reference was borrowed, not handed over
+        std::mem::forget(host);
+        RESULT_OK
+    }
+
+    unsafe extern "system" fn plugin_terminate(this: *mut c_void) -> TResult {
+        (*(this as *mut FakePlugin)).terminated = true;
+        RESULT_OK
+    }
+
+    unsafe extern "system" fn component_not_implemented(_this: *mut c_void, _a: *mut Tuid) -> TResult {
+        NOT_IMPLEMENTED
+    }
+
+    unsafe extern "system" fn component_io_mode(_this: *mut c_void, _mode: i32) -> TResult {
+        NOT_IMPLEMENTED
+    }
+
+    unsafe extern "system" fn component_bus_count(_this: *mut c_void, media: i32, direction: i32) -> i32 {
+        i32::from(direction == BUS_OUTPUT || media == MEDIA_EVENT)
+    }
+
+    unsafe extern "system" fn component_bus_info(
+        _this: *mut c_void,
+        media: i32,
+        direction: i32,
+        _index: i32,
+        info: *mut BusInfo,
+    ) -> TResult {
+        (*info).media_type = media;
+        (*info).direction = direction;
+        (*info).channel_count = 2;
+        RESULT_OK
+    }
+
+    unsafe extern "system" fn component_routing(_this: *mut c_void, _in: *mut c_void, _out: *mut c_void) -> TResult {
+        NOT_IMPLEMENTED
+    }
+
+    unsafe extern "system" fn component_activate_bus(_this: *mut c_void, _m: i32, _d: i32, _i: i32, _s: u8) -> TResult {
+        RESULT_OK
+    }
+
+    unsafe extern "system" fn component_set_active(this: *mut c_void, state: u8) -> TResult {
+        (*(this as *mut FakePlugin)).active = state != 0;
+        RESULT_OK
+    }
+
+    unsafe extern "system" fn component_state(_this: *mut c_void, _stream: *mut c_void) -> TResult {
+        NOT_IMPLEMENTED
+    }
+
+    static COMPONENT_VTBL: IComponentVtbl = IComponentVtbl {
+        unknown: FUnknownVtbl {
+            query_interface: plugin_query,
+            add_ref: plugin_add_ref,
+            release: plugin_release,
+        },
+        initialize: plugin_initialize,
+        terminate: plugin_terminate,
+        get_controller_class_id: component_not_implemented,
+        set_io_mode: component_io_mode,
+        get_bus_count: component_bus_count,
+        get_bus_info: component_bus_info,
+        get_routing_info: component_routing,
+        activate_bus: component_activate_bus,
+        set_active: component_set_active,
+        set_state: component_state,
+        get_state: component_state,
+    };
+
+    unsafe extern "system" fn processor_query(this: *mut c_void, iid: *const Tuid, object: *mut *mut c_void) -> TResult {
+        plugin_query(plugin_from_processor(this) as *mut c_void, iid, object)
+    }
+
+    unsafe extern "system" fn processor_add_ref(this: *mut c_void) -> u32 {
+        plugin_add_ref(plugin_from_processor(this) as *mut c_void)
+    }
+
+    unsafe extern "system" fn processor_release(this: *mut c_void) -> u32 {
+        plugin_release(plugin_from_processor(this) as *mut c_void)
+    }
+
+    unsafe extern "system" fn processor_arrangements(
+        _this: *mut c_void,
+        _inputs: *mut u64,
+        _input_count: i32,
+        _outputs: *mut u64,
+        _output_count: i32,
+    ) -> TResult {
+        RESULT_OK
+    }
+
+    unsafe extern "system" fn processor_get_arrangement(_this: *mut c_void, _d: i32, _i: i32, _a: *mut u64) -> TResult {
+        NOT_IMPLEMENTED
+    }
+
+    unsafe extern "system" fn processor_sample_size(_this: *mut c_void, size: i32) -> TResult {
+        if size == SAMPLE_32 {
+            RESULT_OK
+        } else {
+            NOT_IMPLEMENTED
+        }
+    }
+
+    unsafe extern "system" fn processor_samples(_this: *mut c_void) -> u32 {
+        0
+    }
+
+    unsafe extern "system" fn processor_setup(_this: *mut c_void, setup: *mut ProcessSetup) -> TResult {
+        if (*setup).sample_rate > 0.0 {
+            RESULT_OK
+        } else {
+            NOT_IMPLEMENTED
+        }
+    }
+
+    unsafe extern "system" fn processor_set_processing(_this: *mut c_void, _state: u8) -> TResult {
+        RESULT_OK
+    }
+
+    /// Writes 0.25 per sounding note, changing level at each event's offset
+    unsafe extern "system" fn processor_process(this: *mut c_void, data: *mut ProcessData) -> TResult {
+        let plugin = &mut *plugin_from_processor(this);
+        let data = &mut *data;
+        let events = ComPtr::<IEventListVtbl>::from_raw(data.input_events).unwrap();
+        let count = (events.vtbl().get_event_count)(events.as_raw());
+
+        let output = &*data.outputs;
+        let left = std::slice::from_raw_parts_mut(*output.channel_buffers, data.num_samples as usize);
+        let right = std::slice::from_raw_parts_mut(*output.channel_buffers.add(1), data.num_samples as usize);
+        let mut next = 0;
+        for frame in 0..data.num_samples {
+            while next < count {
+                let mut event = std::mem::zeroed::<Event>();
+                (events.vtbl().get_event)(events.as_raw(), next, &mut event);
+                if event.sample_offset > frame {
+                    break;
+                }
+                match event.event_type {
+                    NOTE_ON_EVENT => plugin.sounding.push(event.data.note_on.note_id),
+                    _ => plugin.sounding.retain(|id| *id != event.data.note_off.note_id),
+                }
+                next += 1;
+            }
+            let level = plugin.sounding.len() as f32 * 0.25;
+            left[frame as usize] = level;
+            right[frame as usize] = level;
+        }
+        std::mem::forget(events);
+        RESULT_OK
+    }
+
+    static PROCESSOR_VTBL: IAudioProcessorVtbl = IAudioProcessorVtbl {
+        unknown: FUnknownVtbl {
+            query_interface: processor_query,
+            add_ref: processor_add_ref,
+            release: processor_release,
+        },
+        set_bus_arrangements: processor_arrangements,
+        get_bus_arrangement: processor_get_arrangement,
+        can_process_sample_size: processor_sample_size,
+        get_latency_samples: processor_samples,
+        setup_processing: processor_setup,
+        set_processing: processor_set_processing,
+        process: processor_process,
+        get_tail_samples: processor_samples,
+    };
+
+    fn fake_module(released: &Arc<AtomicBool>) -> Vst3Module {
+        let factory = Box::new(FakeFactory {
+            vtbl: &FACTORY_VTBL,
+            refs: AtomicU32::new(1),
+            released: Arc::clone(released),
+        });
+        unsafe { Vst3Module::from_factory("Fake", Box::into_raw(factory) as *mut c_void) }
+    }
+
+    fn note_on(sample_offset: usize, note_id: u64, key: u8) -> InstrumentEvent {
+        InstrumentEvent { sample_offset, kind: InstrumentEventKind::NoteOn { note_id, key, velocity: 1.0 } }
+    }
+
+    #[test]
+    fn test_factory_classes_are_read_with_sub_categories() {
+        let released = Arc::new(AtomicBool::new(false));
+        let classes = fake_module(&released).classes();
+        assert_eq!(classes.len(), 2);
+        assert_eq!(classes[1].name, "Fake Synth");
+        assert_eq!(classes[1].sub_categories, vec!["Instrument", "Synth"]);
+        assert!(!classes[0].is_instrument());
+        assert!(classes[1].is_instrument());
+        assert_eq!(classes[1].cid.len(), 32);
+    }
+
+    #[test]
+    fn test_instrument_plays_notes_through_the_com_interfaces() {
+        let released = Arc::new(AtomicBool::new(false));
+        let mut instrument = fake_module(&released).create_instrument().unwrap();
+        assert_eq!(instrument.name(), "Fake Synth");
+        assert!(instrument.process(&[], &mut [0.0; 8], &mut [0.0; 8]).is_err());
+        instrument.prepare(48000, 64).unwrap();
+
+        let (mut left, mut right) = (vec![0.0; 64], vec![0.0; 64]);
+        let events = [
+            note_on(10, 1, 60),
+            note_on(20, 2, 64),
+            InstrumentEvent { sample_offset: 30, kind: InstrumentEventKind::NoteOff { note_id: 1, key: 60 } },
+        ];
+        instrument.process(&events, &mut left, &mut right).unwrap();
+        assert_eq!((left[5], left[15], left[25], left[40]), (0.0, 0.25, 0.5, 0.25));
+        assert_eq!(left, right);
+
+        // All-notes-off releases the note still sounding
+        let all_off = [InstrumentEvent { sample_offset: 0, kind: InstrumentEventKind::AllNotesOff }];
+        instrument.process(&all_off, &mut left, &mut right).unwrap();
+        assert!(left.iter().all(|sample| *sample == 0.0));
+        assert!(instrument.process(&[], &mut [0.0; 128], &mut [0.0; 128]).is_err());
+
+        drop(instrument);
+        assert!(released.load(Ordering::SeqCst), "plugin must be terminated and released on drop");
+    }
+
+    #[test]
+    fn test_host_plugin_runs_a_vst3_instrument() {
+        let released = Arc::new(AtomicBool::new(false));
+        let instrument = fake_module(&released).create_instrument().unwrap();
+        let (handle, mut source) = crate::plugin::host_plugin(Box::new(instrument), 1000).unwrap();
+        handle.note_on(60, 1.0, 0.1, Some(0.2)).unwrap();
+
+        use crate::synthesis::StereoSource;
+        let frames: Vec<f32> = (0..400).map(|_| source.next_frame().unwrap().0).collect();
+        assert_eq!(frames[99], 0.0);
+        assert_eq!(frames[100], 0.25);
+        assert_eq!(frames[300], 0.0);
+    }
+}
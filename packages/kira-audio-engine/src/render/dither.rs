@@ -0,0 +1,90 @@
+/*!
+ * DITHERING AND QUANTIZATION
+ *
+ * Converts f32 samples to integer PCM for file output. Triangular (TPDF)
+ * dither decorrelates quantization error from the signal; the noise
+ * generator is seeded so repeated renders are bit-identical.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use crate::{AudioEngineError, Result};
+use serde::{Deserialize, Serialize};
+
+/// Fixed seed so dithered renders are reproducible
+const DITHER_SEED: u64 = 0x0144_0099_C0DE_C0DE;
+
+/// Dither applied when reducing samples to integer PCM
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Dither {
+    /// Plain rounding
+    None,
+    /// Triangular probability density noise of +/-1 LSB
+    #[default]
+    Triangular,
+}
+
+/// Small xorshift generator; quality is ample for dither noise
+#[derive(Debug, Clone)]
+struct NoiseSource(u64);
+
+impl NoiseSource {
+    /// Uniform value in [0.0, 1.0)
+    fn next_unit(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Check a PCM bit depth is one the file writers support
+pub fn validate_bit_depth(bits_per_sample: u16) -> Result<()> {
+    match bits_per_sample {
+        16 | 24 => Ok(()),
+        other => Err(AudioEngineError::RenderError(format!(
+            "unsupported bit depth {} (supported: 16, 24)",
+            other
+        ))),
+    }
+}
+
+/// Quantize samples in [-1.0, 1.0] to signed integers of `bits_per_sample` bits
+pub fn quantize(samples: &[f32], bits_per_sample: u16, dither: Dither) -> Result<Vec<i32>> {
+    validate_bit_depth(bits_per_sample)?;
+
+    let max = ((1i64 << (bits_per_sample - 1)) - 1) as f64;
+    let min = -max - 1.0;
+    let mut noise = NoiseSource(DITHER_SEED);
+
+    Ok(samples
+        .iter()
+        .map(|sample| {
+            let scaled = sample.clamp(-1.0, 1.0) as f64 * max;
+            let offset = match dither {
+                Dither::None => 0.0,
+                Dither::Triangular => noise.next_unit() - noise.next_unit(),
+            };
+            (scaled + offset).round().clamp(min, max) as i32
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantize_ranges_and_reproducibility() {
+        let samples = [0.0, 0.5, 1.0, -1.0, 2.0];
+        assert_eq!(quantize(&samples, 16, Dither::None).unwrap(), vec![0, 16384, 32767, -32767, 32767]);
+        assert_eq!(quantize(&[1.0], 24, Dither::None).unwrap(), vec![8_388_607]);
+        assert!(quantize(&samples, 12, Dither::None).is_err());
+
+        let first = quantize(&[0.25; 1000], 16, Dither::Triangular).unwrap();
+        assert_eq!(first, quantize(&[0.25; 1000], 16, Dither::Triangular).unwrap());
+        assert!(first.iter().all(|value| (value - 8192).abs() <= 1));
+        assert!(first.iter().any(|value| *value != 8192));
+    }
+}
@@ -0,0 +1,389 @@
+/*!
+ * FLAC OUTPUT
+ *
+ * Lossless FLAC encoder for rendered audio. Each channel of each block
+ * is stored as a constant, verbatim or fixed-predictor subframe, picking
+ * whichever is smallest, with Rice-coded residuals.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::{quantize, validate_bit_depth, Dither};
+use crate::{AudioEngineError, Result};
+use std::path::Path;
+
+/// Samples per channel in each FLAC frame
+pub const FLAC_BLOCK_SIZE: usize = 4096;
+
+/// Largest Rice parameter expressible with the 4-bit residual coding method
+const MAX_RICE_PARAMETER: u32 = 14;
+
+/// Highest fixed-predictor order defined by the format
+const MAX_FIXED_ORDER: usize = 4;
+
+/// MSB-first bit writer
+#[derive(Debug, Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    accumulator: u64,
+    pending_bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u64, bits: u32) {
+        for shift in (0..bits).rev() {
+            self.accumulator = (self.accumulator << 1) | ((value >> shift) & 1);
+            self.pending_bits += 1;
+            if self.pending_bits == 8 {
+                self.bytes.push(self.accumulator as u8);
+                self.accumulator = 0;
+                self.pending_bits = 0;
+            }
+        }
+    }
+
+    fn write_signed(&mut self, value: i64, bits: u32) {
+        self.write(value as u64 & ((1u64 << bits) - 1), bits);
+    }
+
+    fn write_unary_zeros(&mut self, mut zeros: u64) {
+        while zeros >= 32 {
+            self.write(0, 32);
+            zeros -= 32;
+        }
+        self.write(1, zeros as u32 + 1);
+    }
+
+    /// Pad with zero bits to the next byte boundary
+    fn align(&mut self) {
+        if self.pending_bits > 0 {
+            self.write(0, 8 - self.pending_bits);
+        }
+    }
+}
+
+/// How one channel of one block is stored
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Subframe {
+    Constant,
+    Verbatim,
+    Fixed { order: usize, rice_parameter: u32 },
+}
+
+/// Encode interleaved integer samples as a complete FLAC stream
+pub fn encode_flac(samples: &[i32], sample_rate: u32, channels: u16, bits_per_sample: u16) -> Result<Vec<u8>> {
+    validate_bit_depth(bits_per_sample)?;
+    if !(1..=8).contains(&channels) {
+        return Err(AudioEngineError::RenderError(format!("FLAC supports 1-8 channels, got {}", channels)));
+    }
+    if sample_rate == 0 || sample_rate >= 1 << 20 {
+        return Err(AudioEngineError::RenderError(format!("invalid FLAC sample rate {}", sample_rate)));
+    }
+
+    let channels = channels as usize;
+    let total_frames = samples.len() / channels;
+    let mut out = BitWriter::default();
+
+    out.bytes.extend_from_slice(b"fLaC");
+    write_stream_info(&mut out, sample_rate, channels, bits_per_sample, total_frames);
+
+    let block_samples = FLAC_BLOCK_SIZE * channels;
+    for (frame_number, block) in samples[..total_frames * channels].chunks(block_samples).enumerate() {
+        write_frame(&mut out, frame_number as u64, block, channels, bits_per_sample as u32);
+    }
+
+    Ok(out.bytes)
+}
+
+/// Quantize, dither and write interleaved samples in [-1.0, 1.0] to a FLAC file
+pub fn write_flac<P: AsRef<Path>>(
+    path: P,
+    samples: &[f32],
+    sample_rate: u32,
+    channels: u16,
+    bits_per_sample: u16,
+    dither: Dither,
+) -> Result<()> {
+    let pcm = quantize(samples, bits_per_sample, dither)?;
+    let bytes = encode_flac(&pcm, sample_rate, channels, bits_per_sample)?;
+    std::fs::write(path, bytes).map_err(|e| AudioEngineError::RenderError(e.to_string()))
+}
+
+fn write_stream_info(out: &mut BitWriter, sample_rate: u32, channels: usize, bits_per_sample: u16, total_frames: usize) {
+    // Last metadata block, type STREAMINFO, 34 bytes
+    out.write(1, 1);
+    out.write(0, 7);
+    out.write(34, 24);
+
+    let block_size = FLAC_BLOCK_SIZE.min(total_frames.max(16)) as u64;
+    out.write(block_size, 16);
+    out.write(block_size, 16);
+    // Frame sizes and MD5 signature left as "unknown"
+    out.write(0, 24);
+    out.write(0, 24);
+    out.write(sample_rate as u64, 20);
+    out.write(channels as u64 - 1, 3);
+    out.write(bits_per_sample as u64 - 1, 5);
+    // 36-bit total; zero marks a stream too long to count as "unknown length"
+    let total_frames = total_frames as u64;
+    out.write(if total_frames < 1 << 36 { total_frames } else { 0 }, 36);
+    for _ in 0..4 {
+        out.write(0, 32);
+    }
+}
+
+fn write_frame(out: &mut BitWriter, frame_number: u64, block: &[i32], channels: usize, bits: u32) {
+    let frame_start = out.bytes.len();
+    let block_size = block.len() / channels;
+
+    // Sync code, fixed block size strategy
+    out.write(0x3FFE, 14);
+    out.write(0, 1);
+    out.write(0, 1);
+    // Block size as a 16-bit value at the end of the header; rate from STREAMINFO
+    out.write(0b0111, 4);
+    out.write(0b0000, 4);
+    // Independent channels
+    out.write(channels as u64 - 1, 4);
+    out.write(if bits == 16 { 0b100 } else { 0b110 }, 3);
+    out.write(0, 1);
+    for byte in utf8_coded(frame_number) {
+        out.write(byte as u64, 8);
+    }
+    out.write(block_size as u64 - 1, 16);
+    let header_crc = crc8(&out.bytes[frame_start..]);
+    out.write(header_crc as u64, 8);
+
+    for channel in 0..channels {
+        let samples: Vec<i64> = block.iter().skip(channel).step_by(channels).map(|s| *s as i64).collect();
+        write_subframe(out, &samples, bits);
+    }
+
+    out.align();
+    let frame_crc = crc16(&out.bytes[frame_start..]);
+    out.write(frame_crc as u64, 16);
+}
+
+fn write_subframe(out: &mut BitWriter, samples: &[i64], bits: u32) {
+    // Zero padding bit, then the 6-bit type; wasted-bits flag follows
+    match choose_subframe(samples, bits) {
+        Subframe::Constant => {
+            out.write(0b0000_0000, 8);
+            out.write_signed(samples[0], bits);
+        },
+        Subframe::Verbatim => {
+            out.write(0b0000_0010, 8);
+            for sample in samples {
+                out.write_signed(*sample, bits);
+            }
+        },
+        Subframe::Fixed { order, rice_parameter } => {
+            out.write(0, 1);
+            out.write(0b001000 | order as u64, 6);
+            out.write(0, 1);
+            for sample in &samples[..order] {
+                out.write_signed(*sample, bits);
+            }
+            // Rice coding, partition order 0
+            out.write(0b00, 2);
+            out.write(0, 4);
+            out.write(rice_parameter as u64, 4);
+            for residual in fixed_residuals(samples, order) {
+                let folded = zigzag(residual);
+                out.write_unary_zeros(folded >> rice_parameter);
+                out.write(folded & ((1u64 << rice_parameter) - 1), rice_parameter);
+            }
+        },
+    }
+}
+
+fn choose_subframe(samples: &[i64], bits: u32) -> Subframe {
+    if samples.iter().all(|sample| *sample == samples[0]) {
+        return Subframe::Constant;
+    }
+
+    let mut best = (Subframe::Verbatim, samples.len() as u64 * bits as u64);
+    for order in 0..=MAX_FIXED_ORDER.min(samples.len().saturating_sub(1)) {
+        let folded: Vec<u64> = fixed_residuals(samples, order).map(zigzag).collect();
+        let (rice_parameter, residual_bits) = best_rice_parameter(&folded);
+        let size = order as u64 * bits as u64 + 10 + residual_bits;
+        if size < best.1 {
+            best = (Subframe::Fixed { order, rice_parameter }, size);
+        }
+    }
+    best.0
+}
+
+/// Rice parameter minimising the coded size, with that size in bits
+fn best_rice_parameter(folded: &[u64]) -> (u32, u64) {
+    (0..=MAX_RICE_PARAMETER)
+        .map(|parameter| {
+            let bits = folded
+                .iter()
+                .map(|value| (value >> parameter) + 1 + parameter as u64)
+                .sum();
+            (parameter, bits)
+        })
+        .min_by_key(|(_, bits)| *bits)
+        .unwrap_or((0, 0))
+}
+
+/// Prediction error of the fixed polynomial predictor of `order`
+fn fixed_residuals(samples: &[i64], order: usize) -> impl Iterator<Item = i64> + '_ {
+    (order..samples.len()).map(move |i| {
+        let s = |back: usize| samples[i - back];
+        match order {
+            0 => s(0),
+            1 => s(0) - s(1),
+            2 => s(0) - 2 * s(1) + s(2),
+            3 => s(0) - 3 * s(1) + 3 * s(2) - s(3),
+            _ => s(0) - 4 * s(1) + 6 * s(2) - 4 * s(3) + s(4),
+        }
+    })
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+/// Frame number in FLAC's extended UTF-8 style coding
+fn utf8_coded(value: u64) -> Vec<u8> {
+    if value < 0x80 {
+        return vec![value as u8];
+    }
+
+    let mut continuation = Vec::new();
+    let mut remaining = value;
+    let mut first_byte_capacity = 6;
+    while remaining >= 1 << first_byte_capacity {
+        continuation.push(0x80 | (remaining & 0x3F) as u8);
+        remaining >>= 6;
+        first_byte_capacity -= 1;
+    }
+
+    let length = continuation.len() + 1;
+    let prefix = !(0xFFu8 >> length);
+    let mut bytes = vec![prefix | remaining as u8];
+    bytes.extend(continuation.into_iter().rev());
+    bytes
+}
+
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |mut crc, byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x07 } else { crc << 1 };
+        }
+        crc
+    })
+}
+
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0u16, |mut crc, byte| {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x8005 } else { crc << 1 };
+        }
+        crc
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(bytes: &[u8]) -> (claxon::metadata::StreamInfo, Vec<i32>) {
+        let mut reader = claxon::FlacReader::new(std::io::Cursor::new(bytes)).unwrap();
+        let info = reader.streaminfo();
+        let samples = reader.samples().map(|s| s.unwrap()).collect();
+        (info, samples)
+    }
+
+    #[test]
+    fn test_flac_round_trip_is_lossless() {
+        // Tone, silence and noise exercise fixed, constant and verbatim subframes
+        let mut samples: Vec<i32> = (0..10_000)
+            .flat_map(|i| {
+                let tone = ((i as f64 * 0.05).sin() * 20_000.0) as i32;
+                [tone, -tone / 2]
+            })
+            .collect();
+        samples.extend(std::iter::repeat_n(0, 2 * 5000));
+        samples.extend((0..2000).map(|i: i64| ((i * 7_919_993) % 65_536 - 32_768) as i32));
+
+        let (info, decoded) = decode(&encode_flac(&samples, 48000, 2, 16).unwrap());
+        assert_eq!(info.sample_rate, 48000);
+        assert_eq!(info.channels, 2);
+        assert_eq!(info.samples, Some(16_000));
+        assert_eq!(decoded, samples);
+    }
+
+    #[test]
+    fn test_flac_24_bit_and_compression() {
+        let samples: Vec<i32> = (0..20_000).map(|i| ((i as f64 * 0.01).sin() * 4_000_000.0) as i32).collect();
+        let bytes = encode_flac(&samples, 96000, 1, 24).unwrap();
+        assert!(bytes.len() < samples.len() * 3 / 2);
+        assert_eq!(decode(&bytes).1, samples);
+
+        assert!(encode_flac(&samples, 48000, 9, 16).is_err());
+    }
+
+    #[test]
+    fn test_flac_24_bit_stereo_full_scale() {
+        // Full-scale noise defeats prediction; the extremes check sign handling at 24 bits
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        let mut samples: Vec<i32> = (0..2 * 3000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state >> 40) as i32 - (1 << 23)
+            })
+            .collect();
+        samples.extend([-(1 << 23), (1 << 23) - 1, (1 << 23) - 1, -(1 << 23)].repeat(500));
+
+        let (info, decoded) = decode(&encode_flac(&samples, 48000, 2, 24).unwrap());
+        assert_eq!(info.bits_per_sample, 24);
+        assert_eq!(info.samples, Some(4000));
+        assert_eq!(decoded, samples);
+    }
+
+    #[test]
+    fn test_flac_partial_final_block() {
+        for frames in [FLAC_BLOCK_SIZE * 2 + 123, FLAC_BLOCK_SIZE + 1, 5, 1] {
+            let samples: Vec<i32> = (0..frames as i32 * 2).map(|i| (i * 37) % 5000 - 2500).collect();
+            let (info, decoded) = decode(&encode_flac(&samples, 44100, 2, 16).unwrap());
+            assert_eq!(info.samples, Some(frames as u64));
+            assert_eq!(decoded, samples, "{} frames", frames);
+        }
+    }
+
+    #[test]
+    fn test_flac_sample_counts_beyond_32_bits() {
+        // A stream declaring 3 billion frames, whose single frame sits where frame 732,421 would
+        let total_frames = 3_000_000_000usize;
+        let frame_number = (total_frames / FLAC_BLOCK_SIZE) as u64;
+        let block: Vec<i32> = (0..FLAC_BLOCK_SIZE as i32).map(|i| i % 100).collect();
+
+        let mut out = BitWriter::default();
+        out.bytes.extend_from_slice(b"fLaC");
+        write_stream_info(&mut out, 48000, 1, 16, total_frames);
+        write_frame(&mut out, frame_number, &block, 1, 16);
+
+        let (info, decoded) = decode(&out.bytes);
+        assert_eq!(info.samples, Some(total_frames as u64));
+        assert_eq!(info.max_block_size, FLAC_BLOCK_SIZE as u16);
+        assert_eq!(decoded, block);
+    }
+
+    #[test]
+    fn test_utf8_frame_numbers() {
+        assert_eq!(utf8_coded(0x7F), vec![0x7F]);
+        assert_eq!(utf8_coded(0x80), vec![0xC2, 0x80]);
+        assert_eq!(utf8_coded(0x800), vec![0xE0, 0xA0, 0x80]);
+        // Largest 31-bit frame number and largest 36-bit sample number
+        assert_eq!(utf8_coded((1 << 31) - 1), vec![0xFD, 0xBF, 0xBF, 0xBF, 0xBF, 0xBF]);
+        assert_eq!(utf8_coded((1 << 36) - 1), vec![0xFE, 0xBF, 0xBF, 0xBF, 0xBF, 0xBF, 0xBF]);
+    }
+}
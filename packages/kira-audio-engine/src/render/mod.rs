@@ -0,0 +1,24 @@
+/*!
+ * OFFLINE AUDIO RENDERING
+ *
+ * Non-realtime output of generated audio to files, for reproducible
+ * asset generation outside the live audio thread.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ *
+ * Features:
+ * - Pattern and tone-sequence bouncing through the synth engine
+ * - WAV and FLAC output at 16 or 24 bits and any sample rate
+ * - Reproducible TPDF dithering
+ */
+
+mod dither;
+mod flac;
+mod offline;
+mod wav;
+
+pub use dither::*;
+pub use flac::*;
+pub use offline::*;
+pub use wav::*;
@@ -0,0 +1,292 @@
+/*!
+ * OFFLINE BOUNCING
+ *
+ * Non-realtime render path: drives the synth engine without an audio
+ * device as fast as the CPU allows, then writes WAV or FLAC at the
+ * requested sample rate, bit depth and dither. Output is deterministic,
+ * so bounces can be generated and compared in CI or for the web build.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::{validate_bit_depth, write_flac, write_wav, Dither};
+use crate::pattern::Pattern;
+use crate::scheduler::{MusicalClock, Transport};
+use crate::synthesis::{synth_channel, NoteSettings, SynthController, SynthEngine, ToneSpec};
+use crate::{AudioEngineError, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Frames rendered per engine call
+const RENDER_CHUNK_FRAMES: usize = 1024;
+
+/// Longest release tail rendered after scheduled material ends
+const MAX_TAIL_SECONDS: f64 = 10.0;
+
+/// Longest pattern bounce, in seconds, before the release tail
+pub const MAX_PATTERN_RENDER_SECONDS: f64 = 3600.0;
+
+/// Audio file container
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AudioFormat {
+    Wav,
+    Flac,
+}
+
+impl AudioFormat {
+    /// Format implied by a file extension
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "wav" | "wave" => Some(Self::Wav),
+            "flac" => Some(Self::Flac),
+            _ => None,
+        }
+    }
+}
+
+/// Output settings for an offline render
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RenderSettings {
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// 16 or 24
+    pub bits_per_sample: u16,
+    pub dither: Dither,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            sample_rate: crate::constants::SAMPLE_RATE,
+            channels: 2,
+            bits_per_sample: 16,
+            dither: Dither::default(),
+        }
+    }
+}
+
+impl RenderSettings {
+    pub fn with_sample_rate(mut self, sample_rate: u32) -> Self {
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    pub fn with_bit_depth(mut self, bits_per_sample: u16) -> Self {
+        self.bits_per_sample = bits_per_sample;
+        self
+    }
+
+    pub fn with_dither(mut self, dither: Dither) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    pub(crate) fn validate(&self) -> Result<()> {
+        validate_bit_depth(self.bits_per_sample)?;
+        if self.sample_rate == 0 || !(1..=2).contains(&self.channels) {
+            return Err(AudioEngineError::RenderError(format!(
+                "unsupported render format: {} Hz, {} channels",
+                self.sample_rate, self.channels
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Synth engine driven offline instead of by an audio device
+pub struct OfflineRenderer {
+    settings: RenderSettings,
+    controller: SynthController,
+    engine: SynthEngine,
+}
+
+impl OfflineRenderer {
+    pub fn new(settings: RenderSettings) -> Result<Self> {
+        settings.validate()?;
+        let (controller, engine) = synth_channel(settings.sample_rate, settings.channels as usize);
+        Ok(Self {
+            settings,
+            controller,
+            engine,
+        })
+    }
+
+    pub fn settings(&self) -> &RenderSettings {
+        &self.settings
+    }
+
+    /// Schedule notes and sources here; times are relative to the render start
+    pub fn controller(&self) -> &SynthController {
+        &self.controller
+    }
+
+    /// Render exactly `seconds` of interleaved audio
+    pub fn render(&mut self, seconds: f64) -> Vec<f32> {
+        let channels = self.settings.channels as usize;
+        let frames = (seconds.max(0.0) * self.settings.sample_rate as f64).round() as usize;
+        let mut output = vec![0.0; frames * channels];
+        for chunk in output.chunks_mut(RENDER_CHUNK_FRAMES * channels) {
+            self.engine.render(chunk);
+        }
+        output
+    }
+
+    /// Render at least `seconds`, then continue until every voice has
+    /// released (up to a 10 second tail)
+    pub fn render_until_idle(&mut self, seconds: f64) -> Vec<f32> {
+        let mut output = self.render(seconds);
+        let tail_limit = (MAX_TAIL_SECONDS * self.settings.sample_rate as f64 / RENDER_CHUNK_FRAMES as f64) as usize;
+        for _ in 0..tail_limit {
+            if self.engine.is_idle() {
+                break;
+            }
+            output.extend(self.render(RENDER_CHUNK_FRAMES as f64 / self.settings.sample_rate as f64));
+        }
+        output
+    }
+}
+
+/// Bounce cycles 0 to `cycles` of a pattern, including the release tail.
+///
+/// Cycle 0 starts on the first frame, so leading rests render as silence.
+/// The span is limited as by `Pattern::query_cycles` and to
+/// `MAX_PATTERN_RENDER_SECONDS` of audio.
+pub fn render_pattern(
+    pattern: &Pattern,
+    cycles: u32,
+    cycles_per_second: f64,
+    template: NoteSettings,
+    settings: RenderSettings,
+) -> Result<Vec<f32>> {
+    let render_error = |message: String| AudioEngineError::RenderError(message);
+    let seconds = cycles as f64 / cycles_per_second;
+    if seconds.is_nan() || seconds > MAX_PATTERN_RENDER_SECONDS {
+        return Err(render_error(format!(
+            "{} cycles at {} cycles per second exceed {} seconds",
+            cycles, cycles_per_second, MAX_PATTERN_RENDER_SECONDS
+        )));
+    }
+    let events = pattern.query_cycles(cycles).map_err(|e| render_error(e.to_string()))?;
+    let clock = MusicalClock::from_cycles_per_second(cycles_per_second, settings.sample_rate)
+        .map_err(|e| render_error(e.to_string()))?;
+    let transport = Transport::new(clock, 0, 0.0);
+
+    let mut renderer = OfflineRenderer::new(settings)?;
+    renderer
+        .controller()
+        .play_scheduled(&transport.schedule(&events), template)
+        .map_err(|e| render_error(e.to_string()))?;
+    Ok(renderer.render_until_idle(seconds))
+}
+
+/// Bounce tones back to back, each for its paired duration in seconds
+pub fn render_tone_sequence(tones: &[(ToneSpec, f64)], settings: RenderSettings) -> Result<Vec<f32>> {
+    settings.validate()?;
+
+    let channels = settings.channels as usize;
+    let mut output = Vec::new();
+    for (tone, duration) in tones {
+        for sample in tone.render(*duration, settings.sample_rate)? {
+            output.extend(std::iter::repeat_n(sample, channels));
+        }
+    }
+    Ok(output)
+}
+
+/// Write interleaved samples to `path`, choosing WAV or FLAC from the extension
+pub fn save_render<P: AsRef<Path>>(path: P, samples: &[f32], settings: &RenderSettings) -> Result<()> {
+    settings.validate()?;
+    let path = path.as_ref();
+
+    match AudioFormat::from_path(path) {
+        Some(AudioFormat::Wav) => write_wav(path, samples, settings),
+        Some(AudioFormat::Flac) => write_flac(
+            path,
+            samples,
+            settings.sample_rate,
+            settings.channels,
+            settings.bits_per_sample,
+            settings.dither,
+        ),
+        None => Err(AudioEngineError::RenderError(format!(
+            "unknown audio format for {} (expected .wav or .flac)",
+            path.display()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pattern_bounce_is_deterministic_with_tail() {
+        let pattern = Pattern::parse("c4 e4 g4").unwrap();
+        let settings = RenderSettings::default().with_sample_rate(22050);
+
+        let first = render_pattern(&pattern, 1, 1.0, NoteSettings::new(440.0, 0.8), settings).unwrap();
+        let second = render_pattern(&pattern, 1, 1.0, NoteSettings::new(440.0, 0.8), settings).unwrap();
+        assert_eq!(first, second);
+        // One second of pattern plus the release of the last note
+        assert!(first.len() > 22050 * 2);
+        assert!(first.len() < 22050 * 2 * 2);
+        assert!(first.iter().any(|sample| *sample != 0.0));
+    }
+
+    #[test]
+    fn test_pattern_bounce_keeps_leading_rests() {
+        let settings = RenderSettings::default().with_sample_rate(8000).with_dither(Dither::None);
+        let samples = render_pattern(&Pattern::parse("<~ c4>").unwrap(), 2, 1.0, NoteSettings::new(440.0, 0.8), settings).unwrap();
+
+        // Cycle 0 is the rest; c4 sounds for the whole of cycle 1
+        let (rest, note) = samples.split_at(8000 * 2);
+        assert!(rest.iter().all(|sample| *sample == 0.0));
+        assert!(note[..8000 * 2].iter().any(|sample| *sample != 0.0));
+        assert!(note.len() >= 8000 * 2);
+    }
+
+    #[test]
+    fn test_pattern_bounce_is_bounded() {
+        let settings = RenderSettings::default().with_sample_rate(8000);
+        let template = NoteSettings::new(440.0, 0.8);
+        let pattern = Pattern::parse("c4 e4").unwrap();
+        for (cycles, cycles_per_second) in [(2, 1e-6), (2, f64::NAN), (crate::pattern::MAX_QUERY_CYCLES + 1, 1.0)] {
+            assert!(matches!(
+                render_pattern(&pattern, cycles, cycles_per_second, template, settings),
+                Err(AudioEngineError::RenderError(_))
+            ));
+        }
+
+        let dense = Pattern::parse("[c4*256]*200").unwrap();
+        assert!(render_pattern(&dense, crate::pattern::MAX_QUERY_CYCLES, 1000.0, template, settings).is_err());
+    }
+
+    #[test]
+    fn test_save_render_to_wav_and_flac() {
+        let settings = RenderSettings::default().with_sample_rate(44100).with_bit_depth(24);
+        let tones = [
+            (ToneSpec::solfeggio(528.0).unwrap(), 0.2),
+            (ToneSpec::solfeggio(639.0).unwrap(), 0.2),
+        ];
+        let samples = render_tone_sequence(&tones, settings).unwrap();
+        assert_eq!(samples.len(), 44100 * 2 * 4 / 10);
+
+        let dir = std::env::temp_dir();
+        let wav = dir.join(format!("kira_offline_{}.wav", std::process::id()));
+        let flac = dir.join(format!("kira_offline_{}.flac", std::process::id()));
+        save_render(&wav, &samples, &settings).unwrap();
+        save_render(&flac, &samples, &settings).unwrap();
+
+        let wav_samples: Vec<i32> = hound::WavReader::open(&wav).unwrap().samples().map(|s| s.unwrap()).collect();
+        let mut flac_reader = claxon::FlacReader::open(&flac).unwrap();
+        assert_eq!(flac_reader.streaminfo().bits_per_sample, 24);
+        let flac_samples: Vec<i32> = flac_reader.samples().map(|s| s.unwrap()).collect();
+        let _ = std::fs::remove_file(&wav);
+        let _ = std::fs::remove_file(&flac);
+
+        assert_eq!(wav_samples, flac_samples);
+        assert!(save_render(dir.join("kira_offline.ogg"), &samples, &settings).is_err());
+    }
+}
@@ -0,0 +1,34 @@
+/*!
+ * WAV OUTPUT
+ *
+ * Writes interleaved f32 sample buffers to 16- or 24-bit PCM WAV files,
+ * quantized with the render settings' dither.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::{quantize, RenderSettings};
+use crate::{AudioEngineError, Result};
+use std::path::Path;
+
+/// Write interleaved samples in [-1.0, 1.0] to a PCM WAV file
+pub fn write_wav<P: AsRef<Path>>(path: P, samples: &[f32], settings: &RenderSettings) -> Result<()> {
+    settings.validate()?;
+    let spec = hound::WavSpec {
+        channels: settings.channels,
+        sample_rate: settings.sample_rate,
+        bits_per_sample: settings.bits_per_sample,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let mut writer = hound::WavWriter::create(path, spec).map_err(render_error)?;
+    for value in quantize(samples, settings.bits_per_sample, settings.dither)? {
+        writer.write_sample(value).map_err(render_error)?;
+    }
+    writer.finalize().map_err(render_error)
+}
+
+fn render_error(error: hound::Error) -> AudioEngineError {
+    AudioEngineError::RenderError(error.to_string())
+}
@@ -0,0 +1,273 @@
+/*!
+ * MUSICAL CLOCK
+ *
+ * Converts between cycles, seconds and samples. Tempo is given in
+ * quarter-note BPM with a fixed number of beats per cycle (one 4/4 bar by
+ * default, matching MIDI export); swing delays every second subdivision.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::{SchedulerError, SchedulerResult};
+use crate::pattern::PatternEvent;
+use serde::{Deserialize, Serialize};
+
+/// Quarter-note beats per cycle unless configured otherwise
+pub const DEFAULT_BEATS_PER_CYCLE: u8 = 4;
+
+/// Tempo change at a cycle position
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TempoChange {
+    pub cycle: f64,
+    pub bpm: f64,
+}
+
+/// Swing over pairs of equal subdivisions of a cycle.
+///
+/// `amount` is how far each off-beat is delayed, as a fraction of one
+/// subdivision: 0.0 is straight, 1/3 gives a 2:1 triplet feel.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Swing {
+    pub amount: f64,
+    /// Subdivisions per cycle; must be even (8 swings eighth notes in 4/4)
+    pub subdivision: u32,
+}
+
+impl Swing {
+    pub fn new(amount: f64, subdivision: u32) -> SchedulerResult<Self> {
+        if !(0.0..1.0).contains(&amount) || subdivision < 2 || !subdivision.is_multiple_of(2) {
+            return Err(SchedulerError::InvalidSwing { amount, subdivision });
+        }
+        Ok(Self { amount, subdivision })
+    }
+
+    /// Map a straight cycle position to its swung position.
+    ///
+    /// Piecewise linear within each pair of subdivisions, so pair
+    /// boundaries stay fixed and event order is preserved.
+    pub fn apply(&self, cycle: f64) -> f64 {
+        let slot = 1.0 / self.subdivision as f64;
+        let pair_start = (cycle / (2.0 * slot)).floor() * 2.0 * slot;
+        let position = (cycle - pair_start) / slot;
+
+        let swung = if position < 1.0 {
+            position * (1.0 + self.amount)
+        } else {
+            1.0 + self.amount + (position - 1.0) * (1.0 - self.amount)
+        };
+        pair_start + swung * slot
+    }
+}
+
+/// Tempo map, swing and sample rate shared by every playback path
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MusicalClock {
+    sample_rate: u32,
+    beats_per_cycle: u8,
+    /// Sorted by cycle; the first change is always at cycle 0
+    tempo_map: Vec<TempoChange>,
+    swing: Option<Swing>,
+}
+
+impl MusicalClock {
+    pub fn new(bpm: f64, sample_rate: u32) -> SchedulerResult<Self> {
+        if sample_rate == 0 {
+            return Err(SchedulerError::InvalidClock("sample rate must be positive".to_string()));
+        }
+
+        let mut clock = Self {
+            sample_rate,
+            beats_per_cycle: DEFAULT_BEATS_PER_CYCLE,
+            tempo_map: Vec::new(),
+            swing: None,
+        };
+        clock.set_tempo(0.0, bpm)?;
+        Ok(clock)
+    }
+
+    /// Clock running at a constant rate in cycles per second (Strudel's `cps`)
+    pub fn from_cycles_per_second(cycles_per_second: f64, sample_rate: u32) -> SchedulerResult<Self> {
+        Self::new(cycles_per_second * 60.0 * DEFAULT_BEATS_PER_CYCLE as f64, sample_rate)
+    }
+
+    /// Change the beats per cycle, keeping BPM (so cycles get longer or shorter)
+    pub fn with_beats_per_cycle(mut self, beats_per_cycle: u8) -> SchedulerResult<Self> {
+        if beats_per_cycle == 0 {
+            return Err(SchedulerError::InvalidClock("beats per cycle must be positive".to_string()));
+        }
+        self.beats_per_cycle = beats_per_cycle;
+        Ok(self)
+    }
+
+    pub fn with_swing(mut self, swing: Swing) -> Self {
+        self.swing = Some(swing);
+        self
+    }
+
+    /// Set the tempo from `cycle` onwards, replacing any change at the same position
+    pub fn set_tempo(&mut self, cycle: f64, bpm: f64) -> SchedulerResult<()> {
+        if !(bpm.is_finite() && bpm > 0.0) {
+            return Err(SchedulerError::InvalidTempo(bpm));
+        }
+        if !(cycle.is_finite() && cycle >= 0.0) {
+            return Err(SchedulerError::InvalidTime(cycle));
+        }
+
+        self.tempo_map.retain(|change| change.cycle != cycle);
+        self.tempo_map.push(TempoChange { cycle, bpm });
+        self.tempo_map.sort_by(|a, b| a.cycle.total_cmp(&b.cycle));
+        Ok(())
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn beats_per_cycle(&self) -> u8 {
+        self.beats_per_cycle
+    }
+
+    pub fn tempo_map(&self) -> &[TempoChange] {
+        &self.tempo_map
+    }
+
+    pub fn swing(&self) -> Option<Swing> {
+        self.swing
+    }
+
+    /// Tempo in effect at `cycle`
+    pub fn bpm_at(&self, cycle: f64) -> f64 {
+        self.tempo_map
+            .iter()
+            .take_while(|change| change.cycle <= cycle)
+            .last()
+            .unwrap_or(&self.tempo_map[0])
+            .bpm
+    }
+
+    pub fn cycles_per_second_at(&self, cycle: f64) -> f64 {
+        self.cycles_per_second(self.bpm_at(cycle))
+    }
+
+    /// Swung position of a straight cycle position
+    pub fn swung_cycle(&self, cycle: f64) -> f64 {
+        self.swing.map_or(cycle, |swing| swing.apply(cycle))
+    }
+
+    /// Time from cycle 0 to the (swung) onset of `cycle`
+    pub fn cycle_to_seconds(&self, cycle: f64) -> f64 {
+        self.straight_cycle_to_seconds(self.swung_cycle(cycle))
+    }
+
+    /// Playhead position in straight cycles after `seconds` from cycle 0
+    pub fn seconds_to_cycle(&self, seconds: f64) -> f64 {
+        if seconds < 0.0 {
+            return seconds * self.cycles_per_second(self.tempo_map[0].bpm);
+        }
+
+        let mut elapsed = 0.0;
+        for (index, change) in self.tempo_map.iter().enumerate() {
+            let rate = self.cycles_per_second(change.bpm);
+            let segment_seconds = self
+                .tempo_map
+                .get(index + 1)
+                .map_or(f64::INFINITY, |next| (next.cycle - change.cycle) / rate);
+            if seconds <= elapsed + segment_seconds {
+                return change.cycle + (seconds - elapsed) * rate;
+            }
+            elapsed += segment_seconds;
+        }
+        unreachable!("the last tempo segment is unbounded")
+    }
+
+    /// Sample offset from cycle 0 of the (swung) onset of `cycle`
+    pub fn cycle_to_sample(&self, cycle: f64) -> u64 {
+        (self.cycle_to_seconds(cycle) * self.sample_rate as f64).round().max(0.0) as u64
+    }
+
+    /// Playhead position in straight cycles at sample offset `sample`
+    pub fn sample_to_cycle(&self, sample: u64) -> f64 {
+        self.seconds_to_cycle(sample as f64 / self.sample_rate as f64)
+    }
+
+    /// Pattern events with swing applied to their begin and end, still in cycles
+    pub fn swing_events(&self, events: &[PatternEvent]) -> Vec<PatternEvent> {
+        events
+            .iter()
+            .map(|event| PatternEvent {
+                begin: self.swung_cycle(event.begin),
+                end: self.swung_cycle(event.end),
+                ..event.clone()
+            })
+            .collect()
+    }
+
+    fn cycles_per_second(&self, bpm: f64) -> f64 {
+        bpm / 60.0 / self.beats_per_cycle as f64
+    }
+
+    fn straight_cycle_to_seconds(&self, cycle: f64) -> f64 {
+        if cycle < 0.0 {
+            return cycle / self.cycles_per_second(self.tempo_map[0].bpm);
+        }
+
+        let mut seconds = 0.0;
+        for (index, change) in self.tempo_map.iter().enumerate() {
+            let segment_end = self.tempo_map.get(index + 1).map_or(f64::INFINITY, |next| next.cycle);
+            seconds += (cycle.min(segment_end) - change.cycle) / self.cycles_per_second(change.bpm);
+            if cycle <= segment_end {
+                break;
+            }
+        }
+        seconds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tempo_map_conversion() {
+        let mut clock = MusicalClock::new(120.0, 48000).unwrap();
+        clock.set_tempo(2.0, 60.0).unwrap();
+
+        // 4/4 at 120 BPM: 2 seconds per cycle, then 4 seconds per cycle
+        assert_eq!(clock.cycle_to_seconds(1.0), 2.0);
+        assert_eq!(clock.cycle_to_seconds(3.0), 8.0);
+        assert_eq!(clock.cycle_to_sample(2.5), 6 * 48000);
+        assert_eq!(clock.seconds_to_cycle(8.0), 3.0);
+        assert_eq!(clock.sample_to_cycle(48000), 0.5);
+        assert_eq!(clock.bpm_at(2.0), 60.0);
+
+        assert!(clock.set_tempo(1.0, -5.0).is_err());
+        assert!(MusicalClock::new(120.0, 0).is_err());
+    }
+
+    #[test]
+    fn test_cycles_per_second_matches_strudel() {
+        let clock = MusicalClock::from_cycles_per_second(0.5, 44100).unwrap();
+        assert_eq!(clock.bpm_at(0.0), 120.0);
+        assert_eq!(clock.cycle_to_sample(1.0), 88200);
+    }
+
+    #[test]
+    fn test_swing_delays_off_beats_only() {
+        let swing = Swing::new(1.0 / 3.0, 8).unwrap();
+        assert_eq!(swing.apply(0.0), 0.0);
+        assert!((swing.apply(0.125) - 0.125 * 4.0 / 3.0).abs() < 1e-12);
+        assert!((swing.apply(0.25) - 0.25).abs() < 1e-12);
+        assert!(swing.apply(0.2) < swing.apply(0.24));
+
+        assert!(Swing::new(1.0, 8).is_err());
+        assert!(Swing::new(0.2, 3).is_err());
+
+        let clock = MusicalClock::new(120.0, 1000).unwrap().with_swing(swing);
+        let events = crate::pattern::Pattern::parse("a b c d e f g h").unwrap().query_cycle(0);
+        let swung = clock.swing_events(&events);
+        assert!((swung[1].begin - 1.0 / 6.0).abs() < 1e-12);
+        assert!((swung[0].duration() - 1.0 / 6.0).abs() < 1e-12);
+        assert_eq!(clock.cycle_to_sample(events[1].begin), 333);
+    }
+}
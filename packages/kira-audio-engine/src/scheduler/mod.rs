@@ -0,0 +1,41 @@
+/*!
+ * SAMPLE-ACCURATE EVENT SCHEDULING
+ *
+ * One musical clock for every audio path: pattern cycles are converted
+ * to seconds and sample timestamps through a shared tempo map and swing
+ * setting, so the synthesizer, MIDI export and Godot playback agree on
+ * exactly when each event happens.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ *
+ * Features:
+ * - Tempo maps with changes at any cycle position
+ * - Swing as a time warp over paired subdivisions
+ * - Cycle <-> seconds <-> sample conversion in both directions
+ * - Transports anchoring cycles to an engine's sample clock
+ */
+
+mod clock;
+mod transport;
+
+pub use clock::*;
+pub use transport::*;
+
+// Scheduler-related error types
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum SchedulerError {
+    #[error("Invalid tempo: {0} BPM")]
+    InvalidTempo(f64),
+
+    #[error("Invalid time: {0} cycles")]
+    InvalidTime(f64),
+
+    #[error("Invalid swing: amount {amount} over {subdivision} subdivisions")]
+    InvalidSwing { amount: f64, subdivision: u32 },
+
+    #[error("Invalid clock setting: {0}")]
+    InvalidClock(String),
+}
+
+pub type SchedulerResult<T> = std::result::Result<T, SchedulerError>;
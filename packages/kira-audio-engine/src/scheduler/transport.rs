@@ -0,0 +1,112 @@
+/*!
+ * TRANSPORT
+ *
+ * Anchors a musical clock to an engine's sample clock: a given cycle
+ * starts at a given absolute sample, and every later event is placed
+ * relative to that anchor. Queuing cycles one at a time therefore never
+ * drifts, however late each batch is sent.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::MusicalClock;
+use crate::pattern::PatternEvent;
+
+/// A pattern event with its absolute sample timestamps
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledEvent {
+    pub event: PatternEvent,
+    pub start_sample: u64,
+    pub end_sample: u64,
+}
+
+impl ScheduledEvent {
+    pub fn duration_samples(&self) -> u64 {
+        self.end_sample.saturating_sub(self.start_sample)
+    }
+}
+
+/// Musical clock anchored at a sample position
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transport {
+    clock: MusicalClock,
+    start_sample: u64,
+    start_cycle: f64,
+}
+
+impl Transport {
+    /// `start_cycle` plays at absolute sample `start_sample`
+    pub fn new(clock: MusicalClock, start_sample: u64, start_cycle: f64) -> Self {
+        Self {
+            clock,
+            start_sample,
+            start_cycle,
+        }
+    }
+
+    pub fn clock(&self) -> &MusicalClock {
+        &self.clock
+    }
+
+    pub fn start_sample(&self) -> u64 {
+        self.start_sample
+    }
+
+    pub fn start_cycle(&self) -> f64 {
+        self.start_cycle
+    }
+
+    /// Absolute sample at which `cycle` (swung) begins; 0 if before the sample clock's origin
+    pub fn sample_at(&self, cycle: f64) -> u64 {
+        let offset = self.clock.cycle_to_seconds(cycle) - self.clock.cycle_to_seconds(self.start_cycle);
+        (self.start_sample as f64 + offset * self.clock.sample_rate() as f64)
+            .round()
+            .max(0.0) as u64
+    }
+
+    /// Playhead position in straight cycles at absolute sample `sample`
+    pub fn cycle_at(&self, sample: u64) -> f64 {
+        let elapsed = (sample as f64 - self.start_sample as f64) / self.clock.sample_rate() as f64;
+        self.clock
+            .seconds_to_cycle(self.clock.cycle_to_seconds(self.start_cycle) + elapsed)
+    }
+
+    /// Timestamp events, in onset order
+    pub fn schedule(&self, events: &[PatternEvent]) -> Vec<ScheduledEvent> {
+        let mut scheduled: Vec<ScheduledEvent> = events
+            .iter()
+            .map(|event| ScheduledEvent {
+                event: event.clone(),
+                start_sample: self.sample_at(event.begin),
+                end_sample: self.sample_at(event.end),
+            })
+            .collect();
+        scheduled.sort_by_key(|event| event.start_sample);
+        scheduled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::Pattern;
+
+    #[test]
+    fn test_cycles_scheduled_separately_stay_aligned() {
+        let clock = MusicalClock::from_cycles_per_second(1.0, 1000).unwrap();
+        let transport = Transport::new(clock, 5000, 10.0);
+        let pattern = Pattern::parse("a b").unwrap();
+
+        let first = transport.schedule(&pattern.query_cycle(10));
+        let second = transport.schedule(&pattern.query_cycle(11));
+        assert_eq!(first[0].start_sample, 5000);
+        assert_eq!(first[1].start_sample, 5500);
+        assert_eq!(first[1].end_sample, second[0].start_sample);
+        assert_eq!(second[1].duration_samples(), 500);
+
+        assert_eq!(transport.cycle_at(5250), 10.25);
+        assert_eq!(transport.cycle_at(4000), 9.0);
+        assert_eq!(transport.sample_at(0.0), 0);
+    }
+}
@@ -0,0 +1,360 @@
+/*!
+ * BINAURAL BEATS AND ISOCHRONIC TONES
+ *
+ * Brainwave entrainment generators for the meditation and reading-room
+ * scenes. Beat frequencies follow a keyframed ramp schedule, and every
+ * session is bounded by safety caps on carrier, beat rate and level.
+ * Isochronic pulses use smooth raised-cosine gating rather than hard
+ * on/off switching.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::{StereoSource, SynthController, SynthResult, VoiceId};
+use crate::constants::{
+    ENTRAINMENT_MAX_BEAT, ENTRAINMENT_MAX_CARRIER, ENTRAINMENT_MIN_BEAT, ND_SAFE_MAX_AMPLITUDE,
+    ND_SAFE_MIN_FADE_SECONDS,
+};
+use crate::{AudioEngineError, Result};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::TAU;
+
+/// How the beat is delivered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EntrainmentMode {
+    /// Slightly different frequencies in each ear (headphones required)
+    Binaural,
+    /// A single carrier pulsed on and off at the beat rate
+    Isochronic,
+}
+
+/// Beat frequency at a point in the session
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BeatKeyframe {
+    /// Seconds from session start
+    pub time: f64,
+    /// Beat frequency in Hz
+    pub beat_frequency: f64,
+}
+
+/// Built-in session presets for the meditation and reading-room scenes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EntrainmentPreset {
+    /// Steady 10 Hz alpha for calm, focused reading
+    ReadingRoom,
+    /// Alpha easing down into theta
+    Meditation,
+    /// Theta easing down into delta
+    DeepRest,
+    /// Steady 14 Hz low beta
+    Focus,
+}
+
+impl EntrainmentPreset {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().replace(['-', ' '], "_").as_str() {
+            "reading_room" => Some(Self::ReadingRoom),
+            "meditation" => Some(Self::Meditation),
+            "deep_rest" => Some(Self::DeepRest),
+            "focus" => Some(Self::Focus),
+            _ => None,
+        }
+    }
+}
+
+/// A complete entrainment session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntrainmentSession {
+    pub mode: EntrainmentMode,
+    /// Carrier frequency in Hz
+    pub carrier: f64,
+    /// Beat schedule, sorted by time
+    pub schedule: Vec<BeatKeyframe>,
+    /// Total length in seconds
+    pub duration: f64,
+    /// Peak level, capped at `ND_SAFE_MAX_AMPLITUDE`
+    pub amplitude: f32,
+    pub fade_in: f64,
+    pub fade_out: f64,
+}
+
+impl EntrainmentSession {
+    /// Session with a constant beat frequency
+    pub fn new(mode: EntrainmentMode, carrier: f64, beat_frequency: f64, duration: f64) -> Result<Self> {
+        Self::with_schedule(
+            mode,
+            carrier,
+            vec![BeatKeyframe { time: 0.0, beat_frequency }],
+            duration,
+        )
+    }
+
+    /// Session whose beat frequency ramps linearly between keyframes
+    pub fn with_schedule(
+        mode: EntrainmentMode,
+        carrier: f64,
+        mut schedule: Vec<BeatKeyframe>,
+        duration: f64,
+    ) -> Result<Self> {
+        let carrier = crate::validate_frequency(carrier)?;
+        if carrier > ENTRAINMENT_MAX_CARRIER {
+            return Err(AudioEngineError::SafetyLimitExceeded(format!(
+                "carrier {} Hz is above the {} Hz entrainment limit",
+                carrier, ENTRAINMENT_MAX_CARRIER
+            )));
+        }
+        if schedule.is_empty() {
+            return Err(AudioEngineError::SafetyLimitExceeded("beat schedule is empty".to_string()));
+        }
+        if let Some(keyframe) = schedule
+            .iter()
+            .find(|k| !(ENTRAINMENT_MIN_BEAT..=ENTRAINMENT_MAX_BEAT).contains(&k.beat_frequency))
+        {
+            return Err(AudioEngineError::SafetyLimitExceeded(format!(
+                "beat frequency {} Hz is outside {}-{} Hz",
+                keyframe.beat_frequency, ENTRAINMENT_MIN_BEAT, ENTRAINMENT_MAX_BEAT
+            )));
+        }
+        if !(duration.is_finite() && duration > 0.0) {
+            return Err(AudioEngineError::SafetyLimitExceeded(format!(
+                "session duration must be positive, got {}",
+                duration
+            )));
+        }
+
+        schedule.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        Ok(Self {
+            mode,
+            carrier,
+            schedule,
+            duration,
+            amplitude: ND_SAFE_MAX_AMPLITUDE,
+            fade_in: 5.0,
+            fade_out: 5.0,
+        })
+    }
+
+    /// Preset session lasting `duration` seconds
+    pub fn preset(preset: EntrainmentPreset, mode: EntrainmentMode, duration: f64) -> Result<Self> {
+        let keyframes = |start: f64, end: f64| {
+            vec![
+                BeatKeyframe { time: 0.0, beat_frequency: start },
+                BeatKeyframe { time: duration * 0.75, beat_frequency: end },
+            ]
+        };
+
+        let (carrier, schedule) = match preset {
+            EntrainmentPreset::ReadingRoom => (crate::constants::FREQUENCY_LIBERATION, keyframes(10.0, 10.0)),
+            EntrainmentPreset::Meditation => (crate::constants::FREQUENCY_CHANGE, keyframes(10.0, 6.0)),
+            EntrainmentPreset::DeepRest => (crate::constants::FREQUENCY_LIBERATION, keyframes(6.0, 3.0)),
+            EntrainmentPreset::Focus => (crate::constants::FREQUENCY_TRANSFORMATION, keyframes(14.0, 14.0)),
+        };
+
+        Self::with_schedule(mode, carrier, schedule, duration)
+    }
+
+    pub fn with_amplitude(mut self, amplitude: f32) -> Self {
+        self.amplitude = amplitude;
+        self
+    }
+
+    pub fn with_fades(mut self, fade_in: f64, fade_out: f64) -> Self {
+        self.fade_in = fade_in;
+        self.fade_out = fade_out;
+        self
+    }
+
+    /// Beat frequency at `time` seconds, interpolated from the schedule
+    pub fn beat_frequency_at(&self, time: f64) -> f64 {
+        let first = self.schedule[0];
+        if time <= first.time {
+            return first.beat_frequency;
+        }
+
+        for pair in self.schedule.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            if time <= b.time {
+                let span = b.time - a.time;
+                if span <= 0.0 {
+                    return b.beat_frequency;
+                }
+                let x = (time - a.time) / span;
+                return a.beat_frequency + (b.beat_frequency - a.beat_frequency) * x;
+            }
+        }
+
+        self.schedule[self.schedule.len() - 1].beat_frequency
+    }
+
+    /// Streaming stereo generator for this session
+    pub fn source(&self, sample_rate: u32) -> EntrainmentSource {
+        let rate = sample_rate as f64;
+        let fade = |seconds: f64| (seconds.max(ND_SAFE_MIN_FADE_SECONDS) * rate).round().max(1.0) as u64;
+
+        EntrainmentSource {
+            session: self.clone(),
+            sample_rate: rate,
+            position: 0,
+            total: (self.duration * rate).round() as u64,
+            fade_in: fade(self.fade_in),
+            fade_out: fade(self.fade_out),
+            left_phase: 0.0,
+            right_phase: 0.0,
+            beat_phase: 0.0,
+            release_from: None,
+        }
+    }
+
+    /// Render the whole session as interleaved stereo samples
+    pub fn render(&self, sample_rate: u32) -> Vec<f32> {
+        let mut source = self.source(sample_rate);
+        let mut samples = Vec::with_capacity(source.total as usize * 2);
+        while let Some((left, right)) = source.next_frame() {
+            samples.push(left);
+            samples.push(right);
+        }
+        samples
+    }
+
+    /// Render the whole session to a stereo 16-bit dithered WAV file
+    pub fn render_to_wav<P: AsRef<std::path::Path>>(&self, path: P, sample_rate: u32) -> Result<()> {
+        let settings = crate::render::RenderSettings::default().with_sample_rate(sample_rate);
+        crate::render::write_wav(path, &self.render(sample_rate), &settings)
+    }
+
+    /// Play the session live; `note_off` on the returned ID fades it out
+    pub fn play(&self, controller: &SynthController) -> SynthResult<VoiceId> {
+        controller.play_source(Box::new(self.source(controller.sample_rate())))
+    }
+}
+
+/// Sample-by-sample generator for an entrainment session
+#[derive(Debug, Clone)]
+pub struct EntrainmentSource {
+    session: EntrainmentSession,
+    sample_rate: f64,
+    position: u64,
+    total: u64,
+    fade_in: u64,
+    fade_out: u64,
+    left_phase: f64,
+    right_phase: f64,
+    beat_phase: f64,
+    /// Position and gain at which an early release started
+    release_from: Option<(u64, f32)>,
+}
+
+impl EntrainmentSource {
+    fn envelope(&self) -> Option<f32> {
+        let ramp = |x: f64| (0.5 - 0.5 * (std::f64::consts::PI * x.clamp(0.0, 1.0)).cos()) as f32;
+
+        if let Some((start, gain)) = self.release_from {
+            let elapsed = self.position - start;
+            if elapsed >= self.fade_out {
+                return None;
+            }
+            return Some(gain * ramp(1.0 - elapsed as f64 / self.fade_out as f64));
+        }
+
+        if self.position >= self.total {
+            return None;
+        }
+
+        let fade_in = ramp(self.position as f64 / self.fade_in as f64);
+        let remaining = self.total - self.position;
+        let fade_out = ramp(remaining.saturating_sub(1) as f64 / self.fade_out as f64);
+        Some(fade_in.min(fade_out))
+    }
+}
+
+impl StereoSource for EntrainmentSource {
+    fn next_frame(&mut self) -> Option<(f32, f32)> {
+        let gain = self.envelope()? * self.session.amplitude.clamp(0.0, ND_SAFE_MAX_AMPLITUDE);
+
+        let time = self.position as f64 / self.sample_rate;
+        let beat = self.session.beat_frequency_at(time);
+        let carrier = self.session.carrier;
+
+        let frame = match self.session.mode {
+            EntrainmentMode::Binaural => {
+                // Split the beat evenly around the carrier
+                let left = (TAU * self.left_phase).sin() as f32 * gain;
+                let right = (TAU * self.right_phase).sin() as f32 * gain;
+                self.left_phase = (self.left_phase + (carrier - beat / 2.0) / self.sample_rate).fract();
+                self.right_phase = (self.right_phase + (carrier + beat / 2.0) / self.sample_rate).fract();
+                (left, right)
+            },
+            EntrainmentMode::Isochronic => {
+                let pulse = 0.5 - 0.5 * (TAU * self.beat_phase).cos();
+                let sample = ((TAU * self.left_phase).sin() * pulse) as f32 * gain;
+                self.left_phase = (self.left_phase + carrier / self.sample_rate).fract();
+                self.beat_phase = (self.beat_phase + beat / self.sample_rate).fract();
+                (sample, sample)
+            },
+        };
+
+        self.position += 1;
+        Some(frame)
+    }
+
+    fn release(&mut self) {
+        if self.release_from.is_none() {
+            let gain = self.envelope().unwrap_or(0.0);
+            self.release_from = Some((self.position, gain));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_safety_caps() {
+        assert!(EntrainmentSession::new(EntrainmentMode::Binaural, 200.0, 10.0, 60.0).is_ok());
+        assert!(EntrainmentSession::new(EntrainmentMode::Binaural, 200.0, 60.0, 60.0).is_err());
+        assert!(EntrainmentSession::new(EntrainmentMode::Binaural, 200.0, 0.1, 60.0).is_err());
+        assert!(EntrainmentSession::new(EntrainmentMode::Binaural, 5000.0, 10.0, 60.0).is_err());
+        assert!(EntrainmentSession::new(EntrainmentMode::Isochronic, 200.0, 10.0, 0.0).is_err());
+    }
+
+    #[test]
+    fn test_ramp_schedule_interpolation() {
+        let session = EntrainmentSession::preset(EntrainmentPreset::Meditation, EntrainmentMode::Binaural, 400.0).unwrap();
+        assert_eq!(session.beat_frequency_at(0.0), 10.0);
+        assert_eq!(session.beat_frequency_at(150.0), 8.0);
+        assert_eq!(session.beat_frequency_at(300.0), 6.0);
+        assert_eq!(session.beat_frequency_at(399.0), 6.0);
+    }
+
+    #[test]
+    fn test_render_is_bounded_and_faded() {
+        let session = EntrainmentSession::new(EntrainmentMode::Isochronic, 396.0, 10.0, 2.0)
+            .unwrap()
+            .with_amplitude(1.0)
+            .with_fades(0.5, 0.5);
+        let samples = session.render(8000);
+
+        assert_eq!(samples.len(), 2 * 16000);
+        assert_eq!(samples[0], 0.0);
+        assert!(samples[samples.len() - 1].abs() < 1e-6);
+        assert!(samples.iter().all(|s| s.abs() <= ND_SAFE_MAX_AMPLITUDE));
+    }
+
+    #[test]
+    fn test_release_fades_out_early() {
+        let session = EntrainmentSession::new(EntrainmentMode::Binaural, 200.0, 8.0, 600.0)
+            .unwrap()
+            .with_fades(0.1, 0.1);
+        let mut source = session.source(1000);
+        for _ in 0..500 {
+            source.next_frame();
+        }
+        source.release();
+
+        let remaining = std::iter::from_fn(|| source.next_frame()).count();
+        assert_eq!(remaining, 100);
+    }
+}
@@ -0,0 +1,133 @@
+/*!
+ * ENVELOPES
+ *
+ * Linear ADSR envelope generator used by every synthesis voice.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use serde::{Deserialize, Serialize};
+
+/// Envelope timing in seconds and sustain level (0.0-1.0)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AdsrSettings {
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+}
+
+impl Default for AdsrSettings {
+    fn default() -> Self {
+        // Gentle defaults: no clicks on note start or end
+        Self {
+            attack: 0.01,
+            decay: 0.1,
+            sustain: 0.8,
+            release: 0.2,
+        }
+    }
+}
+
+/// Current envelope stage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvelopeStage {
+    Idle,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+/// ADSR envelope generator
+#[derive(Debug, Clone)]
+pub struct AdsrEnvelope {
+    pub settings: AdsrSettings,
+    stage: EnvelopeStage,
+    level: f32,
+    release_step: f32,
+    sample_rate: f32,
+}
+
+impl AdsrEnvelope {
+    pub fn new(settings: AdsrSettings, sample_rate: u32) -> Self {
+        Self {
+            settings,
+            stage: EnvelopeStage::Idle,
+            level: 0.0,
+            release_step: 0.0,
+            sample_rate: sample_rate as f32,
+        }
+    }
+
+    /// Start the attack stage from the current level
+    pub fn note_on(&mut self) {
+        self.stage = EnvelopeStage::Attack;
+    }
+
+    /// Start the release stage from the current level
+    pub fn note_off(&mut self) {
+        if self.stage == EnvelopeStage::Idle {
+            return;
+        }
+        if self.level <= 0.0 {
+            self.stage = EnvelopeStage::Idle;
+            return;
+        }
+        self.release_step = self.level / self.stage_samples(self.settings.release);
+        self.stage = EnvelopeStage::Release;
+    }
+
+    pub fn stage(&self) -> EnvelopeStage {
+        self.stage
+    }
+
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+
+    /// Whether the envelope has fully released
+    pub fn is_finished(&self) -> bool {
+        self.stage == EnvelopeStage::Idle
+    }
+
+    /// Advance one sample and return the envelope level
+    pub fn next_sample(&mut self) -> f32 {
+        let sustain = self.settings.sustain.clamp(0.0, 1.0);
+
+        match self.stage {
+            EnvelopeStage::Idle => {},
+            EnvelopeStage::Attack => {
+                self.level += 1.0 / self.stage_samples(self.settings.attack);
+                if self.level >= 1.0 {
+                    self.level = 1.0;
+                    self.stage = EnvelopeStage::Decay;
+                }
+            },
+            EnvelopeStage::Decay => {
+                self.level -= (1.0 - sustain) / self.stage_samples(self.settings.decay);
+                if self.level <= sustain {
+                    self.level = sustain;
+                    self.stage = EnvelopeStage::Sustain;
+                }
+            },
+            EnvelopeStage::Sustain => {
+                self.level = sustain;
+            },
+            EnvelopeStage::Release => {
+                self.level -= self.release_step;
+                if self.level <= 0.0 {
+                    self.level = 0.0;
+                    self.stage = EnvelopeStage::Idle;
+                }
+            },
+        }
+
+        self.level
+    }
+
+    fn stage_samples(&self, seconds: f32) -> f32 {
+        (seconds * self.sample_rate).max(1.0)
+    }
+}
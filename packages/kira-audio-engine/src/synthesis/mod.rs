@@ -14,17 +14,32 @@
  * - Visual-audio fusion capabilities
  * - Sound spell frequency mapping
  * - Major Arcana level mapping
+ * - Real-time oscillator/envelope voices on a dedicated cpal audio thread
+ * - Solfeggio and harmonic-series tone generation
+ * - Binaural beat and isochronic entrainment
  */
 
 mod virtual_synths;
 mod synthesizer_models;
 mod consciousness_synthesis;
 mod godot_integration;
+mod oscillator;
+mod envelope;
+mod voice;
+mod realtime;
+mod tone_generator;
+mod entrainment;
 
 pub use virtual_synths::*;
 pub use synthesizer_models::*;
 pub use consciousness_synthesis::*;
 pub use godot_integration::*;
+pub use oscillator::*;
+pub use envelope::*;
+pub use voice::*;
+pub use realtime::*;
+pub use tone_generator::*;
+pub use entrainment::*;
 
 // Re-export synthesis types
 pub use kira::instance::Instance;
@@ -51,6 +66,12 @@ pub enum SynthesizerError {
     
     #[error("Consciousness level {0} not supported for this synthesizer")]
     UnsupportedConsciousnessLevel(u8),
+
+    #[error("Audio device error: {0}")]
+    AudioDeviceError(String),
+
+    #[error("Invalid note: {0}")]
+    InvalidNote(String),
 }
 
 pub type SynthResult<T> = std::result::Result<T, SynthesizerError>;
\ No newline at end of file
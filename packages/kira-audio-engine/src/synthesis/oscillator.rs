@@ -0,0 +1,109 @@
+/*!
+ * OSCILLATORS
+ *
+ * Band-limited oscillators for the real-time synthesis engine. Saw and
+ * square waves use PolyBLEP correction to keep aliasing out of the
+ * upper consciousness frequencies.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use serde::{Deserialize, Serialize};
+use std::f64::consts::TAU;
+
+/// Oscillator waveform shapes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Waveform {
+    #[default]
+    Sine,
+    Triangle,
+    Sawtooth,
+    Square,
+    Noise,
+}
+
+/// Single phase-accumulating oscillator
+#[derive(Debug, Clone)]
+pub struct Oscillator {
+    pub waveform: Waveform,
+    frequency: f64,
+    sample_rate: f64,
+    phase: f64,
+    phase_increment: f64,
+    noise_state: u32,
+}
+
+impl Oscillator {
+    /// Create an oscillator at `frequency` Hz
+    pub fn new(waveform: Waveform, frequency: f64, sample_rate: u32) -> Self {
+        let mut oscillator = Self {
+            waveform,
+            frequency,
+            sample_rate: sample_rate as f64,
+            phase: 0.0,
+            phase_increment: 0.0,
+            noise_state: 0x9E37_79B9,
+        };
+        oscillator.set_frequency(frequency);
+        oscillator
+    }
+
+    /// Change the oscillator frequency without resetting phase
+    pub fn set_frequency(&mut self, frequency: f64) {
+        self.frequency = frequency;
+        self.phase_increment = frequency / self.sample_rate;
+    }
+
+    pub fn frequency(&self) -> f64 {
+        self.frequency
+    }
+
+    /// Reset phase to the start of the cycle
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+
+    /// Produce the next sample in the range [-1.0, 1.0]
+    pub fn next_sample(&mut self) -> f32 {
+        let t = self.phase;
+        let dt = self.phase_increment;
+
+        let sample = match self.waveform {
+            Waveform::Sine => (t * TAU).sin(),
+            Waveform::Triangle => 1.0 - 4.0 * (t - 0.5).abs(),
+            Waveform::Sawtooth => (2.0 * t - 1.0) - poly_blep(t, dt),
+            Waveform::Square => {
+                let naive = if t < 0.5 { 1.0 } else { -1.0 };
+                naive + poly_blep(t, dt) - poly_blep((t + 0.5).fract(), dt)
+            },
+            Waveform::Noise => {
+                // xorshift32 white noise
+                let mut x = self.noise_state;
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                self.noise_state = x;
+                (x as f64 / u32::MAX as f64) * 2.0 - 1.0
+            },
+        };
+
+        self.phase = (self.phase + dt).fract();
+        sample as f32
+    }
+}
+
+/// PolyBLEP residual for a discontinuity at phase 0
+fn poly_blep(t: f64, dt: f64) -> f64 {
+    if dt <= 0.0 {
+        0.0
+    } else if t < dt {
+        let t = t / dt;
+        t + t - t * t - 1.0
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt;
+        t * t + t + t + 1.0
+    } else {
+        0.0
+    }
+}
@@ -0,0 +1,671 @@
+/*!
+ * REAL-TIME SYNTHESIS ENGINE
+ *
+ * Runs the voice allocator on a dedicated cpal audio thread. The control
+ * side (Godot, WASM, pattern playback) talks to the audio callback via a
+ * command channel that the callback polls without waiting, so it never
+ * blocks on a lock. Pending notes, streaming sources and the callback's
+ * scratch buffer are preallocated, so steady playback does not allocate;
+ * queueing past those capacities grows them, and a finished streaming
+ * source is freed on the audio thread.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::{NoteSettings, SynthResult, SynthesizerError, VoiceAllocator, VoiceId, DEFAULT_MAX_VOICES};
+use crate::analysis::AnalysisTap;
+use crate::pattern::PatternEvent;
+use crate::scheduler::{MusicalClock, ScheduledEvent, Transport};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SizedSample};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+/// Default master gain, leaving headroom for stacked voices
+pub const DEFAULT_MASTER_GAIN: f32 = 0.25;
+
+/// Longest delay or duration, in seconds, a note can be scheduled with
+pub const MAX_SCHEDULE_SECONDS: f64 = 86_400.0;
+
+/// Notes that can wait for their start sample before the queue reallocates
+const PENDING_NOTE_CAPACITY: usize = 1024;
+
+/// Streaming sources mixed before the source list reallocates
+const SOURCE_CAPACITY: usize = 64;
+
+/// Interleaved samples preallocated for the device callback
+const CALLBACK_SCRATCH_CAPACITY: usize = 16384;
+
+/// Streaming stereo generator mixed alongside the voices
+pub trait StereoSource: Send + std::fmt::Debug {
+    /// Next (left, right) frame, or `None` once the source has finished
+    fn next_frame(&mut self) -> Option<(f32, f32)>;
+
+    /// Begin a graceful stop (e.g. fade out); sources stop immediately by default
+    fn release(&mut self) {}
+}
+
+/// Messages sent from the control side to the audio thread
+#[derive(Debug)]
+pub enum SynthCommand {
+    NoteOn {
+        id: VoiceId,
+        settings: NoteSettings,
+        /// Samples to wait before the note starts
+        delay_samples: u64,
+        /// Automatic release after this many samples
+        duration_samples: Option<u64>,
+    },
+    /// Start a note at an absolute position on the engine's sample clock
+    NoteOnAt {
+        id: VoiceId,
+        settings: NoteSettings,
+        start_sample: u64,
+        duration_samples: Option<u64>,
+    },
+    PlaySource {
+        id: VoiceId,
+        source: Box<dyn StereoSource>,
+    },
+    NoteOff { id: VoiceId },
+    AllNotesOff,
+    SetMasterGain(f32),
+    /// Copy every rendered buffer into an analysis tap
+    SetAnalysisTap(Option<AnalysisTap>),
+}
+
+/// A note waiting for its start sample
+#[derive(Debug, Clone)]
+struct PendingNote {
+    start_sample: u64,
+    id: VoiceId,
+    settings: NoteSettings,
+    duration_samples: Option<u64>,
+}
+
+/// Audio-thread state: applies commands and renders voices.
+///
+/// Independent of any audio device, so it can also be driven offline.
+pub struct SynthEngine {
+    allocator: VoiceAllocator,
+    commands: Receiver<SynthCommand>,
+    pending: Vec<PendingNote>,
+    sources: Vec<(VoiceId, Box<dyn StereoSource>, bool)>,
+    master_gain: f32,
+    sample_clock: u64,
+    /// Sample clock as seen by controllers
+    published_clock: Arc<AtomicU64>,
+    analysis_tap: Option<AnalysisTap>,
+    channels: usize,
+}
+
+impl SynthEngine {
+    pub fn new(commands: Receiver<SynthCommand>, sample_rate: u32, channels: usize) -> Self {
+        Self {
+            allocator: VoiceAllocator::new(DEFAULT_MAX_VOICES, sample_rate),
+            commands,
+            pending: Vec::with_capacity(PENDING_NOTE_CAPACITY),
+            sources: Vec::with_capacity(SOURCE_CAPACITY),
+            master_gain: DEFAULT_MASTER_GAIN,
+            sample_clock: 0,
+            published_clock: Arc::new(AtomicU64::new(0)),
+            analysis_tap: None,
+            channels: channels.max(1),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.allocator.sample_rate()
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Number of frames rendered so far
+    pub fn sample_clock(&self) -> u64 {
+        self.sample_clock
+    }
+
+    pub fn active_voices(&self) -> usize {
+        self.allocator.active_voices()
+    }
+
+    pub fn active_sources(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// Whether any voice or source is sounding or waiting to start
+    pub fn is_idle(&self) -> bool {
+        self.allocator.active_voices() == 0 && self.pending.is_empty() && self.sources.is_empty()
+    }
+
+    /// Fill an interleaved buffer, starting pending notes on their exact frame
+    pub fn render(&mut self, output: &mut [f32]) {
+        self.drain_commands();
+
+        let channels = self.channels;
+        let mut offset = 0;
+        while offset < output.len() {
+            self.start_due_notes();
+
+            let frames_left = ((output.len() - offset) / channels).max(1) as u64;
+            let frames = self
+                .pending
+                .iter()
+                .map(|note| note.start_sample - self.sample_clock)
+                .min()
+                .map_or(frames_left, |until_next| until_next.clamp(1, frames_left));
+
+            let end = (offset + frames as usize * channels).min(output.len());
+            self.allocator.render(&mut output[offset..end], channels, self.master_gain);
+            self.sample_clock += frames;
+            offset = end;
+        }
+
+        self.mix_sources(output);
+        if let Some(tap) = &self.analysis_tap {
+            tap.push_interleaved(output, channels);
+        }
+        self.published_clock.store(self.sample_clock, Ordering::Release);
+    }
+
+    /// Add streaming sources on top of the rendered voices
+    fn mix_sources(&mut self, output: &mut [f32]) {
+        if self.sources.is_empty() {
+            return;
+        }
+
+        let gain = self.master_gain;
+        for frame in output.chunks_mut(self.channels) {
+            for (_, source, finished) in self.sources.iter_mut().filter(|(_, _, finished)| !finished) {
+                let Some((left, right)) = source.next_frame() else {
+                    *finished = true;
+                    continue;
+                };
+                match frame.len() {
+                    1 => frame[0] += (left + right) * 0.5 * gain,
+                    _ => {
+                        frame[0] += left * gain;
+                        frame[1] += right * gain;
+                    },
+                }
+            }
+        }
+
+        self.sources.retain(|(_, _, finished)| !finished);
+    }
+
+    fn drain_commands(&mut self) {
+        while let Ok(command) = self.commands.try_recv() {
+            match command {
+                SynthCommand::NoteOn { id, settings, delay_samples, duration_samples } => {
+                    self.pending.push(PendingNote {
+                        start_sample: self.sample_clock.saturating_add(delay_samples),
+                        id,
+                        settings,
+                        duration_samples,
+                    });
+                },
+                SynthCommand::NoteOnAt { id, settings, start_sample, duration_samples } => {
+                    // Notes that arrive late start immediately
+                    self.pending.push(PendingNote {
+                        start_sample: start_sample.max(self.sample_clock),
+                        id,
+                        settings,
+                        duration_samples,
+                    });
+                },
+                SynthCommand::PlaySource { id, source } => {
+                    self.sources.push((id, source, false));
+                },
+                SynthCommand::NoteOff { id } => {
+                    self.pending.retain(|note| note.id != id);
+                    self.allocator.note_off(id);
+                    for (_, source, _) in self.sources.iter_mut().filter(|(source_id, _, _)| *source_id == id) {
+                        source.release();
+                    }
+                },
+                SynthCommand::AllNotesOff => {
+                    self.pending.clear();
+                    self.allocator.all_notes_off();
+                    for (_, source, _) in &mut self.sources {
+                        source.release();
+                    }
+                },
+                SynthCommand::SetMasterGain(gain) => {
+                    if gain.is_finite() {
+                        self.master_gain = gain.clamp(0.0, 1.0);
+                    }
+                },
+                SynthCommand::SetAnalysisTap(tap) => {
+                    self.analysis_tap = tap;
+                },
+            }
+        }
+    }
+
+    fn start_due_notes(&mut self) {
+        let now = self.sample_clock;
+        let mut index = 0;
+        while index < self.pending.len() {
+            if self.pending[index].start_sample <= now {
+                let note = self.pending.swap_remove(index);
+                self.allocator.note_on(note.id, note.settings, note.duration_samples, now);
+            } else {
+                index += 1;
+            }
+        }
+    }
+}
+
+/// Control handle for sending notes to a synth engine
+#[derive(Debug, Clone)]
+pub struct SynthController {
+    commands: Sender<SynthCommand>,
+    next_voice_id: Arc<AtomicU64>,
+    sample_clock: Arc<AtomicU64>,
+    sample_rate: u32,
+}
+
+impl SynthController {
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Frames the engine had rendered at the end of its last buffer
+    pub fn sample_clock(&self) -> u64 {
+        self.sample_clock.load(Ordering::Acquire)
+    }
+
+    /// Start a note immediately; it sounds until `note_off`
+    pub fn note_on(&self, settings: NoteSettings) -> SynthResult<VoiceId> {
+        self.schedule_note(settings, 0.0, None)
+    }
+
+    /// Start a note after `delay` seconds, releasing it after `duration` seconds
+    pub fn schedule_note(&self, settings: NoteSettings, delay: f64, duration: Option<f64>) -> SynthResult<VoiceId> {
+        crate::validate_frequency(settings.frequency)
+            .map_err(|e| SynthesizerError::InvalidNote(e.to_string()))?;
+        for seconds in std::iter::once(delay).chain(duration) {
+            if !seconds.is_finite() || seconds > MAX_SCHEDULE_SECONDS {
+                return Err(SynthesizerError::InvalidNote(format!(
+                    "note timing {} s must be finite and at most {} s",
+                    seconds, MAX_SCHEDULE_SECONDS
+                )));
+            }
+        }
+
+        let id = self.next_voice_id.fetch_add(1, Ordering::Relaxed);
+        self.send(SynthCommand::NoteOn {
+            id,
+            settings,
+            delay_samples: self.seconds_to_samples(delay),
+            duration_samples: duration.map(|seconds| self.seconds_to_samples(seconds)),
+        })?;
+        Ok(id)
+    }
+
+    /// Start a note at absolute sample `start_sample`, releasing it after `duration_samples`
+    pub fn schedule_note_at(
+        &self,
+        settings: NoteSettings,
+        start_sample: u64,
+        duration_samples: Option<u64>,
+    ) -> SynthResult<VoiceId> {
+        crate::validate_frequency(settings.frequency)
+            .map_err(|e| SynthesizerError::InvalidNote(e.to_string()))?;
+
+        let id = self.next_voice_id.fetch_add(1, Ordering::Relaxed);
+        self.send(SynthCommand::NoteOnAt {
+            id,
+            settings,
+            start_sample,
+            duration_samples,
+        })?;
+        Ok(id)
+    }
+
+    /// Anchor `clock` so that `start_cycle` begins `latency` seconds from now
+    pub fn start_transport(&self, clock: MusicalClock, start_cycle: f64, latency: f64) -> Transport {
+        Transport::new(clock, self.sample_clock().saturating_add(self.seconds_to_samples(latency)), start_cycle)
+    }
+
+    /// Play timestamped events; those whose values are not notes are skipped.
+    ///
+    /// Every note is validated before any is sent, so an error never leaves
+    /// part of the batch sounding.
+    pub fn play_scheduled(&self, events: &[ScheduledEvent], template: NoteSettings) -> SynthResult<Vec<VoiceId>> {
+        let mut notes = Vec::with_capacity(events.len());
+        for scheduled in events {
+            let Some(frequency) = scheduled.event.frequency() else {
+                continue;
+            };
+            crate::validate_frequency(frequency).map_err(|e| SynthesizerError::InvalidNote(e.to_string()))?;
+            notes.push((NoteSettings { frequency, ..template }, scheduled));
+        }
+
+        notes
+            .into_iter()
+            .map(|(settings, scheduled)| {
+                self.schedule_note_at(settings, scheduled.start_sample, Some(scheduled.duration_samples()))
+            })
+            .collect()
+    }
+
+    /// Start a streaming source; stop it with `note_off`
+    pub fn play_source(&self, source: Box<dyn StereoSource>) -> SynthResult<VoiceId> {
+        let id = self.next_voice_id.fetch_add(1, Ordering::Relaxed);
+        self.send(SynthCommand::PlaySource { id, source })?;
+        Ok(id)
+    }
+
+    pub fn note_off(&self, id: VoiceId) -> SynthResult<()> {
+        self.send(SynthCommand::NoteOff { id })
+    }
+
+    pub fn all_notes_off(&self) -> SynthResult<()> {
+        self.send(SynthCommand::AllNotesOff)
+    }
+
+    pub fn set_master_gain(&self, gain: f32) -> SynthResult<()> {
+        self.send(SynthCommand::SetMasterGain(gain))
+    }
+
+    /// Start copying the engine's output into a new analysis tap
+    pub fn attach_analysis_tap(&self) -> SynthResult<AnalysisTap> {
+        let tap = AnalysisTap::default();
+        self.send(SynthCommand::SetAnalysisTap(Some(tap.clone())))?;
+        Ok(tap)
+    }
+
+    pub fn detach_analysis_tap(&self) -> SynthResult<()> {
+        self.send(SynthCommand::SetAnalysisTap(None))
+    }
+
+    /// Play pattern events, timed relative to the first event's cycle.
+    ///
+    /// Events whose values are not notes are skipped.
+    pub fn play_pattern_events(
+        &self,
+        events: &[PatternEvent],
+        cycles_per_second: f64,
+        template: NoteSettings,
+    ) -> SynthResult<Vec<VoiceId>> {
+        if !(cycles_per_second.is_finite() && cycles_per_second > 0.0) {
+            return Err(SynthesizerError::InvalidNote(format!(
+                "cycles per second must be positive, got {}",
+                cycles_per_second
+            )));
+        }
+
+        let clock = MusicalClock::from_cycles_per_second(cycles_per_second, self.sample_rate)
+            .map_err(|e| SynthesizerError::InvalidNote(e.to_string()))?;
+        let origin = events.iter().map(|e| e.begin.floor()).fold(f64::INFINITY, f64::min);
+        let transport = self.start_transport(clock, origin, 0.0);
+        self.play_scheduled(&transport.schedule(events), template)
+    }
+
+    fn seconds_to_samples(&self, seconds: f64) -> u64 {
+        (seconds.max(0.0) * self.sample_rate as f64).round() as u64
+    }
+
+    fn send(&self, command: SynthCommand) -> SynthResult<()> {
+        self.commands
+            .send(command)
+            .map_err(|_| SynthesizerError::AudioDeviceError("audio thread has stopped".to_string()))
+    }
+}
+
+/// Create a connected controller/engine pair without an audio device
+pub fn synth_channel(sample_rate: u32, channels: usize) -> (SynthController, SynthEngine) {
+    let (sender, receiver) = mpsc::channel();
+    let engine = SynthEngine::new(receiver, sample_rate, channels);
+    let controller = SynthController {
+        commands: sender,
+        next_voice_id: Arc::new(AtomicU64::new(1)),
+        sample_clock: Arc::clone(&engine.published_clock),
+        sample_rate,
+    };
+    (controller, engine)
+}
+
+/// Synth engine playing through the default output device on its own thread
+pub struct RealtimeSynth {
+    controller: SynthController,
+    shutdown: Option<Sender<()>>,
+    audio_thread: Option<JoinHandle<()>>,
+}
+
+impl RealtimeSynth {
+    /// Open the default output device and start the audio thread
+    pub fn start() -> SynthResult<Self> {
+        let (ready_sender, ready_receiver) = mpsc::channel::<SynthResult<SynthController>>();
+        let (shutdown_sender, shutdown_receiver) = mpsc::channel::<()>();
+
+        // cpal streams are not Send on every platform, so the stream is
+        // created and kept alive entirely on the audio thread
+        let audio_thread = std::thread::Builder::new()
+            .name("kira-synth-audio".to_string())
+            .spawn(move || match open_output_stream() {
+                Ok((stream, controller)) => {
+                    let _ = ready_sender.send(Ok(controller));
+                    let _ = shutdown_receiver.recv();
+                    drop(stream);
+                },
+                Err(error) => {
+                    let _ = ready_sender.send(Err(error));
+                },
+            })
+            .map_err(|e| SynthesizerError::AudioDeviceError(e.to_string()))?;
+
+        let controller = ready_receiver
+            .recv()
+            .map_err(|_| SynthesizerError::AudioDeviceError("audio thread exited during startup".to_string()))??;
+
+        Ok(Self {
+            controller,
+            shutdown: Some(shutdown_sender),
+            audio_thread: Some(audio_thread),
+        })
+    }
+
+    /// Handle for sending notes to the audio thread
+    pub fn controller(&self) -> &SynthController {
+        &self.controller
+    }
+}
+
+impl Drop for RealtimeSynth {
+    fn drop(&mut self) {
+        let _ = self.controller.all_notes_off();
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        if let Some(handle) = self.audio_thread.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn open_output_stream() -> SynthResult<(cpal::Stream, SynthController)> {
+    let host = cpal::default_host();
+    let device = host
+        .default_output_device()
+        .ok_or_else(|| SynthesizerError::AudioDeviceError("no output device available".to_string()))?;
+    let supported = device
+        .default_output_config()
+        .map_err(|e| SynthesizerError::AudioDeviceError(e.to_string()))?;
+
+    let sample_format = supported.sample_format();
+    let config: cpal::StreamConfig = supported.into();
+    let (controller, engine) = synth_channel(config.sample_rate.0, config.channels as usize);
+
+    let stream = match sample_format {
+        cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config, engine),
+        cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config, engine),
+        cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config, engine),
+        other => Err(SynthesizerError::AudioDeviceError(format!("unsupported sample format {:?}", other))),
+    }?;
+
+    stream
+        .play()
+        .map_err(|e| SynthesizerError::AudioDeviceError(e.to_string()))?;
+
+    Ok((stream, controller))
+}
+
+fn build_stream<T>(device: &cpal::Device, config: &cpal::StreamConfig, mut engine: SynthEngine) -> SynthResult<cpal::Stream>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let mut scratch: Vec<f32> = vec![0.0; CALLBACK_SCRATCH_CAPACITY];
+
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                if scratch.len() < data.len() {
+                    scratch.resize(data.len(), 0.0);
+                }
+                let buffer = &mut scratch[..data.len()];
+                engine.render(buffer);
+                for (out, sample) in data.iter_mut().zip(buffer.iter()) {
+                    *out = T::from_sample(*sample);
+                }
+            },
+            |error| log::error!("kira synth stream error: {}", error),
+            None,
+        )
+        .map_err(|e| SynthesizerError::AudioDeviceError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_engine_starts_notes_on_exact_frame() {
+        let (controller, mut engine) = synth_channel(48000, 1);
+        controller.schedule_note(NoteSettings::new(440.0, 1.0), 100.0 / 48000.0, None).unwrap();
+
+        let mut buffer = vec![0.0; 256];
+        engine.render(&mut buffer);
+
+        assert!(buffer[..100].iter().all(|s| *s == 0.0));
+        assert!(buffer[100..].iter().any(|s| *s != 0.0));
+        assert_eq!(engine.sample_clock(), 256);
+    }
+
+    #[test]
+    fn test_timed_notes_release_and_free_voices() {
+        let (controller, mut engine) = synth_channel(48000, 2);
+        controller.schedule_note(NoteSettings::new(528.0, 0.8), 0.0, Some(0.01)).unwrap();
+
+        let mut buffer = vec![0.0; 2 * 48000];
+        engine.render(&mut buffer);
+
+        assert!(engine.is_idle());
+    }
+
+    #[test]
+    fn test_pattern_events_schedule_notes_only() {
+        let (controller, mut engine) = synth_channel(48000, 1);
+        let events = crate::pattern::Pattern::parse("c4 ~ bd e4").unwrap().query_cycle(0);
+        let ids = controller
+            .play_pattern_events(&events, 1.0, NoteSettings::new(440.0, 1.0))
+            .unwrap();
+        assert_eq!(ids.len(), 2);
+
+        let mut buffer = vec![0.0; 64];
+        engine.render(&mut buffer);
+        assert_eq!(engine.active_voices(), 1);
+    }
+
+    #[test]
+    fn test_transport_keeps_separately_queued_cycles_aligned() {
+        let (controller, mut engine) = synth_channel(1000, 1);
+        let clock = MusicalClock::from_cycles_per_second(1.0, 1000).unwrap();
+        let transport = controller.start_transport(clock, 0.0, 0.0);
+        let pattern = crate::pattern::Pattern::parse("c4 ~").unwrap();
+
+        controller
+            .play_scheduled(&transport.schedule(&pattern.query_cycle(0)), NoteSettings::new(440.0, 1.0))
+            .unwrap();
+        let mut buffer = vec![0.0; 800];
+        engine.render(&mut buffer);
+        assert_eq!(controller.sample_clock(), 800);
+        assert!(engine.is_idle());
+
+        // Queued 800 samples in, but still lands exactly on the cycle boundary
+        controller
+            .play_scheduled(&transport.schedule(&pattern.query_cycle(1)), NoteSettings::new(440.0, 1.0))
+            .unwrap();
+        let mut buffer = vec![0.0; 400];
+        engine.render(&mut buffer);
+        assert!(buffer[..200].iter().all(|s| *s == 0.0));
+        assert!(buffer[200..].iter().any(|s| *s != 0.0));
+    }
+
+    #[test]
+    fn test_analysis_tap_receives_output() {
+        let (controller, mut engine) = synth_channel(48000, 2);
+        let tap = controller.attach_analysis_tap().unwrap();
+        controller.note_on(NoteSettings::new(440.0, 1.0)).unwrap();
+
+        let mut buffer = vec![0.0; 512];
+        engine.render(&mut buffer);
+        let samples = tap.drain();
+        assert_eq!(samples.len(), 256);
+        assert!(samples.iter().any(|s| *s != 0.0));
+    }
+
+    #[test]
+    fn test_scheduled_batch_with_invalid_note_sends_nothing() {
+        let (controller, mut engine) = synth_channel(1000, 1);
+        let clock = MusicalClock::from_cycles_per_second(1.0, 1000).unwrap();
+        let transport = controller.start_transport(clock, 0.0, 0.0);
+        let events = crate::pattern::Pattern::parse("c4 e4 c-1").unwrap().query_cycle(0);
+
+        let result = controller.play_scheduled(&transport.schedule(&events), NoteSettings::new(440.0, 1.0));
+        assert!(matches!(result, Err(SynthesizerError::InvalidNote(_))));
+
+        let mut buffer = vec![0.0; 1000];
+        engine.render(&mut buffer);
+        assert!(engine.is_idle());
+        assert!(buffer.iter().all(|s| *s == 0.0));
+    }
+
+    #[test]
+    fn test_invalid_frequency_rejected() {
+        let (controller, _engine) = synth_channel(48000, 1);
+        assert!(controller.note_on(NoteSettings::new(5.0, 1.0)).is_err());
+    }
+
+    #[test]
+    fn test_note_timing_must_be_finite_and_bounded() {
+        let (controller, mut engine) = synth_channel(48000, 1);
+        let note = NoteSettings::new(440.0, 0.5);
+        for (delay, duration) in [
+            (f64::NAN, None),
+            (f64::INFINITY, None),
+            (MAX_SCHEDULE_SECONDS * 2.0, None),
+            (0.0, Some(f64::NAN)),
+            (0.0, Some(1e300)),
+        ] {
+            assert!(matches!(
+                controller.schedule_note(note, delay, duration),
+                Err(SynthesizerError::InvalidNote(_))
+            ));
+        }
+
+        // The longest allowed delay still queues without overflowing the clock
+        controller.schedule_note(note, MAX_SCHEDULE_SECONDS, Some(1.0)).unwrap();
+        let mut buffer = vec![0.0; 64];
+        engine.render(&mut buffer);
+        assert!(buffer.iter().all(|s| *s == 0.0));
+    }
+}
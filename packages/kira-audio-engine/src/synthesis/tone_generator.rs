@@ -0,0 +1,328 @@
+/*!
+ * SOLFEGGIO AND HARMONIC-SERIES TONES
+ *
+ * Additive tone generator for the seven solfeggio frequencies and their
+ * harmonic series, tied to Codex 144:99 node numbers. Output is always
+ * faded in/out and limited to the ND-safe peak amplitude, whether it is
+ * rendered offline to WAV or played live through the real-time engine.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::{AdsrSettings, NoteSettings, SynthController, SynthResult, SynthesizerError, VoiceId, Waveform};
+use crate::constants::{
+    CODEX_NODE_COUNT, ND_SAFE_MAX_AMPLITUDE, ND_SAFE_MIN_FADE_SECONDS, SOLFEGGIO_FREQUENCIES,
+};
+use crate::render::{write_wav, RenderSettings};
+use crate::{AudioEngineError, Result};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::TAU;
+use std::path::Path;
+
+/// Longest tone, in seconds, that `ToneSpec::render` will produce
+pub const MAX_TONE_SECONDS: f64 = 3600.0;
+
+/// How partial amplitudes fall off with harmonic number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum HarmonicRolloff {
+    /// Every partial at equal amplitude
+    Flat,
+    /// Amplitude 1/n (natural string-like spectrum)
+    #[default]
+    Inverse,
+    /// Amplitude 1/n² (soft, flute-like spectrum)
+    InverseSquare,
+}
+
+impl HarmonicRolloff {
+    fn weight(self, harmonic: u32) -> f32 {
+        let n = harmonic as f32;
+        match self {
+            HarmonicRolloff::Flat => 1.0,
+            HarmonicRolloff::Inverse => 1.0 / n,
+            HarmonicRolloff::InverseSquare => 1.0 / (n * n),
+        }
+    }
+}
+
+/// One sine component of a tone
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Partial {
+    /// Harmonic number (1 = fundamental)
+    pub harmonic: u32,
+    pub frequency: f64,
+    /// Relative amplitude before peak normalization
+    pub amplitude: f32,
+}
+
+/// A sustained additive tone with click-free fades
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToneSpec {
+    pub fundamental: f64,
+    pub partials: Vec<Partial>,
+    /// Peak output amplitude, capped at `ND_SAFE_MAX_AMPLITUDE`
+    pub amplitude: f32,
+    pub fade_in: f64,
+    pub fade_out: f64,
+}
+
+impl ToneSpec {
+    /// Pure sine tone at a solfeggio (or any audible) frequency
+    pub fn solfeggio(frequency: f64) -> Result<Self> {
+        Self::harmonic_series(frequency, 1, HarmonicRolloff::Flat)
+    }
+
+    /// Fundamental plus `count` harmonics (including the fundamental).
+    ///
+    /// Harmonics above 20 kHz are dropped.
+    pub fn harmonic_series(fundamental: f64, count: u32, rolloff: HarmonicRolloff) -> Result<Self> {
+        let fundamental = crate::validate_frequency(fundamental)?;
+        // Only harmonics up to 20 kHz survive, so never walk past the last of them
+        let audible = (20_000.0 / fundamental).floor() as u32;
+
+        let partials = (1..=count.clamp(1, audible))
+            .map(|harmonic| Partial {
+                harmonic,
+                frequency: fundamental * harmonic as f64,
+                amplitude: rolloff.weight(harmonic),
+            })
+            .filter(|partial| crate::validate_frequency(partial.frequency).is_ok())
+            .collect();
+
+        Ok(Self {
+            fundamental,
+            partials,
+            amplitude: ND_SAFE_MAX_AMPLITUDE,
+            fade_in: 0.5,
+            fade_out: 1.0,
+        })
+    }
+
+    /// Tone for a Codex 144:99 node.
+    ///
+    /// Nodes cycle through the seven solfeggio frequencies in order, and the
+    /// node's digital root (1-9) sets how many harmonics sound.
+    pub fn for_codex_node(node: u16) -> Result<Self> {
+        if node == 0 || node > CODEX_NODE_COUNT {
+            return Err(AudioEngineError::InvalidCodexNode(node));
+        }
+
+        Self::harmonic_series(
+            codex_node_frequency(node)?,
+            digital_root(node as u32),
+            HarmonicRolloff::Inverse,
+        )
+    }
+
+    pub fn with_amplitude(mut self, amplitude: f32) -> Self {
+        self.amplitude = amplitude;
+        self
+    }
+
+    pub fn with_fades(mut self, fade_in: f64, fade_out: f64) -> Self {
+        self.fade_in = fade_in;
+        self.fade_out = fade_out;
+        self
+    }
+
+    /// Peak amplitude actually used, after the ND-safe cap
+    pub fn safe_amplitude(&self) -> f32 {
+        self.amplitude.clamp(0.0, ND_SAFE_MAX_AMPLITUDE)
+    }
+
+    /// Render `duration` seconds of mono audio, up to `MAX_TONE_SECONDS`
+    pub fn render(&self, duration: f64, sample_rate: u32) -> Result<Vec<f32>> {
+        if !duration.is_finite() || duration > MAX_TONE_SECONDS {
+            return Err(AudioEngineError::RenderError(format!(
+                "tone duration {} s must be finite and at most {} s",
+                duration, MAX_TONE_SECONDS
+            )));
+        }
+
+        let rate = sample_rate as f64;
+        let total = (duration.max(0.0) * rate).round() as usize;
+        let nyquist = rate / 2.0;
+
+        let partials: Vec<&Partial> = self.partials.iter().filter(|p| p.frequency < nyquist).collect();
+        let weight_sum: f32 = partials.iter().map(|p| p.amplitude.abs()).sum();
+        if total == 0 || weight_sum == 0.0 {
+            return Ok(vec![0.0; total]);
+        }
+        let scale = self.safe_amplitude() / weight_sum;
+
+        let (fade_in, fade_out) = self.fade_samples(total, rate);
+
+        Ok((0..total)
+            .map(|index| {
+                // Phase from the exact sample index avoids accumulated drift
+                let t = index as f64 / rate;
+                let sample: f64 = partials
+                    .iter()
+                    .map(|p| p.amplitude as f64 * (TAU * (p.frequency * t).fract()).sin())
+                    .sum();
+                (sample as f32) * scale * fade_gain(index, total, fade_in, fade_out)
+            })
+            .collect())
+    }
+
+    /// Render `duration` seconds to a mono 16-bit dithered WAV file
+    pub fn render_to_wav<P: AsRef<Path>>(&self, path: P, duration: f64, sample_rate: u32) -> Result<()> {
+        let settings = RenderSettings { sample_rate, channels: 1, ..RenderSettings::default() };
+        write_wav(path, &self.render(duration, sample_rate)?, &settings)
+    }
+
+    /// Play the tone live for `duration` seconds, one sine voice per partial
+    pub fn play(&self, controller: &SynthController, duration: f64) -> SynthResult<Vec<VoiceId>> {
+        let weight_sum: f32 = self.partials.iter().map(|p| p.amplitude.abs()).sum();
+        if weight_sum == 0.0 {
+            return Ok(Vec::new());
+        }
+        let scale = self.safe_amplitude() / weight_sum;
+
+        let envelope = AdsrSettings {
+            attack: self.fade_in.max(ND_SAFE_MIN_FADE_SECONDS) as f32,
+            decay: 0.0,
+            sustain: 1.0,
+            release: self.fade_out.max(ND_SAFE_MIN_FADE_SECONDS) as f32,
+        };
+
+        // Check every partial before starting any, so a bad one cannot leave others sounding
+        for partial in &self.partials {
+            crate::validate_frequency(partial.frequency).map_err(|e| SynthesizerError::InvalidNote(e.to_string()))?;
+        }
+
+        let mut voice_ids = Vec::with_capacity(self.partials.len());
+        for partial in &self.partials {
+            let settings = NoteSettings {
+                frequency: partial.frequency,
+                velocity: partial.amplitude * scale,
+                waveform: Waveform::Sine,
+                envelope,
+                pan: 0.0,
+            };
+            match controller.schedule_note(settings, 0.0, Some(duration)) {
+                Ok(voice_id) => voice_ids.push(voice_id),
+                Err(error) => {
+                    for voice_id in voice_ids {
+                        let _ = controller.note_off(voice_id);
+                    }
+                    return Err(error);
+                },
+            }
+        }
+        Ok(voice_ids)
+    }
+
+    /// Fade lengths in samples, never shorter than the ND-safe minimum
+    /// and never overlapping within the rendered length
+    fn fade_samples(&self, total: usize, rate: f64) -> (usize, usize) {
+        let min_fade = ND_SAFE_MIN_FADE_SECONDS * rate;
+        let fade_in = (self.fade_in * rate).max(min_fade).round() as usize;
+        let fade_out = (self.fade_out * rate).max(min_fade).round() as usize;
+
+        if fade_in + fade_out > total {
+            let fade_in_share = fade_in * total / (fade_in + fade_out);
+            (fade_in_share, total - fade_in_share)
+        } else {
+            (fade_in, fade_out)
+        }
+    }
+}
+
+/// Solfeggio frequency assigned to a Codex 144:99 node
+pub fn codex_node_frequency(node: u16) -> Result<f64> {
+    if node == 0 || node > CODEX_NODE_COUNT {
+        return Err(AudioEngineError::InvalidCodexNode(node));
+    }
+    Ok(SOLFEGGIO_FREQUENCIES[(node as usize - 1) % SOLFEGGIO_FREQUENCIES.len()])
+}
+
+/// Repeated digit sum, e.g. 144 -> 9
+fn digital_root(value: u32) -> u32 {
+    if value == 0 {
+        0
+    } else {
+        1 + (value - 1) % 9
+    }
+}
+
+/// Raised-cosine fade gain for a sample index
+fn fade_gain(index: usize, total: usize, fade_in: usize, fade_out: usize) -> f32 {
+    let ramp = |position: usize, length: usize| -> f32 {
+        let x = position as f64 / length as f64;
+        (0.5 - 0.5 * (std::f64::consts::PI * x).cos()) as f32
+    };
+
+    if index < fade_in {
+        ramp(index, fade_in)
+    } else if index >= total - fade_out {
+        ramp(total - 1 - index, fade_out)
+    } else {
+        1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codex_node_mapping() {
+        assert_eq!(codex_node_frequency(1).unwrap(), 396.0);
+        assert_eq!(codex_node_frequency(7).unwrap(), 963.0);
+        assert_eq!(codex_node_frequency(8).unwrap(), 396.0);
+        assert!(codex_node_frequency(0).is_err());
+        assert!(codex_node_frequency(145).is_err());
+
+        let tone = ToneSpec::for_codex_node(144).unwrap();
+        assert_eq!(tone.partials.len(), 9);
+        assert_eq!(tone.partials[2].frequency, tone.fundamental * 3.0);
+    }
+
+    #[test]
+    fn test_render_is_faded_and_nd_safe() {
+        let tone = ToneSpec::harmonic_series(528.0, 5, HarmonicRolloff::Flat)
+            .unwrap()
+            .with_amplitude(1.0)
+            .with_fades(0.0, 0.0);
+        let samples = tone.render(0.5, 48000).unwrap();
+
+        assert_eq!(samples.len(), 24000);
+        assert_eq!(samples[0], 0.0);
+        assert!(samples[samples.len() - 1].abs() < 1e-6);
+        assert!(samples.iter().all(|s| s.abs() <= ND_SAFE_MAX_AMPLITUDE + 1e-6));
+        assert!(samples.iter().any(|s| s.abs() > 0.1));
+    }
+
+    #[test]
+    fn test_render_duration_is_bounded() {
+        let tone = ToneSpec::solfeggio(528.0).unwrap();
+        for duration in [f64::NAN, f64::INFINITY, MAX_TONE_SECONDS + 1.0, 1e300] {
+            assert!(matches!(tone.render(duration, 48000), Err(AudioEngineError::RenderError(_))));
+        }
+        assert!(tone.render(-1.0, 48000).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_harmonics_above_audible_range_dropped() {
+        let tone = ToneSpec::harmonic_series(963.0, 40, HarmonicRolloff::Inverse).unwrap();
+        assert_eq!(tone.partials.len(), 20);
+
+        let tone = ToneSpec::harmonic_series(20.0, u32::MAX, HarmonicRolloff::Flat).unwrap();
+        assert_eq!(tone.partials.len(), 1000);
+        assert_eq!(tone.partials.last().unwrap().frequency, 20_000.0);
+    }
+
+    #[test]
+    fn test_play_with_invalid_partial_starts_nothing() {
+        let (controller, mut engine) = crate::synthesis::synth_channel(48000, 1);
+        let mut tone = ToneSpec::harmonic_series(528.0, 3, HarmonicRolloff::Inverse).unwrap();
+        tone.partials[2].frequency = f64::NAN;
+
+        assert!(tone.play(&controller, 1.0).is_err());
+        let mut buffer = vec![0.0; 256];
+        engine.render(&mut buffer);
+        assert!(engine.is_idle());
+    }
+}
@@ -0,0 +1,263 @@
+/*!
+ * VOICES AND VOICE ALLOCATION
+ *
+ * Polyphonic voice pool for the real-time synthesis engine. When every
+ * voice is busy the oldest one is stolen: it fades out over the ND-safe
+ * minimum fade while the new note starts, so stealing never clicks.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::{AdsrEnvelope, AdsrSettings, Oscillator, Waveform};
+use crate::constants::ND_SAFE_MIN_FADE_SECONDS;
+
+/// Identifier handed out for each triggered note
+pub type VoiceId = u64;
+
+/// Default polyphony for the real-time engine
+pub const DEFAULT_MAX_VOICES: usize = 32;
+
+/// Parameters for starting a note
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoteSettings {
+    pub frequency: f64,
+    pub velocity: f32,
+    pub waveform: Waveform,
+    pub envelope: AdsrSettings,
+    /// Stereo position (-1.0 left to 1.0 right)
+    pub pan: f32,
+}
+
+impl NoteSettings {
+    pub fn new(frequency: f64, velocity: f32) -> Self {
+        Self {
+            frequency,
+            velocity,
+            waveform: Waveform::default(),
+            envelope: AdsrSettings::default(),
+            pan: 0.0,
+        }
+    }
+}
+
+/// One sounding note: oscillator shaped by an envelope
+#[derive(Debug, Clone)]
+pub struct Voice {
+    pub id: VoiceId,
+    oscillator: Oscillator,
+    envelope: AdsrEnvelope,
+    velocity: f32,
+    pan: f32,
+    /// Samples remaining before an automatic note-off
+    remaining_samples: Option<u64>,
+    started_at: u64,
+    /// (samples left, fade length) once the voice has been stolen
+    steal_fade: Option<(u32, u32)>,
+}
+
+impl Voice {
+    pub fn new(id: VoiceId, settings: NoteSettings, sample_rate: u32, started_at: u64) -> Self {
+        let mut envelope = AdsrEnvelope::new(settings.envelope, sample_rate);
+        envelope.note_on();
+
+        Self {
+            id,
+            oscillator: Oscillator::new(settings.waveform, settings.frequency, sample_rate),
+            envelope,
+            velocity: settings.velocity.clamp(0.0, 1.0),
+            pan: settings.pan.clamp(-1.0, 1.0),
+            remaining_samples: None,
+            started_at,
+            steal_fade: None,
+        }
+    }
+
+    /// Release the note automatically after `samples`
+    pub fn with_duration(mut self, samples: u64) -> Self {
+        self.remaining_samples = Some(samples);
+        self
+    }
+
+    pub fn release(&mut self) {
+        self.remaining_samples = None;
+        self.envelope.note_off();
+    }
+
+    /// Fade out linearly over `fade_samples`, whatever the envelope is doing
+    pub fn steal(&mut self, fade_samples: u32) {
+        let fade_samples = fade_samples.max(1);
+        self.steal_fade = Some((fade_samples, fade_samples));
+    }
+
+    pub fn is_stolen(&self) -> bool {
+        self.steal_fade.is_some()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.envelope.is_finished() || matches!(self.steal_fade, Some((0, _)))
+    }
+
+    /// Render the next sample as a (left, right) pair
+    pub fn next_frame(&mut self) -> (f32, f32) {
+        if let Some(remaining) = self.remaining_samples.as_mut() {
+            if *remaining == 0 {
+                self.release();
+            } else {
+                *remaining -= 1;
+            }
+        }
+
+        let mut sample = self.oscillator.next_sample() * self.envelope.next_sample() * self.velocity;
+        if let Some((remaining, length)) = self.steal_fade.as_mut() {
+            sample *= *remaining as f32 / *length as f32;
+            *remaining = remaining.saturating_sub(1);
+        }
+
+        // Equal-power panning
+        let angle = (self.pan + 1.0) * std::f32::consts::FRAC_PI_4;
+        (sample * angle.cos(), sample * angle.sin())
+    }
+}
+
+/// Fixed-size pool of voices with oldest-first stealing.
+///
+/// Stolen voices keep sounding while they fade, so the pool holds up to
+/// twice `max_voices`; only past that is a fading voice cut outright.
+#[derive(Debug, Clone)]
+pub struct VoiceAllocator {
+    voices: Vec<Voice>,
+    max_voices: usize,
+    sample_rate: u32,
+}
+
+impl VoiceAllocator {
+    pub fn new(max_voices: usize, sample_rate: u32) -> Self {
+        let max_voices = max_voices.max(1);
+        Self {
+            voices: Vec::with_capacity(max_voices * 2),
+            max_voices,
+            sample_rate,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn active_voices(&self) -> usize {
+        self.voices.len()
+    }
+
+    /// Start a voice, fading out the oldest one if the pool is full
+    pub fn note_on(&mut self, id: VoiceId, settings: NoteSettings, duration_samples: Option<u64>, now: u64) {
+        let sounding = self.voices.iter().filter(|voice| !voice.is_stolen()).count();
+        if sounding >= self.max_voices {
+            let fade_samples = (ND_SAFE_MIN_FADE_SECONDS * self.sample_rate as f64).round() as u32;
+            if let Some(oldest) = self
+                .voices
+                .iter_mut()
+                .filter(|voice| !voice.is_stolen())
+                .min_by_key(|voice| voice.started_at)
+            {
+                oldest.steal(fade_samples);
+            }
+        }
+
+        // Only a flood of notes faster than the fade fills the fading half of the pool
+        if self.voices.len() >= self.max_voices * 2 {
+            if let Some(quietest) = self
+                .voices
+                .iter()
+                .enumerate()
+                .filter_map(|(index, voice)| voice.steal_fade.map(|(remaining, _)| (index, remaining)))
+                .min_by_key(|(_, remaining)| *remaining)
+                .map(|(index, _)| index)
+            {
+                self.voices.swap_remove(quietest);
+            }
+        }
+
+        let mut voice = Voice::new(id, settings, self.sample_rate, now);
+        if let Some(samples) = duration_samples {
+            voice = voice.with_duration(samples);
+        }
+        self.voices.push(voice);
+    }
+
+    pub fn note_off(&mut self, id: VoiceId) {
+        if let Some(voice) = self.voices.iter_mut().find(|voice| voice.id == id) {
+            voice.release();
+        }
+    }
+
+    pub fn all_notes_off(&mut self) {
+        for voice in &mut self.voices {
+            voice.release();
+        }
+    }
+
+    /// Mix every voice into an interleaved buffer with `channels` channels
+    pub fn render(&mut self, output: &mut [f32], channels: usize, gain: f32) {
+        let channels = channels.max(1);
+
+        for frame in output.chunks_mut(channels) {
+            let (mut left, mut right) = (0.0, 0.0);
+            for voice in &mut self.voices {
+                let (l, r) = voice.next_frame();
+                left += l;
+                right += r;
+            }
+
+            match frame.len() {
+                1 => frame[0] = (left + right) * 0.5 * gain,
+                _ => {
+                    frame[0] = left * gain;
+                    frame[1] = right * gain;
+                    for extra in &mut frame[2..] {
+                        *extra = 0.0;
+                    }
+                },
+            }
+        }
+
+        self.voices.retain(|voice| !voice.is_finished());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stolen_voice_fades_instead_of_cutting() {
+        let peak = |samples: &[f32]| samples.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        let mut allocator = VoiceAllocator::new(1, 48000);
+        allocator.note_on(1, NoteSettings::new(440.0, 1.0), None, 0);
+        let mut buffer = vec![0.0; 4800];
+        allocator.render(&mut buffer, 1, 1.0);
+        let sustained = peak(&buffer[4600..]);
+
+        // A silent note steals the only voice, so what remains is the fade
+        allocator.note_on(2, NoteSettings::new(440.0, 0.0), None, 4800);
+        assert_eq!(allocator.active_voices(), 2);
+        let fade = (ND_SAFE_MIN_FADE_SECONDS * 48000.0) as usize;
+        let mut tail = vec![0.0; fade];
+        allocator.render(&mut tail, 1, 1.0);
+
+        assert!(peak(&tail[..200]) > sustained * 0.8);
+        assert!(peak(&tail[fade / 2 - 100..fade / 2 + 100]) < sustained * 0.6);
+        assert!(peak(&tail[fade - 50..]) < sustained * 0.05);
+        assert_eq!(allocator.active_voices(), 1);
+    }
+
+    #[test]
+    fn test_note_flood_stays_within_twice_the_pool() {
+        let mut allocator = VoiceAllocator::new(4, 48000);
+        for id in 0..100 {
+            allocator.note_on(id, NoteSettings::new(440.0, 1.0), None, id);
+        }
+        assert_eq!(allocator.active_voices(), 8);
+        assert_eq!(allocator.voices.iter().filter(|voice| !voice.is_stolen()).count(), 4);
+    }
+}
@@ -0,0 +1,252 @@
+/*!
+ * CHORDS
+ *
+ * Chord qualities, rooted chords with inversions and transposition, and
+ * diatonic chords built by stacking thirds on a scale degree.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::Scale;
+use crate::pattern::midi_to_frequency;
+use crate::synthesis::{NoteSettings, SynthController, SynthResult, SynthesizerError, VoiceId};
+use serde::{Deserialize, Serialize};
+
+/// Pitch-class names, spelled with sharps
+pub const PITCH_CLASS_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+/// Chord quality, defined by its semitone offsets from the root
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ChordQuality {
+    Major,
+    Minor,
+    Diminished,
+    Augmented,
+    Suspended2,
+    Suspended4,
+    Major7,
+    Dominant7,
+    Minor7,
+    HalfDiminished7,
+    Diminished7,
+}
+
+impl ChordQuality {
+    pub const ALL: [ChordQuality; 11] = [
+        ChordQuality::Major,
+        ChordQuality::Minor,
+        ChordQuality::Diminished,
+        ChordQuality::Augmented,
+        ChordQuality::Suspended2,
+        ChordQuality::Suspended4,
+        ChordQuality::Major7,
+        ChordQuality::Dominant7,
+        ChordQuality::Minor7,
+        ChordQuality::HalfDiminished7,
+        ChordQuality::Diminished7,
+    ];
+
+    pub fn intervals(&self) -> &'static [u8] {
+        match self {
+            ChordQuality::Major => &[0, 4, 7],
+            ChordQuality::Minor => &[0, 3, 7],
+            ChordQuality::Diminished => &[0, 3, 6],
+            ChordQuality::Augmented => &[0, 4, 8],
+            ChordQuality::Suspended2 => &[0, 2, 7],
+            ChordQuality::Suspended4 => &[0, 5, 7],
+            ChordQuality::Major7 => &[0, 4, 7, 11],
+            ChordQuality::Dominant7 => &[0, 4, 7, 10],
+            ChordQuality::Minor7 => &[0, 3, 7, 10],
+            ChordQuality::HalfDiminished7 => &[0, 3, 6, 10],
+            ChordQuality::Diminished7 => &[0, 3, 6, 9],
+        }
+    }
+
+    /// Quality whose offsets exactly match `intervals`
+    pub fn from_intervals(intervals: &[u8]) -> Option<Self> {
+        Self::ALL.into_iter().find(|quality| quality.intervals() == intervals)
+    }
+
+    /// Lead-sheet suffix ("" for major, "m7b5" for half-diminished)
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            ChordQuality::Major => "",
+            ChordQuality::Minor => "m",
+            ChordQuality::Diminished => "dim",
+            ChordQuality::Augmented => "aug",
+            ChordQuality::Suspended2 => "sus2",
+            ChordQuality::Suspended4 => "sus4",
+            ChordQuality::Major7 => "maj7",
+            ChordQuality::Dominant7 => "7",
+            ChordQuality::Minor7 => "m7",
+            ChordQuality::HalfDiminished7 => "m7b5",
+            ChordQuality::Diminished7 => "dim7",
+        }
+    }
+}
+
+/// A chord in root position on a MIDI note
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chord {
+    /// MIDI note number of the root
+    pub root: i32,
+    pub quality: ChordQuality,
+}
+
+impl Chord {
+    pub fn new(root: i32, quality: ChordQuality) -> Self {
+        Self { root, quality }
+    }
+
+    /// MIDI notes in root position, ascending
+    pub fn notes(&self) -> Vec<i32> {
+        self.quality.intervals().iter().map(|interval| self.root + *interval as i32).collect()
+    }
+
+    /// MIDI notes with the lowest note raised an octave, `inversion` times
+    pub fn inversion(&self, inversion: usize) -> Vec<i32> {
+        let mut notes = self.notes();
+        for _ in 0..inversion {
+            notes[0] += 12;
+            notes.sort_unstable();
+        }
+        notes
+    }
+
+    /// Equal-tempered frequencies of the root-position notes
+    pub fn frequencies(&self) -> Vec<f64> {
+        self.notes().into_iter().map(|note| midi_to_frequency(note as f64)).collect()
+    }
+
+    /// Play the chord live, one voice per note with `template`'s other settings,
+    /// releasing after `duration` seconds or held until `note_off` when `None`.
+    ///
+    /// Every note is checked before any starts and started voices are released
+    /// if a later one fails, so an error never leaves part of the chord sounding.
+    pub fn play(
+        &self,
+        controller: &SynthController,
+        template: NoteSettings,
+        duration: Option<f64>,
+    ) -> SynthResult<Vec<VoiceId>> {
+        let frequencies = self.frequencies();
+        for frequency in &frequencies {
+            crate::validate_frequency(*frequency).map_err(|e| SynthesizerError::InvalidNote(e.to_string()))?;
+        }
+
+        let mut voice_ids = Vec::with_capacity(frequencies.len());
+        for frequency in frequencies {
+            match controller.schedule_note(NoteSettings { frequency, ..template }, 0.0, duration) {
+                Ok(voice_id) => voice_ids.push(voice_id),
+                Err(error) => {
+                    for voice_id in voice_ids {
+                        let _ = controller.note_off(voice_id);
+                    }
+                    return Err(error);
+                },
+            }
+        }
+        Ok(voice_ids)
+    }
+
+    pub fn transpose(&self, semitones: i32) -> Self {
+        Self { root: self.root + semitones, quality: self.quality }
+    }
+
+    /// Lead-sheet name such as "F#m7"
+    pub fn name(&self) -> String {
+        format!("{}{}", PITCH_CLASS_NAMES[self.root.rem_euclid(12) as usize], self.quality.suffix())
+    }
+}
+
+impl Scale {
+    /// `size` scale tones stacked in thirds on `degree` (3 for a triad, 4 for a seventh chord);
+    /// `None` if a tone overflows `i32`
+    pub fn stacked_thirds(&self, degree: i32, size: usize) -> Option<Vec<i32>> {
+        (0..size as i32)
+            .map(|step| degree.checked_add(step * 2).and_then(|degree| self.degree_to_midi(degree)))
+            .collect()
+    }
+
+    /// Diatonic triad on a degree; `None` if the stacked tones form no known quality
+    pub fn triad(&self, degree: i32) -> Option<Chord> {
+        self.diatonic_chord(degree, 3)
+    }
+
+    /// Diatonic seventh chord on a degree; `None` if the stacked tones form no known quality
+    pub fn seventh(&self, degree: i32) -> Option<Chord> {
+        self.diatonic_chord(degree, 4)
+    }
+
+    fn diatonic_chord(&self, degree: i32, size: usize) -> Option<Chord> {
+        let notes = self.stacked_thirds(degree, size)?;
+        let root = notes[0];
+        let intervals: Vec<u8> = notes.iter().map(|note| (note - root) as u8).collect();
+        ChordQuality::from_intervals(&intervals).map(|quality| Chord::new(root, quality))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_major_scale_diatonic_triads_and_sevenths() {
+        let scale = Scale::major(60);
+        let triads: Vec<String> = (0..7).map(|degree| scale.triad(degree).unwrap().name()).collect();
+        assert_eq!(triads, vec!["C", "Dm", "Em", "F", "G", "Am", "Bdim"]);
+
+        let sevenths: Vec<String> = (0..7).map(|degree| scale.seventh(degree).unwrap().name()).collect();
+        assert_eq!(sevenths, vec!["Cmaj7", "Dm7", "Em7", "Fmaj7", "G7", "Am7", "Bm7b5"]);
+
+        assert_eq!(scale.triad(4).unwrap().notes(), vec![67, 71, 74]);
+        assert_eq!(scale.triad(-1).unwrap().root, 59);
+        assert_eq!(scale.triad(i32::MAX), None);
+        assert_eq!(scale.seventh(i32::MAX - 1), None);
+    }
+
+    #[test]
+    fn test_inversion_and_transpose() {
+        let chord = Chord::new(60, ChordQuality::Major);
+        assert_eq!(chord.inversion(1), vec![64, 67, 72]);
+        assert_eq!(chord.inversion(2), vec![67, 72, 76]);
+        assert_eq!(chord.inversion(3), vec![72, 76, 79]);
+
+        let transposed = chord.transpose(-3);
+        assert_eq!(transposed.name(), "A");
+        assert!((transposed.frequencies()[0] - 220.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_pentatonic_stacks_have_no_quality() {
+        let pentatonic = Scale::new(60, vec![0, 2, 4, 7, 9]).unwrap();
+        assert_eq!(pentatonic.stacked_thirds(0, 3), Some(vec![60, 64, 69]));
+        assert_eq!(pentatonic.triad(0), None);
+    }
+
+    #[test]
+    fn test_play_is_all_or_nothing() {
+        let (controller, mut engine) = crate::synthesis::synth_channel(48000, 1);
+        let template = NoteSettings::new(440.0, 0.5);
+
+        // The root (MIDI 2) is below 20 Hz, so no voice may start
+        let low = Chord::new(2, ChordQuality::Major);
+        assert!(matches!(low.play(&controller, template, None), Err(SynthesizerError::InvalidNote(_))));
+        let mut buffer = vec![0.0; 256];
+        engine.render(&mut buffer);
+        assert!(engine.is_idle());
+
+        let held = Chord::new(60, ChordQuality::Minor).play(&controller, template, None).unwrap();
+        assert_eq!(held.len(), 3);
+        engine.render(&mut buffer);
+        assert_eq!(engine.active_voices(), 3);
+
+        for voice_id in held {
+            controller.note_off(voice_id).unwrap();
+        }
+        let mut tail = vec![0.0; 48000];
+        engine.render(&mut tail);
+        assert!(engine.is_idle());
+    }
+}
@@ -0,0 +1,150 @@
+/*!
+ * PLANETARY & ELEMENTAL CORRESPONDENCES
+ *
+ * Maps the seven classical planets and four elements onto modes.
+ *
+ * Planets follow Bartolomeo Ramos de Pareja (Musica practica, 1482),
+ * who assigned one of the eight Greek modes to each sphere. His plagal
+ * modes are given here by their octave species in modern names
+ * (Hypodorian = Aeolian, Hypophrygian = Locrian, Hypolydian = Ionian),
+ * which makes the mapping one-to-one over the seven modes.
+ *
+ * Elements follow the four temperaments and their ruling planets
+ * (choleric fire / Mars, phlegmatic water / Moon, sanguine air /
+ * Jupiter, melancholic earth / Saturn), so fire resolves to Phrygian
+ * as in the Volcanic Fire spell.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::{Mode, Scale};
+use serde::{Deserialize, Serialize};
+
+/// The seven classical planets, in Chaldean order from the Moon outwards
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Planet {
+    Moon,
+    Mercury,
+    Venus,
+    Sun,
+    Mars,
+    Jupiter,
+    Saturn,
+}
+
+impl Planet {
+    pub const ALL: [Planet; 7] = [
+        Planet::Moon,
+        Planet::Mercury,
+        Planet::Venus,
+        Planet::Sun,
+        Planet::Mars,
+        Planet::Jupiter,
+        Planet::Saturn,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "moon" | "luna" => Some(Self::Moon),
+            "mercury" => Some(Self::Mercury),
+            "venus" => Some(Self::Venus),
+            "sun" | "sol" => Some(Self::Sun),
+            "mars" => Some(Self::Mars),
+            "jupiter" => Some(Self::Jupiter),
+            "saturn" => Some(Self::Saturn),
+            _ => None,
+        }
+    }
+
+    /// Mode of the planet's sphere (Ramos de Pareja)
+    pub fn mode(&self) -> Mode {
+        match self {
+            Planet::Moon => Mode::Aeolian,
+            Planet::Mercury => Mode::Locrian,
+            Planet::Venus => Mode::Ionian,
+            Planet::Sun => Mode::Dorian,
+            Planet::Mars => Mode::Phrygian,
+            Planet::Jupiter => Mode::Lydian,
+            Planet::Saturn => Mode::Mixolydian,
+        }
+    }
+
+    pub fn scale(&self, root: u8) -> Scale {
+        self.mode().scale(root)
+    }
+}
+
+/// The four classical elements
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Element {
+    Fire,
+    Water,
+    Air,
+    Earth,
+}
+
+impl Element {
+    pub const ALL: [Element; 4] = [Element::Fire, Element::Water, Element::Air, Element::Earth];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "fire" => Some(Self::Fire),
+            "water" => Some(Self::Water),
+            "air" => Some(Self::Air),
+            "earth" => Some(Self::Earth),
+            _ => None,
+        }
+    }
+
+    /// Planet ruling the element's temperament
+    pub fn planet(&self) -> Planet {
+        match self {
+            Element::Fire => Planet::Mars,
+            Element::Water => Planet::Moon,
+            Element::Air => Planet::Jupiter,
+            Element::Earth => Planet::Saturn,
+        }
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.planet().mode()
+    }
+
+    pub fn scale(&self, root: u8) -> Scale {
+        self.mode().scale(root)
+    }
+}
+
+/// Mode for a planet, element or mode name, in that order of precedence
+pub fn mode_for_correspondence(name: &str) -> Option<Mode> {
+    Planet::from_name(name)
+        .map(|planet| planet.mode())
+        .or_else(|| Element::from_name(name).map(|element| element.mode()))
+        .or_else(|| Mode::from_name(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_each_planet_has_its_own_mode() {
+        let modes: HashSet<Mode> = Planet::ALL.iter().map(|planet| planet.mode()).collect();
+        assert_eq!(modes.len(), 7);
+        for planet in Planet::ALL {
+            assert_eq!(Planet::from_name(&format!("{:?}", planet)), Some(planet));
+        }
+    }
+
+    #[test]
+    fn test_fire_is_phrygian_through_mars() {
+        assert_eq!(Element::Fire.mode(), Mode::Phrygian);
+        assert_eq!(Element::Fire.scale(64).degree_to_midi(1), Some(65));
+        assert_eq!(mode_for_correspondence("Fire"), Some(Mode::Phrygian));
+        assert_eq!(mode_for_correspondence("moon"), Some(Mode::Aeolian));
+        assert_eq!(mode_for_correspondence("dorian"), Some(Mode::Dorian));
+        assert_eq!(mode_for_correspondence("aether"), None);
+    }
+}
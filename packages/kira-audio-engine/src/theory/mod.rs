@@ -0,0 +1,25 @@
+/*!
+ * MUSIC THEORY
+ *
+ * Scales, modes, chords and pitch resolution shared by pattern playback,
+ * MIDI export and gameplay music generation.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ *
+ * Features:
+ * - Rooted scales with degree lookup, quantization and transposition
+ * - The seven diatonic modes
+ * - Triads and seventh chords, diatonic or by quality
+ * - Planetary and elemental mode correspondences
+ */
+
+mod chord;
+mod correspondence;
+mod mode;
+mod scale;
+
+pub use chord::*;
+pub use correspondence::*;
+pub use mode::*;
+pub use scale::*;
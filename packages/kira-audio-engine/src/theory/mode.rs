@@ -0,0 +1,114 @@
+/*!
+ * MODES
+ *
+ * The seven diatonic church modes, each a rotation of the major scale.
+ * Ionian and Aeolian are the familiar major and natural minor.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use super::{Scale, MAJOR_INTERVALS};
+use serde::{Deserialize, Serialize};
+
+/// A diatonic mode, in the order of the major-scale degree it starts on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Mode {
+    Ionian,
+    Dorian,
+    Phrygian,
+    Lydian,
+    Mixolydian,
+    Aeolian,
+    Locrian,
+}
+
+impl Mode {
+    pub const ALL: [Mode; 7] = [
+        Mode::Ionian,
+        Mode::Dorian,
+        Mode::Phrygian,
+        Mode::Lydian,
+        Mode::Mixolydian,
+        Mode::Aeolian,
+        Mode::Locrian,
+    ];
+
+    /// Accepts mode names plus the aliases "major" and "minor"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "ionian" | "major" => Some(Self::Ionian),
+            "dorian" => Some(Self::Dorian),
+            "phrygian" => Some(Self::Phrygian),
+            "lydian" => Some(Self::Lydian),
+            "mixolydian" => Some(Self::Mixolydian),
+            "aeolian" | "minor" | "natural_minor" | "natural minor" => Some(Self::Aeolian),
+            "locrian" => Some(Self::Locrian),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Ionian => "ionian",
+            Mode::Dorian => "dorian",
+            Mode::Phrygian => "phrygian",
+            Mode::Lydian => "lydian",
+            Mode::Mixolydian => "mixolydian",
+            Mode::Aeolian => "aeolian",
+            Mode::Locrian => "locrian",
+        }
+    }
+
+    /// Major-scale degree (0-6) the mode starts on
+    pub fn rotation(&self) -> usize {
+        *self as usize
+    }
+
+    /// Semitone offsets from the mode's own tonic
+    pub fn intervals(&self) -> [u8; 7] {
+        let start = MAJOR_INTERVALS[self.rotation()];
+        let mut intervals = [0; 7];
+        for (step, interval) in intervals.iter_mut().enumerate() {
+            let major = MAJOR_INTERVALS[(self.rotation() + step) % 7];
+            *interval = (major + 12 - start) % 12;
+        }
+        intervals
+    }
+
+    /// The mode rooted on a MIDI note
+    pub fn scale(&self, root: u8) -> Scale {
+        Scale { root, intervals: self.intervals().to_vec() }
+    }
+
+    /// Whether the tonic triad has a major third
+    pub fn is_major(&self) -> bool {
+        self.intervals()[2] == 4
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theory::NATURAL_MINOR_INTERVALS;
+
+    #[test]
+    fn test_mode_intervals_rotate_the_major_scale() {
+        assert_eq!(Mode::Ionian.intervals(), MAJOR_INTERVALS);
+        assert_eq!(Mode::Aeolian.intervals(), NATURAL_MINOR_INTERVALS);
+        assert_eq!(Mode::Dorian.intervals(), [0, 2, 3, 5, 7, 9, 10]);
+        assert_eq!(Mode::Phrygian.intervals(), [0, 1, 3, 5, 7, 8, 10]);
+        assert_eq!(Mode::Lydian.intervals(), [0, 2, 4, 6, 7, 9, 11]);
+        assert_eq!(Mode::Mixolydian.intervals(), [0, 2, 4, 5, 7, 9, 10]);
+        assert_eq!(Mode::Locrian.intervals(), [0, 1, 3, 5, 6, 8, 10]);
+    }
+
+    #[test]
+    fn test_mode_names_round_trip() {
+        for mode in Mode::ALL {
+            assert_eq!(Mode::from_name(mode.name()), Some(mode));
+        }
+        assert_eq!(Mode::from_name("Minor"), Some(Mode::Aeolian));
+        assert_eq!(Mode::from_name("hypodorian"), None);
+    }
+}
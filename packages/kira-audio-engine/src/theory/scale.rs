@@ -0,0 +1,119 @@
+/*!
+ * SCALES
+ *
+ * A scale is a root MIDI note plus semitone offsets within one octave.
+ * Scale degrees wrap into neighbouring octaves, so degree 7 of a
+ * seven-note scale is the root an octave up and degree -1 is the
+ * leading tone below the root.
+ *
+ * Author: Rebecca Respawn (International Reiki Master)
+ * License: CC0 - Your Original Work
+ */
+
+use serde::{Deserialize, Serialize};
+
+/// Semitone offsets of the major scale
+pub const MAJOR_INTERVALS: [u8; 7] = [0, 2, 4, 5, 7, 9, 11];
+
+/// Semitone offsets of the natural minor scale
+pub const NATURAL_MINOR_INTERVALS: [u8; 7] = [0, 2, 3, 5, 7, 8, 10];
+
+/// A rooted scale
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Scale {
+    /// MIDI note number of degree 0
+    pub root: u8,
+    /// Ascending semitone offsets from the root, starting at 0, all below 12
+    pub intervals: Vec<u8>,
+}
+
+impl Scale {
+    /// Create a scale; returns `None` unless intervals start at 0, ascend and stay within the octave
+    pub fn new(root: u8, intervals: Vec<u8>) -> Option<Self> {
+        let valid = intervals.first() == Some(&0)
+            && intervals.windows(2).all(|pair| pair[0] < pair[1])
+            && intervals.iter().all(|interval| *interval < 12);
+
+        valid.then_some(Self { root, intervals })
+    }
+
+    pub fn major(root: u8) -> Self {
+        Self { root, intervals: MAJOR_INTERVALS.to_vec() }
+    }
+
+    pub fn natural_minor(root: u8) -> Self {
+        Self { root, intervals: NATURAL_MINOR_INTERVALS.to_vec() }
+    }
+
+    /// MIDI note for a scale degree, wrapping across octaves; `None` if the note overflows `i32`
+    pub fn degree_to_midi(&self, degree: i32) -> Option<i32> {
+        let size = self.intervals.len() as i32;
+        let octave = degree.div_euclid(size);
+        let step = degree.rem_euclid(size) as usize;
+        octave
+            .checked_mul(12)?
+            .checked_add(self.root as i32 + self.intervals[step] as i32)
+    }
+
+    /// Scale degree of a MIDI note (inverse of `degree_to_midi`), or `None` if it is not a scale tone
+    pub fn degree_of(&self, note: i32) -> Option<i32> {
+        let offset = note - self.root as i32;
+        let step = self.intervals.iter().position(|interval| *interval as i32 == offset.rem_euclid(12))?;
+        Some(offset.div_euclid(12) * self.intervals.len() as i32 + step as i32)
+    }
+
+    pub fn contains(&self, note: i32) -> bool {
+        self.degree_of(note).is_some()
+    }
+
+    /// Nearest scale tone to a MIDI note; ties resolve downward
+    pub fn quantize(&self, note: i32) -> i32 {
+        (0..12)
+            .flat_map(|distance| [note - distance, note + distance])
+            .find(|candidate| self.contains(*candidate))
+            .unwrap_or(note)
+    }
+
+    /// The same scale moved by `semitones`, with the root clamped to the MIDI range
+    pub fn transpose(&self, semitones: i32) -> Self {
+        Self {
+            root: (self.root as i32 + semitones).clamp(0, 127) as u8,
+            intervals: self.intervals.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_degree_of_inverts_degree_to_midi() {
+        let scale = Scale::natural_minor(57);
+        for degree in -10..20 {
+            assert_eq!(scale.degree_of(scale.degree_to_midi(degree).unwrap()), Some(degree));
+        }
+        assert_eq!(scale.degree_of(58), None);
+    }
+
+    #[test]
+    fn test_extreme_degrees_do_not_overflow() {
+        let scale = Scale::major(127);
+        assert_eq!(scale.degree_to_midi(i32::MAX), None);
+        assert_eq!(scale.degree_to_midi(i32::MIN), None);
+        assert_eq!(Scale::major(60).degree_to_midi(-35), Some(0));
+    }
+
+    #[test]
+    fn test_quantize_and_transpose() {
+        let scale = Scale::major(60);
+        assert_eq!(scale.quantize(61), 60);
+        assert_eq!(scale.quantize(66), 65);
+        assert_eq!(scale.quantize(64), 64);
+
+        let up = scale.transpose(7);
+        assert_eq!(up.root, 67);
+        assert!(up.contains(66));
+        assert!(!up.contains(65));
+    }
+}